@@ -43,6 +43,8 @@ export class GameServer
 			this.createGame();
 			this.startGame();
 			this.sendGameState();
+			this.listOngoingGames();
+			this.sendSpectatorState();
 		}
 		catch (error)
 		{
@@ -524,4 +526,136 @@ export class GameServer
 			}
 		});
 	}
+
+	/**
+	 * list the games that can be watched, a local game has a single player
+	*/
+	private listOngoingGames(): void
+	{
+		this.server.get('/api/game/ongoing',
+		{
+			schema:
+			{
+				rateLimit: rateLimitMed,
+				headers: tokenHeader
+			}
+		},
+		async (request: FastifyRequest, reply: FastifyReply) =>
+		{
+			try
+			{
+				const token = getToken(request.headers.authorization as string);
+				if (!token)
+					return reply.status(400).send({ error: 'missing authorization header' });
+
+				const data: any = await jwtVerif(token, core.sessionKey);
+				if (!data)
+				{
+					return reply.status(400).send({ error: 'Invalid token' });
+				}
+
+				const games: { gameId: string, mode: string | null, player1Id: number, player2Id: number }[] = [];
+				for (const [id, game] of this.activeGames)
+				{
+					if (game.mode !== 'local' && game.winner === null)
+					{
+						games.push({ gameId: id, mode: game.mode, player1Id: game.player1Id, player2Id: game.player2Id });
+					}
+				}
+				reply.status(200).send(games);
+			}
+			catch (error)
+			{
+				Logger.error('Error listing ongoing games:', error);
+				reply.status(500).send({ error });
+			}
+		});
+	}
+
+	/**
+	 * read-only stream of a game, every spectator gets the side 1 state
+	 * spectators are not players: their messages are ignored and leaving does not end the game
+	*/
+	private sendSpectatorState(): void
+	{
+		this.server.get('/api/spectate/:gameId', { websocket: true }, async (connection: any, request: FastifyRequest) =>
+		{
+			const refuse = (message: string): void =>
+			{
+				connection.send(JSON.stringify({ type: 'error', message }));
+				connection.close();
+			};
+
+			try
+			{
+				const token = request.cookies.jwt_session;
+				const data: any = token ? await jwtVerif(token, core.sessionKey) : null;
+				if (!data)
+				{
+					return refuse('Invalid token');
+				}
+
+				const { gameId } = request.params as { gameId: string };
+				const game = this.activeGames.get(gameId);
+
+				if (!game)
+				{
+					return refuse('Game not found');
+				}
+
+				if (game.mode === 'local')
+				{
+					return refuse('Local games cannot be watched');
+				}
+
+				if (game.winner !== null)
+				{
+					connection.send(JSON.stringify({ type: 'winner', winner: game.winner }));
+					connection.close();
+					return ;
+				}
+
+				Logger.log(`${await getUserName(data.id)} is watching game ${gameId}`);
+
+				const send = () =>
+				{
+					// both players left before the end, the game was removed without a winner
+					if (this.activeGames.get(gameId) !== game && game.winner === null)
+					{
+						clearInterval(interval);
+						refuse('The game was abandoned');
+						return ;
+					}
+
+					if (game.state)
+						connection.send(game.state);
+
+					if (game.winner !== null)
+					{
+						clearInterval(interval);
+						connection.send(JSON.stringify({ type: 'winner', winner: game.winner }));
+						connection.close();
+					}
+				};
+
+				const interval = setInterval(send, GameServer.FPS_INTERVAL);
+
+				connection.on('close', () =>
+				{
+					clearInterval(interval);
+				});
+
+				connection.on('error', () =>
+				{
+					Logger.error(`Spectator connection error for game ${gameId}`);
+					clearInterval(interval);
+				});
+			}
+			catch (error)
+			{
+				Logger.error('Error in spectator connection:', error);
+				connection.close();
+			}
+		});
+	}
 }
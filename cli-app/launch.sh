@@ -1,11 +1,16 @@
 #!/bin/bash
 if [[ "$*" == *"--repeat"* ]]; then
-    xset r rate 150 30                                                                     
-fi                                                                                         
-                                                                                           
-ARGS=$(echo "$*" | sed 's/--repeat//g' | xargs)                                            
-docker run --rm --name cli-pong -it cli:latest ./cli_app $ARGS                             
-                                                                                           
-if [[ "$*" == *"--repeat"* ]]; then                                                        
-    xset r rate 660 25                                                                     
+    xset r rate 150 30
+fi
+
+ARGS=$(echo "$*" | sed 's/--repeat//g' | xargs)
+if [[ -n "$ARGS" && "$ARGS" != -* ]]; then
+    ARGS="--server $ARGS"
+fi
+docker run --rm --name cli-pong -it -e PONG_SERVER -e PONG_PROFILE -e PONG_PIN -e PONG_PASSWORD -e PONG_LOG \
+    -v "${XDG_CONFIG_HOME:-$HOME/.config}/transcendence_cli:/root/.config/transcendence_cli:ro" \
+    -v "${XDG_DATA_HOME:-$HOME/.local/share}/transcendence_cli:/root/.local/share/transcendence_cli" cli:latest ./cli_app $ARGS
+
+if [[ "$*" == *"--repeat"* ]]; then
+    xset r rate 660 25
 fi
\ No newline at end of file
@@ -0,0 +1,676 @@
+use crate::context::Context;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use std::time::Instant;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+#[derive(Debug, Error)]
+pub(crate) enum ApiError {
+    #[error("{message} (HTTP {})", status.as_u16())]
+    Server { status: StatusCode, message: String },
+    #[error("unexpected response from {endpoint}: {source}")]
+    Decode {
+        endpoint: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("could not reach server: {}", root_cause(.0))]
+    Transport(#[from] reqwest::Error),
+}
+
+/// reqwest only says "error sending request", the reason (such as a rejected certificate) is deeper
+fn root_cause(error: &reqwest::Error) -> String {
+    let mut cause: &dyn std::error::Error = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+impl ApiError {
+    /// HTTP status returned by the server, if the request got that far
+    pub(crate) fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Server { status, .. } => Some(*status),
+            ApiError::Transport(e) => e.status(),
+            ApiError::Decode { .. } => None,
+        }
+    }
+}
+
+pub(crate) type ApiResult<T> = Result<T, ApiError>;
+
+/// Error bodies are either `{ message }`, `{ error }` or a bare string
+#[derive(Deserialize, Default)]
+struct ErrorBody {
+    message: Option<String>,
+    error: Option<String>,
+}
+
+fn server_message(status: StatusCode, body: &str) -> String {
+    if let Ok(parsed) = serde_json::from_str::<ErrorBody>(body)
+        && let Some(message) = parsed.message.or(parsed.error)
+    {
+        return message;
+    }
+    let body = body.trim();
+    if !body.is_empty() && !body.starts_with('{') {
+        return body.to_string();
+    }
+    status
+        .canonical_reason()
+        .unwrap_or("Server error")
+        .to_string()
+}
+
+/// Accepts `1`, `"1"`, `true` or `false` as the sqlite/fastify stack is not consistent
+fn flexible_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u64),
+        Text(String),
+        Flag(bool),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(n) => Ok(n),
+        Raw::Flag(b) => Ok(b as u64),
+        Raw::Text(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn flexible_u64_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    flexible_u64(deserializer).map(Some)
+}
+
+//
+// user
+//
+
+#[derive(Serialize)]
+pub(crate) struct SignupRequest<'a> {
+    pub(crate) username: &'a str,
+    pub(crate) passw: &'a str,
+    pub(crate) email: &'a str,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LoginRequest<'a> {
+    pub(crate) email: &'a str,
+    pub(crate) passw: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) totp: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TokenResponse {
+    pub(crate) token: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub(crate) struct Profile {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) elo: f64,
+    #[serde(default)]
+    pub(crate) games_played: u64,
+    #[serde(default)]
+    pub(crate) wins: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ProfileId {
+    pub(crate) id: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct MatchRecord {
+    #[serde(default)]
+    pub(crate) id: u64,
+    pub(crate) player1_id: u64,
+    pub(crate) player2_id: u64,
+    pub(crate) winner_id: u64,
+    #[serde(default)]
+    pub(crate) score1: u64,
+    #[serde(default)]
+    pub(crate) score2: u64,
+    #[serde(default)]
+    pub(crate) played_at: String,
+}
+
+//
+// friends
+//
+
+#[derive(Serialize)]
+struct FriendRequest {
+    friend_id: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct FriendLink {
+    pub(crate) user1_id: u64,
+    pub(crate) user2_id: u64,
+    #[serde(deserialize_with = "flexible_u64")]
+    pub(crate) pending: u64,
+}
+
+impl FriendLink {
+    /// The server stores both ids of a friendship, return the one that isn't ours
+    pub(crate) fn other(&self, me: u64) -> Option<u64> {
+        match (self.user1_id == me, self.user2_id == me) {
+            (true, false) => Some(self.user2_id),
+            (false, true) => Some(self.user1_id),
+            _ => None,
+        }
+    }
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending != 0
+    }
+}
+
+/// Row of the blocked users table, only the ones blocked by us are listed
+#[derive(Deserialize, Clone)]
+pub(crate) struct BlockedLink {
+    pub(crate) user1_id: u64,
+    pub(crate) user2_id: u64,
+}
+
+impl BlockedLink {
+    /// The server stores both ids of a block, return the one that isn't ours
+    pub(crate) fn other(&self, me: u64) -> Option<u64> {
+        match (self.user1_id == me, self.user2_id == me) {
+            (true, false) => Some(self.user2_id),
+            (false, true) => Some(self.user1_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub(crate) struct MessageResponse {
+    #[serde(default)]
+    pub(crate) message: String,
+}
+
+//
+// chat
+//
+
+#[derive(Serialize)]
+struct DmRequest<'a> {
+    username: &'a str,
+    msg: &'a str,
+}
+
+/// Any frame received on the `/api/chat` websocket
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChatFrame {
+    #[serde(default)]
+    pub(crate) username: String,
+    #[serde(default)]
+    pub(crate) message: String,
+    pub(crate) flag: Option<String>,
+    pub(crate) game_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_u64_opt")]
+    pub(crate) opponent_id: Option<u64>,
+    #[serde(default, deserialize_with = "flexible_u64_opt")]
+    pub(crate) player_side: Option<u64>,
+    pub(crate) mode: Option<String>,
+    #[serde(default)]
+    pub(crate) connections: Option<Connections>,
+}
+
+/// Server messages list the ids of every connected user, player messages only count them
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub(crate) enum Connections {
+    Count(u64),
+    Ids(Vec<i64>),
+}
+
+impl Connections {
+    pub(crate) fn count(&self) -> usize {
+        match self {
+            Connections::Count(count) => *count as usize,
+            Connections::Ids(ids) => ids.len(),
+        }
+    }
+}
+
+/// Sent on the chat websocket, the server broadcasts it to everyone but us
+#[derive(Serialize)]
+pub(crate) struct ChatMessageRequest<'a> {
+    pub(crate) token: &'a str,
+    pub(crate) message: &'a str,
+}
+
+/// Match notification sent by the server through the chat socket
+#[derive(Clone, Default)]
+pub(crate) struct MatchStart {
+    pub(crate) game_id: String,
+    pub(crate) opponent_id: u64,
+    pub(crate) player_side: u64,
+    pub(crate) mode: String,
+}
+
+impl ChatFrame {
+    pub(crate) fn match_start(&self) -> Option<MatchStart> {
+        Some(MatchStart {
+            game_id: self.game_id.clone()?,
+            opponent_id: self.opponent_id?,
+            player_side: self.player_side?,
+            mode: self.mode.clone().unwrap_or_else(|| "online".to_string()),
+        })
+    }
+    pub(crate) fn is_health_check(&self) -> bool {
+        self.flag.as_deref() == Some("health")
+    }
+    /// Notices from the server itself, such as users joining or leaving
+    pub(crate) fn is_server(&self) -> bool {
+        self.username == "<SERVER>"
+    }
+    /// Direct messages come without the connection count of the room messages
+    ///
+    /// #Returns
+    /// The text of a direct message
+    pub(crate) fn direct_message(&self) -> Option<&str> {
+        match self.connections.is_none() && !self.is_server() {
+            true => self.message.strip_prefix("[dm] -> "),
+            false => None,
+        }
+    }
+}
+
+//
+// game
+//
+
+#[derive(Serialize)]
+struct CreateGameRequest<'a> {
+    mode: &'a str,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GameSettings {
+    pub(crate) paddle_height: f64,
+    pub(crate) paddle_width: f64,
+    pub(crate) paddle_padding: f64,
+    pub(crate) ball_size: f64,
+}
+
+/// `gameId`, `opponentId` and `playerSide` are only present when the game starts right away
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateGameResponse {
+    pub(crate) game_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_u64_opt")]
+    pub(crate) opponent_id: Option<u64>,
+    #[serde(default, deserialize_with = "flexible_u64_opt")]
+    pub(crate) player_side: Option<u64>,
+    #[serde(flatten)]
+    pub(crate) settings: GameSettings,
+}
+
+impl CreateGameResponse {
+    pub(crate) fn match_start(&self, mode: &str) -> Option<MatchStart> {
+        Some(MatchStart {
+            game_id: self.game_id.clone()?,
+            opponent_id: self.opponent_id?,
+            player_side: self.player_side?,
+            mode: mode.to_string(),
+        })
+    }
+}
+
+/// Game that can be watched, from `/game/ongoing`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OngoingGame {
+    pub(crate) game_id: String,
+    #[serde(deserialize_with = "flexible_u64")]
+    pub(crate) player1_id: u64,
+    #[serde(deserialize_with = "flexible_u64")]
+    pub(crate) player2_id: u64,
+}
+
+impl OngoingGame {
+    pub(crate) fn has(&self, player: u64) -> bool {
+        self.player1_id == player || self.player2_id == player
+    }
+}
+
+//
+// duel
+//
+
+#[derive(Serialize)]
+struct DuelRequest {
+    id: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Duel {
+    pub(crate) sender_id: u64,
+    pub(crate) id: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DuelAccepted {
+    #[serde(default)]
+    pub(crate) id: Option<String>,
+    #[serde(default)]
+    pub(crate) message: String,
+}
+
+//
+// tournament
+//
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LobbyRequest<'a> {
+    lobby_id: &'a str,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TournamentSummary {
+    pub(crate) id: String,
+    pub(crate) owner_name: String,
+    pub(crate) player_count: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct LobbyPlayer {
+    #[serde(deserialize_with = "flexible_u64")]
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) elo: f64,
+}
+
+/// Frame of the lobby websockets: `created` for the owner, then an `UPDATE` for every change
+/// while the lobby waits for players
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LobbyFrame {
+    #[serde(default)]
+    pub(crate) message: String,
+    pub(crate) error: Option<String>,
+    pub(crate) lobby_id: Option<String>,
+    #[serde(default, deserialize_with = "flexible_u64_opt")]
+    pub(crate) owner_id: Option<u64>,
+    #[serde(default)]
+    pub(crate) owner_name: String,
+    #[serde(default)]
+    pub(crate) players: Vec<LobbyPlayer>,
+    #[serde(default)]
+    pub(crate) state: u8,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct ChainRecord {
+    pub(crate) address: String,
+    #[serde(deserialize_with = "flexible_u64")]
+    pub(crate) winner: u64,
+}
+
+/// Typed access to the backend's REST endpoints
+///
+/// The bearer token, when set, is attached to every request
+#[derive(Clone)]
+pub(crate) struct ApiClient {
+    client: Client,
+    base: String,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    pub(crate) fn new(context: &Context) -> Self {
+        ApiClient {
+            client: context.client.clone(),
+            base: format!("https://{}/api", context.location),
+            token: None,
+        }
+    }
+    pub(crate) fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self
+            .client
+            .request(method, format!("{}{}", self.base, path));
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+    /// Sends the request and returns the body, or the server's message on failure
+    async fn execute(&self, builder: RequestBuilder) -> ApiResult<String> {
+        let request = builder.build()?;
+        let (method, path) = (request.method().clone(), request.url().path().to_string());
+        let started = Instant::now();
+        let response = match self.client.execute(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(%method, path, "request failed: {}", root_cause(&e));
+                return Err(e.into());
+            }
+        };
+        let status = response.status();
+        let body = response.text().await?;
+        let elapsed = started.elapsed().as_millis() as u64;
+        if !status.is_success() {
+            let message = server_message(status, &body);
+            warn!(%method, path, status = status.as_u16(), elapsed, "{}", message);
+            return Err(ApiError::Server { status, message });
+        }
+        debug!(%method, path, status = status.as_u16(), elapsed, "request done");
+        Ok(body)
+    }
+    async fn send<T: DeserializeOwned>(
+        &self,
+        builder: RequestBuilder,
+        endpoint: &str,
+    ) -> ApiResult<T> {
+        let body = self.execute(builder).await?;
+        serde_json::from_str(&body).map_err(|source| {
+            // the body is not logged, it may hold a token
+            warn!(endpoint, "unexpected response: {}", source);
+            ApiError::Decode {
+                endpoint: endpoint.to_string(),
+                source,
+            }
+        })
+    }
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> ApiResult<T> {
+        self.send(self.request(Method::GET, path), path).await
+    }
+    async fn post<T: DeserializeOwned>(&self, path: &str) -> ApiResult<T> {
+        self.send(self.request(Method::POST, path), path).await
+    }
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> ApiResult<T> {
+        self.send(self.request(Method::POST, path).json(body), path)
+            .await
+    }
+    async fn delete_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> ApiResult<T> {
+        self.send(self.request(Method::DELETE, path).json(body), path)
+            .await
+    }
+
+    // user
+    pub(crate) async fn signup(&self, request: &SignupRequest<'_>) -> ApiResult<TokenResponse> {
+        self.post_json("/user/create", request).await
+    }
+    pub(crate) async fn login(&self, request: &LoginRequest<'_>) -> ApiResult<TokenResponse> {
+        self.post_json("/user/login", request).await
+    }
+    pub(crate) async fn create_guest(&self) -> ApiResult<TokenResponse> {
+        self.post("/user/create_guest").await
+    }
+    pub(crate) async fn profile_from_token(&self) -> ApiResult<Profile> {
+        self.post("/user/get_profile_token").await
+    }
+    pub(crate) async fn profile_by_id(&self, id: u64) -> ApiResult<Profile> {
+        self.get(&format!("/user/get_profile_id?user_id={}", id))
+            .await
+    }
+    pub(crate) async fn profile_by_name(&self, name: &str) -> ApiResult<Profile> {
+        let builder = self
+            .request(Method::GET, "/user/get_profile_name")
+            .query(&[("profile_name", name)]);
+        self.send(builder, "/user/get_profile_name").await
+    }
+    pub(crate) async fn history(&self, name: &str) -> ApiResult<Vec<MatchRecord>> {
+        let path = format!("/user/get_history_name/{}", urlencode(name));
+        match self.get(&path).await {
+            Err(ApiError::Server { status, .. }) if status == StatusCode::NOT_FOUND => Ok(vec![]),
+            result => result,
+        }
+    }
+    pub(crate) async fn best_elo(&self, page_size: u64) -> ApiResult<Vec<ProfileId>> {
+        self.get(&format!("/user/get_best_elo?page_size={}", page_size))
+            .await
+    }
+    pub(crate) async fn logout(&self) -> ApiResult<MessageResponse> {
+        self.post("/user/logout").await
+    }
+    pub(crate) async fn blocked_users(&self) -> ApiResult<Vec<BlockedLink>> {
+        self.post("/user/blocked_users").await
+    }
+
+    // friends
+    pub(crate) async fn friends(&self, user_id: u64) -> ApiResult<Vec<FriendLink>> {
+        self.get(&format!("/friends/get?user_id={}", user_id)).await
+    }
+    pub(crate) async fn send_friend_request(&self, friend_id: u64) -> ApiResult<MessageResponse> {
+        self.post_json("/friends/send_request", &FriendRequest { friend_id })
+            .await
+    }
+    pub(crate) async fn accept_friend(&self, friend_id: u64) -> ApiResult<MessageResponse> {
+        self.post_json("/friends/accept", &FriendRequest { friend_id })
+            .await
+    }
+    pub(crate) async fn remove_friend(&self, friend_id: u64) -> ApiResult<MessageResponse> {
+        self.delete_json("/friends/remove", &FriendRequest { friend_id })
+            .await
+    }
+
+    // chat
+    pub(crate) async fn health_callback(&self) -> ApiResult<MessageResponse> {
+        self.post("/chat/healthCallback").await
+    }
+    pub(crate) async fn remove_from_queue(&self) -> ApiResult<MessageResponse> {
+        self.send(
+            self.request(Method::DELETE, "/chat/removeQueue"),
+            "/chat/removeQueue",
+        )
+        .await
+    }
+    pub(crate) async fn send_dm(&self, username: &str, msg: &str) -> ApiResult<MessageResponse> {
+        self.post_json("/chat/dm", &DmRequest { username, msg })
+            .await
+    }
+
+    // game
+    pub(crate) async fn create_game(&self, mode: &str) -> ApiResult<CreateGameResponse> {
+        self.post_json("/create-game", &CreateGameRequest { mode })
+            .await
+    }
+    /// Marks us as ready, the server answers with the raw game state
+    pub(crate) async fn start_game(&self, game_id: &str) -> ApiResult<()> {
+        let path = format!("/start-game/{}", game_id);
+        self.execute(self.request(Method::POST, &path)).await?;
+        Ok(())
+    }
+    /// Games being played that spectators may join, local ones excluded
+    pub(crate) async fn ongoing_games(&self) -> ApiResult<Vec<OngoingGame>> {
+        self.get("/game/ongoing").await
+    }
+
+    // duel
+    pub(crate) async fn duels(&self) -> ApiResult<Vec<Duel>> {
+        self.post("/duel/list").await
+    }
+    pub(crate) async fn invite_duel(&self, id: u64) -> ApiResult<MessageResponse> {
+        self.post_json("/duel/invite", &DuelRequest { id }).await
+    }
+    pub(crate) async fn accept_duel(&self, id: u64) -> ApiResult<DuelAccepted> {
+        self.post_json("/duel/accept", &DuelRequest { id }).await
+    }
+    pub(crate) async fn decline_duel(&self, id: u64) -> ApiResult<MessageResponse> {
+        self.post_json("/duel/decline", &DuelRequest { id }).await
+    }
+
+    // tournament
+    pub(crate) async fn tournaments(&self) -> ApiResult<Vec<TournamentSummary>> {
+        self.get("/tournament/list").await
+    }
+    pub(crate) async fn start_tournament(&self, lobby_id: &str) -> ApiResult<()> {
+        let builder = self
+            .request(Method::POST, "/tournament/start")
+            .json(&LobbyRequest { lobby_id });
+        self.execute(builder).await?;
+        Ok(())
+    }
+    pub(crate) async fn leave_tournament(&self, lobby_id: &str) -> ApiResult<MessageResponse> {
+        self.post_json("/tournament/leave", &LobbyRequest { lobby_id })
+            .await
+    }
+    pub(crate) async fn chain_records(&self) -> ApiResult<Vec<ChainRecord>> {
+        self.get("/tournament/blockchain").await
+    }
+}
+
+pub(crate) fn urlencode(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn server_message_reads_every_error_shape() {
+        let status = StatusCode::BAD_REQUEST;
+        assert_eq!(server_message(status, r#"{"message":"taken"}"#), "taken");
+        assert_eq!(server_message(status, r#"{"error":"invalid"}"#), "invalid");
+        assert_eq!(server_message(status, " plain text \n"), "plain text");
+        assert_eq!(server_message(status, r#"{"other":1}"#), "Bad Request");
+        assert_eq!(server_message(status, ""), "Bad Request");
+    }
+
+    #[tokio::test]
+    async fn odd_profile_is_a_decode_error() {
+        let server = MockServer::start().await;
+        server.reply("/user/get_profile_id", 200, r#"{"id":"one"}"#);
+        let error = server.context().api().profile_by_id(1).await.err().unwrap();
+        assert!(matches!(error, ApiError::Decode { .. }));
+        assert_eq!(error.status(), None);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::api::{ApiClient, ApiError, ChainRecord};
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+/// Block explorer page of a tournament contract, the one the web frontend links to
+const EXPLORER: &str = "https://testnet.avascan.info/blockchain/c/address/";
+
+/// Finished tournament as recorded on the blockchain
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct ChainResult {
+    /// 1-based, in the order the contracts were created
+    pub(crate) number: usize,
+    pub(crate) address: String,
+    pub(crate) winner_id: u64,
+    /// None when the id on chain matches no profile
+    pub(crate) winner: Option<String>,
+}
+
+impl ChainResult {
+    pub(crate) fn winner_name(&self) -> String {
+        match &self.winner {
+            Some(name) => name.clone(),
+            None => format!("#{}", self.winner_id),
+        }
+    }
+    pub(crate) fn explorer(&self) -> String {
+        format!("{}{}", EXPLORER, self.address)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct Ranking {
+    pub(crate) rank: usize,
+    pub(crate) winner: String,
+    pub(crate) titles: usize,
+}
+
+/// One cross-check of an on-chain result against the match history
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct Check {
+    pub(crate) passed: bool,
+    pub(crate) detail: String,
+}
+
+/// Fetches the finished tournaments and the names of their winners
+pub(crate) async fn results(api: &ApiClient) -> Result<Vec<ChainResult>> {
+    let records: Vec<ChainRecord> = api.chain_records().await?;
+    let mut names: HashMap<u64, Option<String>> = HashMap::new();
+    let mut results = vec![];
+    for (index, record) in records.into_iter().enumerate() {
+        if let Entry::Vacant(entry) = names.entry(record.winner) {
+            let name = match api.profile_by_id(record.winner).await {
+                Ok(profile) => Some(profile.name),
+                Err(ApiError::Server { status, .. }) if status == StatusCode::NOT_FOUND => None,
+                Err(e) => return Err(e.into()),
+            };
+            entry.insert(name);
+        }
+        results.push(ChainResult {
+            number: index + 1,
+            address: record.address,
+            winner_id: record.winner,
+            winner: names[&record.winner].clone(),
+        });
+    }
+    Ok(results)
+}
+
+/// Winners by number of titles, ties share a rank
+pub(crate) fn rankings(results: &[ChainResult]) -> Vec<Ranking> {
+    let mut titles: HashMap<u64, (String, usize)> = HashMap::new();
+    for result in results {
+        let entry = titles
+            .entry(result.winner_id)
+            .or_insert((result.winner_name(), 0));
+        entry.1 += 1;
+    }
+    let mut counts: Vec<(String, usize)> = titles.into_values().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let mut rankings: Vec<Ranking> = vec![];
+    for (index, (winner, titles)) in counts.into_iter().enumerate() {
+        let rank = match rankings.last() {
+            Some(last) if last.titles == titles => last.rank,
+            _ => index + 1,
+        };
+        rankings.push(Ranking {
+            rank,
+            winner,
+            titles,
+        });
+    }
+    rankings
+}
+
+/// A result by its number or by its contract address, a prefix of it is enough
+pub(crate) fn find<'a>(results: &'a [ChainResult], id: &str) -> Option<&'a ChainResult> {
+    if let Ok(number) = id.parse::<usize>() {
+        return results.iter().find(|result| result.number == number);
+    }
+    let id = id.to_lowercase();
+    let mut found = results
+        .iter()
+        .filter(|result| result.address.to_lowercase().starts_with(&id));
+    match (found.next(), found.next()) {
+        (Some(result), None) => Some(result),
+        _ => None,
+    }
+}
+
+/// Checks a result against the history the API reports for its winner
+///
+/// The server records each tournament match in the history as a regular game, so a champion
+/// has won at least one game per title
+pub(crate) async fn verify(
+    api: &ApiClient,
+    results: &[ChainResult],
+    result: &ChainResult,
+) -> Result<Vec<Check>> {
+    let Some(name) = &result.winner else {
+        return Ok(vec![Check {
+            passed: false,
+            detail: format!("winner #{} matches no player", result.winner_id),
+        }]);
+    };
+    let mut checks = vec![Check {
+        passed: true,
+        detail: format!("winner #{} is {}", result.winner_id, name),
+    }];
+    let wins = api
+        .history(name)
+        .await?
+        .iter()
+        .filter(|game| game.winner_id == result.winner_id)
+        .count();
+    checks.push(Check {
+        passed: wins > 0,
+        detail: format!("{} has won {} games in the match history", name, wins),
+    });
+    let titles = results
+        .iter()
+        .filter(|other| other.winner_id == result.winner_id)
+        .count();
+    checks.push(Check {
+        passed: titles <= wins,
+        detail: format!(
+            "{} tournament titles on chain for {} won games",
+            titles, wins
+        ),
+    });
+    Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    fn result(number: usize, address: &str, winner: &str) -> ChainResult {
+        ChainResult {
+            number,
+            address: address.to_string(),
+            winner_id: winner.len() as u64,
+            winner: Some(winner.to_string()),
+        }
+    }
+
+    #[test]
+    fn results_are_found_by_number_or_address() {
+        let results = [result(1, "0xAbC1", "bob"), result(2, "0xabd2", "alice")];
+        assert_eq!(find(&results, "2").map(|r| r.number), Some(2));
+        assert_eq!(find(&results, "0xabc").map(|r| r.number), Some(1));
+        // ambiguous or unknown
+        assert!(find(&results, "0xab").is_none());
+        assert!(find(&results, "3").is_none());
+        let rankings = rankings(&[
+            result(1, "0x1", "bob"),
+            result(2, "0x2", "alice"),
+            result(3, "0x3", "bob"),
+            result(4, "0x4", "carl"),
+        ]);
+        let ranks: Vec<(usize, &str, usize)> = rankings
+            .iter()
+            .map(|r| (r.rank, r.winner.as_str(), r.titles))
+            .collect();
+        assert_eq!(ranks, [(1, "bob", 2), (2, "alice", 1), (2, "carl", 1)]);
+    }
+
+    #[tokio::test]
+    async fn results_are_checked_against_the_history() {
+        let server = MockServer::start().await;
+        let bob = server.add_user("bob", "bob@mail.com", "pass");
+        let alice = server.add_user("alice", "alice@mail.com", "pass");
+        server.record_game((bob, alice), (5, 3));
+        server.record_chain("0xaaa", bob);
+        server.record_chain("0xbbb", bob);
+        server.record_chain("0xccc", 42);
+        let api = server.context().api();
+        let results = results(&api).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].winner.as_deref(), Some("bob"));
+        assert_eq!(results[2].winner_name(), "#42");
+        assert_eq!(
+            results[1].explorer(),
+            "https://testnet.avascan.info/blockchain/c/address/0xbbb"
+        );
+
+        // two titles for a single win
+        let checks = verify(&api, &results, &results[0]).await.unwrap();
+        let passed: Vec<bool> = checks.iter().map(|check| check.passed).collect();
+        assert_eq!(passed, [true, true, false]);
+
+        server.record_game((alice, bob), (1, 5));
+        let checks = verify(&api, &results, &results[1]).await.unwrap();
+        assert!(checks.iter().all(|check| check.passed));
+
+        let checks = verify(&api, &results, &results[2]).await.unwrap();
+        assert!(!checks[0].passed);
+    }
+}
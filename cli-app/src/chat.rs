@@ -0,0 +1,710 @@
+use crate::Context;
+use crate::api::{ApiClient, ChatFrame, ChatMessageRequest, MatchStart};
+use crate::dm::blocked_names;
+use crate::game::WsStream;
+use anyhow::{Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{MissedTickBehavior, interval, sleep};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{Connector, connect_async_tls_with_config, tungstenite::protocol::Message};
+use tracing::{debug, info, warn};
+
+/// A ping is sent when the socket stayed quiet this long
+const PING_EVERY: Duration = Duration::from_secs(20);
+/// Without any frame for this long, pongs included, the connection is considered dead
+const SILENCE_LIMIT: Duration = Duration::from_secs(50);
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const LAST_RETRY: Duration = Duration::from_secs(30);
+/// Match notifications remembered to drop the ones sent again after a reconnection
+const REMEMBERED_GAMES: usize = 32;
+/// Lines kept in the chat log, the oldest are dropped
+const SCROLLBACK: usize = 500;
+/// How often the users we blocked are fetched again, they can be blocked from the web interface
+const BLOCKED_EVERY: Duration = Duration::from_secs(60);
+/// Longest message the server accepts
+pub(crate) const MAX_MESSAGE_LEN: usize = 512;
+
+/// State of the chat websocket, shown by the interface
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ChatStatus {
+    Connected,
+    /// The connection was lost, the next attempt is at `retry_at`
+    Reconnecting {
+        attempt: u32,
+        retry_at: Instant,
+    },
+    /// The server does not accept the session anymore, the user has to log in again
+    Rejected,
+}
+
+impl ChatStatus {
+    /// #Returns
+    /// What to tell the user, nothing while connected
+    pub(crate) fn describe(&self) -> Option<String> {
+        match self {
+            ChatStatus::Connected => None,
+            ChatStatus::Reconnecting { attempt, retry_at } => Some(format!(
+                "Chat connection lost, retrying in {}s (attempt {})",
+                retry_at
+                    .saturating_duration_since(Instant::now())
+                    .as_secs_f32()
+                    .ceil(),
+                attempt
+            )),
+            ChatStatus::Rejected => Some("Session expired, log in again".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LineKind {
+    /// Notice from the server, such as a user joining
+    Server,
+    Player,
+    /// Sent by us, the server does not echo our messages
+    Own,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ChatLine {
+    pub(crate) kind: LineKind,
+    pub(crate) author: String,
+    pub(crate) text: String,
+    /// What a server notice is about, such as `duelDeclined`
+    pub(crate) flag: Option<String>,
+}
+
+/// Direct messages exchanged with one user since login
+#[derive(Default)]
+pub(crate) struct Conversation {
+    pub(crate) with: String,
+    pub(crate) lines: VecDeque<ChatLine>,
+    pub(crate) received: u64,
+    /// Messages from `with` not shown yet
+    pub(crate) unread: usize,
+}
+
+/// Messages of the global chat room and the direct conversations, filled whether a chat screen is open or not
+#[derive(Default)]
+pub(crate) struct ChatLog {
+    pub(crate) lines: VecDeque<ChatLine>,
+    /// Users connected to the chat, as last reported by the server
+    pub(crate) connections: usize,
+    /// Lines received since login, to count the unread ones
+    pub(crate) received: u64,
+    /// Most recently active first
+    pub(crate) conversations: Vec<Conversation>,
+    /// Users we blocked, as of the last fetch, their conversations are not counted as unread
+    pub(crate) blocked: Vec<String>,
+}
+
+fn push_line(lines: &mut VecDeque<ChatLine>, line: ChatLine) {
+    if lines.len() == SCROLLBACK {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+impl ChatLog {
+    fn push(&mut self, line: ChatLine) {
+        push_line(&mut self.lines, line);
+        self.received += 1;
+    }
+    /// Adds a line to the conversation with `with`, which moves to the top of the list
+    fn push_direct(&mut self, with: &str, line: ChatLine) {
+        let mut conversation = match self.conversations.iter().position(|c| c.with == with) {
+            Some(index) => self.conversations.remove(index),
+            None => Conversation {
+                with: with.to_string(),
+                ..Conversation::default()
+            },
+        };
+        if line.kind == LineKind::Player {
+            conversation.unread += 1;
+        }
+        push_line(&mut conversation.lines, line);
+        conversation.received += 1;
+        self.conversations.insert(0, conversation);
+    }
+    /// Lines of the room received after the first `seen` ones, as far as the scrollback goes
+    pub(crate) fn since(&self, seen: u64) -> impl Iterator<Item = &ChatLine> {
+        let new = self.received.saturating_sub(seen) as usize;
+        self.lines.iter().skip(self.lines.len().saturating_sub(new))
+    }
+    pub(crate) fn conversation(&self, with: &str) -> Option<&Conversation> {
+        self.conversations.iter().find(|c| c.with == with)
+    }
+    /// Direct messages not shown yet, all conversations together but the blocked users'
+    pub(crate) fn unread(&self) -> usize {
+        self.conversations
+            .iter()
+            .filter(|c| !self.blocked.contains(&c.with))
+            .map(|c| c.unread)
+            .sum()
+    }
+}
+
+/// What the interface asks of the chat
+pub(crate) enum Outgoing {
+    /// Message to everyone in the room, kept until the socket is connected
+    Global(String),
+    /// Sent through the API since the socket only broadcasts
+    Direct { to: String, text: String },
+    /// The conversation with this user was shown
+    Read(String),
+}
+
+/// Handles of the chat websocket: match notifications, messages, status and the way to close it
+pub(crate) struct ChatRoom {
+    pub(crate) receiver: mpsc::Receiver<MatchStart>,
+    pub(crate) status: watch::Receiver<ChatStatus>,
+    pub(crate) log: watch::Receiver<ChatLog>,
+    pub(crate) outgoing: mpsc::UnboundedSender<Outgoing>,
+    pub(crate) closer: oneshot::Sender<()>,
+}
+
+/// Everything needed to open the socket again, the context stays on the main task
+#[derive(Clone)]
+struct Endpoint {
+    url: String,
+    token: String,
+    connector: Connector,
+    api: ApiClient,
+}
+
+impl Endpoint {
+    async fn connect(&self) -> Result<WsStream> {
+        let mut request = self.url.as_str().into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert("Cookie", format!("jwt_session={}", self.token).parse()?);
+        let (ws_stream, _) =
+            connect_async_tls_with_config(request, None, false, Some(self.connector.clone()))
+                .await?;
+        Ok(ws_stream)
+    }
+}
+
+/// Why a connection ended
+enum Ended {
+    /// Closed on purpose, or nobody listens anymore
+    Stopped,
+    Lost(anyhow::Error),
+}
+
+/// Opens the chat websocket and keeps it open until the `closer` is used
+///
+/// The first connection has to succeed, the following ones are retried with an exponential backoff
+pub(crate) async fn join(context: &Context, token: &str) -> Result<ChatRoom> {
+    let endpoint = Endpoint {
+        url: format!("wss://{}/api/chat", context.location),
+        token: token.to_string(),
+        connector: context.connector(),
+        api: context.api().with_token(token),
+    };
+    let ws_stream = endpoint.connect().await?;
+    info!("chat connected");
+    let (matches, receiver) = mpsc::channel(1024);
+    let (status_sender, status) = watch::channel(ChatStatus::Connected);
+    let (log_sender, log) = watch::channel(ChatLog::default());
+    let (outgoing, messages) = mpsc::unbounded_channel();
+    let (closer, close_signal) = oneshot::channel();
+    tokio::spawn(watch_blocked(
+        endpoint.api.clone(),
+        log_sender.clone(),
+        status.clone(),
+    ));
+    let supervisor = Supervisor {
+        endpoint,
+        matches,
+        status: status_sender,
+        log: log_sender,
+        messages,
+        close_signal,
+        games: VecDeque::new(),
+    };
+    tokio::spawn(supervisor.run(ws_stream));
+    Ok(ChatRoom {
+        receiver,
+        status,
+        log,
+        outgoing,
+        closer,
+    })
+}
+
+/// Keeps `ChatLog::blocked` up to date, again after each reconnection, until the supervisor
+/// stops or the session is rejected
+///
+/// Direct messages only carry the sender's name, the blocked ids are resolved to names
+async fn watch_blocked(
+    api: ApiClient,
+    log: watch::Sender<ChatLog>,
+    mut status: watch::Receiver<ChatStatus>,
+) {
+    loop {
+        let blocked = match api.profile_from_token().await {
+            Ok(me) => blocked_names(&api, me.id).await,
+            Err(e) => Err(e.into()),
+        };
+        match blocked {
+            Ok(names) => log.send_modify(|log| log.blocked = names),
+            Err(e) => warn!("blocked users not fetched: {:#}", e),
+        }
+        let next = sleep(BLOCKED_EVERY);
+        tokio::pin!(next);
+        loop {
+            tokio::select! {
+                _ = &mut next => break,
+                changed = status.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    match *status.borrow_and_update() {
+                        ChatStatus::Connected => break,
+                        ChatStatus::Rejected => return,
+                        ChatStatus::Reconnecting { .. } => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Owns the websocket and everything that outlives a connection
+struct Supervisor {
+    endpoint: Endpoint,
+    matches: mpsc::Sender<MatchStart>,
+    status: watch::Sender<ChatStatus>,
+    log: watch::Sender<ChatLog>,
+    messages: mpsc::UnboundedReceiver<Outgoing>,
+    close_signal: oneshot::Receiver<()>,
+    /// Ids of the matches already announced
+    games: VecDeque<String>,
+}
+
+impl Supervisor {
+    async fn run(mut self, mut ws_stream: WsStream) {
+        loop {
+            match self.chat(ws_stream).await {
+                Ended::Stopped => {
+                    info!("chat closed");
+                    return;
+                }
+                Ended::Lost(e) => warn!("chat connection lost: {:#}", e),
+            }
+            ws_stream = match self.reconnect().await {
+                Some(ws_stream) => ws_stream,
+                None => return,
+            };
+            let _ = self.status.send(ChatStatus::Connected);
+        }
+    }
+    /// #Returns
+    /// The new connection, None once closed or when the session was rejected
+    async fn reconnect(&mut self) -> Option<WsStream> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let delay = backoff(attempt);
+            let retry_at = Instant::now() + delay;
+            let _ = self
+                .status
+                .send(ChatStatus::Reconnecting { attempt, retry_at });
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = &mut self.close_signal => return None,
+            }
+            // the socket accepts any token, ask the API whether the session is still valid
+            match self.endpoint.api.profile_from_token().await {
+                Err(e) if matches!(e.status().map(|s| s.as_u16()), Some(400 | 401 | 403 | 404)) => {
+                    warn!("chat session rejected: {}", e);
+                    let _ = self.status.send(ChatStatus::Rejected);
+                    return None;
+                }
+                Err(e) => {
+                    debug!(attempt, "server still unreachable: {}", e);
+                    continue;
+                }
+                Ok(_) => {}
+            }
+            match self.endpoint.connect().await {
+                Ok(ws_stream) => {
+                    info!(attempt, "chat reconnected");
+                    return Some(ws_stream);
+                }
+                Err(e) => debug!(attempt, "chat reconnection failed: {:#}", e),
+            }
+        }
+    }
+    /// Forwards match notifications and messages and answers health checks until the connection ends
+    async fn chat(&mut self, mut ws_stream: WsStream) -> Ended {
+        let mut heartbeat = interval(PING_EVERY);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.reset();
+        let mut last_seen = Instant::now();
+        loop {
+            let msg = tokio::select! {
+                msg = ws_stream.next() => msg,
+                _ = &mut self.close_signal => {
+                    let _ = ws_stream.close(None).await;
+                    return Ended::Stopped;
+                }
+                Some(outgoing) = self.messages.recv() => {
+                    let sent = match outgoing {
+                        Outgoing::Global(message) => self.say(&mut ws_stream, message).await,
+                        Outgoing::Direct { to, text } => {
+                            self.whisper(to, text).await;
+                            Ok(())
+                        }
+                        Outgoing::Read(with) => {
+                            self.log.send_modify(|log| {
+                                if let Some(c) = log.conversations.iter_mut().find(|c| c.with == with) {
+                                    c.unread = 0;
+                                }
+                            });
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = sent {
+                        return Ended::Lost(e);
+                    }
+                    continue;
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > SILENCE_LIMIT {
+                        return Ended::Lost(anyhow!("no answer from the server"));
+                    }
+                    if let Err(e) = ws_stream.send(Message::Ping(Default::default())).await {
+                        return Ended::Lost(e.into());
+                    }
+                    continue;
+                }
+            };
+            let last_message = match msg {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Close(frame))) => {
+                    return Ended::Lost(anyhow!("closed by the server: {:?}", frame));
+                }
+                Some(Ok(_)) => {
+                    last_seen = Instant::now();
+                    continue;
+                }
+                Some(Err(e)) => return Ended::Lost(e.into()),
+                None => return Ended::Lost(anyhow!("connection ended")),
+            };
+            last_seen = Instant::now();
+            let frame: ChatFrame = match serde_json::from_str(last_message.as_str()) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("unexpected chat frame: {}", e);
+                    continue;
+                }
+            };
+            if !self.receive(frame).await {
+                return Ended::Stopped;
+            }
+        }
+    }
+    /// #Returns
+    /// false when nobody listens to match notifications anymore
+    async fn receive(&mut self, frame: ChatFrame) -> bool {
+        if frame.is_health_check() {
+            debug!("health check");
+            if let Err(e) = self.endpoint.api.health_callback().await {
+                warn!("health check not answered: {}", e);
+            }
+            return true;
+        }
+        if let Some(start) = frame.match_start() {
+            if self.games.contains(&start.game_id) {
+                debug!(game_id = start.game_id, "match already announced");
+                return true;
+            }
+            info!(
+                game_id = start.game_id,
+                opponent = start.opponent_id,
+                side = start.player_side,
+                mode = start.mode,
+                "match found"
+            );
+            if self.games.len() == REMEMBERED_GAMES {
+                self.games.pop_front();
+            }
+            self.games.push_back(start.game_id.clone());
+            return self.matches.send(start).await.is_ok();
+        }
+        if let Some(text) = frame.direct_message() {
+            debug!(from = frame.username, "direct message");
+            let line = ChatLine {
+                kind: LineKind::Player,
+                author: frame.username.clone(),
+                text: text.to_string(),
+                flag: None,
+            };
+            self.log
+                .send_modify(|log| log.push_direct(&frame.username, line));
+            return true;
+        }
+        if frame.message.is_empty() {
+            return true;
+        }
+        let kind = match frame.is_server() {
+            true => LineKind::Server,
+            false => LineKind::Player,
+        };
+        self.log.send_modify(|log| {
+            if let Some(connections) = &frame.connections {
+                log.connections = connections.count();
+            }
+            log.push(ChatLine {
+                kind,
+                author: frame.username,
+                text: frame.message,
+                flag: frame.flag,
+            });
+        });
+        true
+    }
+    async fn say(&mut self, ws_stream: &mut WsStream, message: String) -> Result<()> {
+        let request = ChatMessageRequest {
+            token: &self.endpoint.token,
+            message: &message,
+        };
+        ws_stream
+            .send(Message::text(serde_json::to_string(&request)?))
+            .await?;
+        debug!(len = message.len(), "message sent");
+        self.log.send_modify(|log| {
+            log.push(ChatLine {
+                kind: LineKind::Own,
+                author: String::new(),
+                text: message,
+                flag: None,
+            })
+        });
+        Ok(())
+    }
+    /// Sends a direct message, what went wrong is told in the conversation
+    async fn whisper(&mut self, to: String, text: String) {
+        let notice = |text: String| ChatLine {
+            kind: LineKind::Server,
+            author: String::new(),
+            text,
+            flag: None,
+        };
+        let lines = match self.endpoint.api.send_dm(&to, &text).await {
+            Ok(answer) => {
+                let own = ChatLine {
+                    kind: LineKind::Own,
+                    author: String::new(),
+                    text,
+                    flag: None,
+                };
+                match answer.message.as_str() {
+                    // the server does not keep messages for later
+                    "user is offline" => vec![
+                        own,
+                        notice(format!("{} is offline, the message was lost", to)),
+                    ],
+                    _ => vec![own],
+                }
+            }
+            Err(e) => {
+                warn!("direct message not sent: {}", e);
+                vec![notice(format!("Not sent: {}", e))]
+            }
+        };
+        self.log.send_modify(|log| {
+            for line in lines {
+                log.push_direct(&to, line);
+            }
+        });
+    }
+}
+
+/// 1s, 2s, 4s... up to 30s
+fn backoff(attempt: u32) -> Duration {
+    FIRST_RETRY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(5))
+        .min(LAST_RETRY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use tokio::time::timeout;
+
+    fn start(game_id: &str) -> String {
+        format!(
+            r#"{{"username":"SERVER","message":"START","opponentId":"2","gameId":"{}","playerSide":1,"mode":"online"}}"#,
+            game_id
+        )
+    }
+
+    async fn becomes(room: &mut ChatRoom, expected: fn(&ChatStatus) -> bool) {
+        timeout(Duration::from_secs(5), room.status.wait_for(expected))
+            .await
+            .expect("chat status timed out")
+            .expect("chat supervisor stopped");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let delays: Vec<u64> = (1..=8).map(|attempt| backoff(attempt).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_drops_replayed_matches() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(id)).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        server.send_chat(id, &start("first"));
+        assert_eq!(room.receiver.recv().await.unwrap().game_id, "first");
+        server.disconnect_chat(id);
+        becomes(&mut room, |s| matches!(s, ChatStatus::Reconnecting { .. })).await;
+        becomes(&mut room, |s| *s == ChatStatus::Connected).await;
+        assert!(server.wait_chat(id, true).await);
+        server.send_chat(id, &start("first"));
+        server.send_chat(id, &start("second"));
+        assert_eq!(room.receiver.recv().await.unwrap().game_id, "second");
+    }
+
+    #[tokio::test]
+    async fn rejected_session_stops_reconnecting() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(id)).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        server.reply(
+            "/user/get_profile_token",
+            400,
+            r#"{"message":"invalid token"}"#,
+        );
+        server.disconnect_chat(id);
+        becomes(&mut room, |s| *s == ChatStatus::Rejected).await;
+        assert!(room.receiver.recv().await.is_none());
+        assert!(!server.chat_connected(id));
+    }
+
+    async fn logged(room: &mut ChatRoom, count: usize) {
+        timeout(
+            Duration::from_secs(5),
+            room.log.wait_for(|log| log.lines.len() >= count),
+        )
+        .await
+        .expect("chat log timed out")
+        .expect("chat supervisor stopped");
+    }
+
+    #[tokio::test]
+    async fn logs_messages_but_not_notifications() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(id)).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        server.send_chat(
+            id,
+            r#"{"username":"<SERVER>","message":"","flag":"health"}"#,
+        );
+        server.send_chat(id, &start("first"));
+        server.send_chat(
+            id,
+            r#"{"username":"<SERVER>","message":"bob joined","connections":[1,2]}"#,
+        );
+        server.send_chat(
+            id,
+            r#"{"username":"bob","message":"hi alice","connections":3}"#,
+        );
+        logged(&mut room, 2).await;
+        let log = room.log.borrow();
+        let lines: Vec<_> = log
+            .lines
+            .iter()
+            .map(|line| (line.kind, line.author.as_str(), line.text.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (LineKind::Server, "<SERVER>", "bob joined"),
+                (LineKind::Player, "bob", "hi alice"),
+            ]
+        );
+        assert_eq!(log.connections, 3);
+        assert_eq!(log.received, 2);
+    }
+
+    #[tokio::test]
+    async fn blocked_senders_are_not_counted_as_unread() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.block(alice, carol);
+        let mut room = join(&server.context(), &server.token(alice)).await.unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        server.send_chat(alice, r#"{"username":"carol","message":"[dm] -> hi"}"#);
+        server.send_chat(alice, r#"{"username":"bob","message":"[dm] -> hi"}"#);
+        timeout(
+            Duration::from_secs(5),
+            room.log
+                .wait_for(|log| log.conversations.len() == 2 && !log.blocked.is_empty()),
+        )
+        .await
+        .expect("direct messages timed out")
+        .unwrap();
+        {
+            let log = room.log.borrow();
+            assert_eq!(log.blocked, vec!["carol"]);
+            assert_eq!(log.conversation("carol").unwrap().unread, 1);
+            assert_eq!(log.unread(), 1);
+        }
+        // blocked from the web interface, picked up once the chat reconnects
+        server.block(alice, bob);
+        server.disconnect_chat(alice);
+        timeout(
+            Duration::from_secs(5),
+            room.log.wait_for(|log| log.blocked.len() == 2),
+        )
+        .await
+        .expect("blocked users not refreshed")
+        .unwrap();
+        assert_eq!(room.log.borrow().unread(), 0);
+    }
+
+    #[tokio::test]
+    async fn sends_messages_with_the_token() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let token = server.token(id);
+        let mut room = join(&server.context(), &token).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        room.outgoing
+            .send(Outgoing::Global("hello".to_string()))
+            .unwrap();
+        logged(&mut room, 1).await;
+        assert_eq!(room.log.borrow().lines[0].kind, LineKind::Own);
+        let said = server.wait_said(1).await;
+        let sent: serde_json::Value = serde_json::from_str(&said[0]).unwrap();
+        assert_eq!(
+            sent,
+            serde_json::json!({"token": token, "message": "hello"})
+        );
+    }
+
+    #[tokio::test]
+    async fn closing_stops_the_supervisor() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(id)).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        room.closer.send(()).unwrap();
+        assert!(room.receiver.recv().await.is_none());
+        assert!(server.wait_chat(id, false).await);
+    }
+}
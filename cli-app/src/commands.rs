@@ -0,0 +1,572 @@
+use crate::api::{ApiClient, ApiError, Profile};
+use crate::chain::{self, ChainResult, Check, Ranking};
+use crate::context::Context;
+use crate::friends::{
+    Friend, get_friends, send_accept_friend_request, send_delete_friend_request,
+    send_friend_request,
+};
+use crate::login::{register, request_guest_token, request_token};
+use anyhow::{Result, anyhow, bail};
+use clap::Subcommand;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
+use thiserror::Error;
+use tokio::time::{Duration, error::Elapsed, timeout};
+
+/// Exit codes, 2 is used by clap for invalid arguments
+const EXIT_FAILURE: u8 = 1;
+const EXIT_AUTH: u8 = 3;
+const EXIT_NOT_FOUND: u8 = 4;
+const EXIT_UNREACHABLE: u8 = 5;
+
+/// Longest a command may take before the server is considered unreachable
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Commands run without the full screen interface, for scripts
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Create an account and log in
+    Signup {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        /// Read from stdin when not given
+        #[arg(long, env = "PONG_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Log in, the session is kept for the next commands and launches
+    Login {
+        #[arg(long)]
+        email: String,
+        /// Read from stdin when not given
+        #[arg(long, env = "PONG_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        /// 2FA code, if enabled on the account
+        #[arg(long)]
+        totp: Option<String>,
+    },
+    /// Log in with a new guest account
+    Guest,
+    /// End the stored session
+    Logout,
+    /// Show the logged in user
+    Whoami,
+    /// Manage the friends of the logged in user
+    #[command(subcommand)]
+    Friends(FriendsCommand),
+    /// Show the matches played by a user
+    History { user: String },
+    /// Show the best players by elo
+    Leaderboard {
+        /// Number of players to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        count: u64,
+    },
+    /// Check tournament results recorded on the blockchain
+    #[command(subcommand)]
+    Tournament(TournamentCommand),
+}
+
+#[derive(Subcommand)]
+pub(crate) enum FriendsCommand {
+    /// List friends and pending requests
+    List,
+    /// Send a friend request
+    Add { name: String },
+    /// Accept a friend request
+    Accept { name: String },
+    /// Remove a friend or cancel a request
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum TournamentCommand {
+    /// List the finished tournaments and their winners
+    Results,
+    /// Rank the winners by number of titles
+    Rankings,
+    /// Cross-check a result against the match history
+    Verify {
+        /// Number shown by `tournament results`, or the contract address
+        id: String,
+    },
+}
+
+#[derive(Debug, Error)]
+enum CommandError {
+    #[error("not logged in, run `login` or `guest` first")]
+    NotLoggedIn,
+    #[error("the stored session was rejected by the server, log in again")]
+    SessionRejected,
+    #[error("no tournament {0} on the blockchain")]
+    UnknownTournament(String),
+}
+
+#[derive(Serialize)]
+struct User {
+    id: u64,
+    name: String,
+    elo: f64,
+    games_played: u64,
+    wins: u64,
+}
+
+impl From<Profile> for User {
+    fn from(profile: Profile) -> Self {
+        User {
+            id: profile.id,
+            name: profile.name,
+            elo: profile.elo,
+            games_played: profile.games_played,
+            wins: profile.wins,
+        }
+    }
+}
+
+impl User {
+    fn line(&self) -> String {
+        format!(
+            "{}\t{}\t{:.0}\t{}\t{}",
+            self.id, self.name, self.elo, self.games_played, self.wins
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct Ranked {
+    rank: usize,
+    #[serde(flatten)]
+    user: User,
+}
+
+#[derive(Serialize)]
+struct Match {
+    played_at: String,
+    player1: String,
+    score1: u64,
+    player2: String,
+    score2: u64,
+    winner: String,
+}
+
+#[derive(Serialize)]
+struct Verified {
+    #[serde(flatten)]
+    result: ChainResult,
+    explorer: String,
+    ranking: Option<Ranking>,
+    checks: Vec<Check>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    message: String,
+}
+
+/// What a command prints, tab separated lines or one JSON document
+pub(crate) struct Output {
+    text: Vec<String>,
+    json: serde_json::Value,
+}
+
+impl Output {
+    fn new<T: Serialize>(value: &T, text: Vec<String>) -> Result<Self> {
+        Ok(Output {
+            text,
+            json: serde_json::to_value(value)?,
+        })
+    }
+    fn message(message: String) -> Result<Self> {
+        Output::new(
+            &Message {
+                message: message.clone(),
+            },
+            vec![message],
+        )
+    }
+    pub(crate) fn print(&self, json: bool) {
+        let printed = self.printed(json);
+        // an empty list prints nothing
+        if !printed.is_empty() {
+            println!("{}", printed);
+        }
+    }
+    fn printed(&self, json: bool) -> String {
+        match json {
+            true => self.json.to_string(),
+            false => self.text.join("\n"),
+        }
+    }
+}
+
+/// Runs a command and prints its result
+///
+/// #Returns
+/// The process exit code, errors are printed on stderr
+pub(crate) async fn run(context: &Context, command: Command, json: bool) -> ExitCode {
+    let result = match timeout(COMMAND_TIMEOUT, execute(context, command)).await {
+        Ok(result) => result,
+        Err(elapsed) => Err(elapsed.into()),
+    };
+    match result {
+        Ok(output) => {
+            output.print(json);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<CommandError>() {
+        Some(CommandError::UnknownTournament(_)) => return EXIT_NOT_FOUND,
+        Some(_) => return EXIT_AUTH,
+        None => {}
+    }
+    if error.downcast_ref::<Elapsed>().is_some() {
+        return EXIT_UNREACHABLE;
+    }
+    match error.downcast_ref::<ApiError>() {
+        Some(ApiError::Transport(_)) => EXIT_UNREACHABLE,
+        Some(e) => match e.status().map(|s| s.as_u16()) {
+            Some(401 | 403) => EXIT_AUTH,
+            Some(404) => EXIT_NOT_FOUND,
+            _ => EXIT_FAILURE,
+        },
+        None => EXIT_FAILURE,
+    }
+}
+
+async fn execute(context: &Context, command: Command) -> Result<Output> {
+    match command {
+        Command::Signup {
+            username,
+            email,
+            password,
+        } => {
+            let password = read_password(password)?;
+            register(context, &(username, password.clone(), email.clone())).await?;
+            let token = request_token(context, &(email, password, String::new())).await?;
+            whoami(&context.api().with_token(&token)).await
+        }
+        Command::Login {
+            email,
+            password,
+            totp,
+        } => {
+            let password = read_password(password)?;
+            let token =
+                request_token(context, &(email, password, totp.unwrap_or_default())).await?;
+            whoami(&context.api().with_token(&token)).await
+        }
+        Command::Guest => {
+            let token = request_guest_token(context).await?;
+            whoami(&context.api().with_token(&token)).await
+        }
+        Command::Logout => {
+            let token = context.session.load().ok_or(CommandError::NotLoggedIn)?;
+            context.session.clear()?;
+            let answer = context.api().with_token(&token).logout().await?;
+            Output::message(answer.message)
+        }
+        Command::Whoami => {
+            let (_, profile) = authenticate(context).await?;
+            show_user(profile)
+        }
+        Command::Friends(command) => friends(context, command).await,
+        Command::History { user } => history(&context.api(), &user).await,
+        Command::Leaderboard { count } => leaderboard(&context.api(), count).await,
+        Command::Tournament(command) => tournament(&context.api(), command).await,
+    }
+}
+
+/// Client for the stored session and the profile it belongs to
+async fn authenticate(context: &Context) -> Result<(ApiClient, Profile)> {
+    let token = context.session.load().ok_or(CommandError::NotLoggedIn)?;
+    let api = context.api().with_token(&token);
+    let profile = match api.profile_from_token().await {
+        Ok(profile) => profile,
+        // same statuses as login::resume_session
+        Err(e) if matches!(e.status().map(|s| s.as_u16()), Some(400 | 401 | 403 | 404)) => {
+            return Err(CommandError::SessionRejected.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok((api, profile))
+}
+
+/// The password is never prompted for, it would be echoed on the terminal
+fn read_password(password: Option<String>) -> Result<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(anyhow!(
+            "no password: use --password, PONG_PASSWORD or pipe it on stdin"
+        ));
+    }
+    let mut password = String::new();
+    stdin.read_to_string(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn whoami(api: &ApiClient) -> Result<Output> {
+    show_user(api.profile_from_token().await?)
+}
+
+pub(crate) fn show_user(profile: Profile) -> Result<Output> {
+    let user = User::from(profile);
+    let text = vec![user.line()];
+    Output::new(&user, text)
+}
+
+async fn friends(context: &Context, command: FriendsCommand) -> Result<Output> {
+    let (api, profile) = authenticate(context).await?;
+    let answer = match command {
+        FriendsCommand::List => return show_friends(&get_friends(&api, profile.id).await?),
+        FriendsCommand::Add { name } => send_friend_request(&api, &name).await?,
+        FriendsCommand::Accept { name } => send_accept_friend_request(&api, &name).await?,
+        FriendsCommand::Remove { name } => send_delete_friend_request(&api, &name).await?,
+    };
+    Output::message(answer)
+}
+
+pub(crate) fn show_friends(friends: &[Friend]) -> Result<Output> {
+    let text = friends
+        .iter()
+        .map(|friend| {
+            let state = if friend.pending { "pending" } else { "accepted" };
+            format!("{}\t{}\t{}", friend.id, friend.name, state)
+        })
+        .collect();
+    Output::new(&friends, text)
+}
+
+pub(crate) async fn history(api: &ApiClient, user: &str) -> Result<Output> {
+    // an unknown user has an empty history, look it up to fail instead
+    api.profile_by_name(user).await?;
+    let records = api.history(user).await?;
+    let mut names: HashMap<u64, String> = HashMap::new();
+    let mut matches: Vec<Match> = vec![];
+    for record in records {
+        for id in [record.player1_id, record.player2_id, record.winner_id] {
+            if id != 0 && !names.contains_key(&id) {
+                let name = api.profile_by_id(id).await?.name;
+                names.insert(id, name);
+            }
+        }
+        matches.push(Match {
+            played_at: record.played_at,
+            player1: names.get(&record.player1_id).cloned().unwrap_or_default(),
+            score1: record.score1,
+            player2: names.get(&record.player2_id).cloned().unwrap_or_default(),
+            score2: record.score2,
+            winner: names.get(&record.winner_id).cloned().unwrap_or_default(),
+        });
+    }
+    let text = matches
+        .iter()
+        .map(|m| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                m.played_at, m.player1, m.score1, m.player2, m.score2, m.winner
+            )
+        })
+        .collect();
+    Output::new(&matches, text)
+}
+
+pub(crate) async fn leaderboard(api: &ApiClient, count: u64) -> Result<Output> {
+    let mut ranking: Vec<Ranked> = vec![];
+    for (index, entry) in api.best_elo(count).await?.into_iter().enumerate() {
+        let user = User::from(api.profile_by_id(entry.id).await?);
+        ranking.push(Ranked {
+            rank: index + 1,
+            user,
+        });
+    }
+    let text = ranking
+        .iter()
+        .map(|ranked| format!("{}\t{}", ranked.rank, ranked.user.line()))
+        .collect();
+    Output::new(&ranking, text)
+}
+
+async fn tournament(api: &ApiClient, command: TournamentCommand) -> Result<Output> {
+    let results = chain::results(api).await?;
+    match command {
+        TournamentCommand::Results => {
+            let text = results
+                .iter()
+                .map(|result| {
+                    format!(
+                        "{}\t{}\t{}",
+                        result.number,
+                        result.address,
+                        result.winner_name()
+                    )
+                })
+                .collect();
+            Output::new(&results, text)
+        }
+        TournamentCommand::Rankings => {
+            let rankings = chain::rankings(&results);
+            let text = rankings
+                .iter()
+                .map(|ranking| format!("{}\t{}\t{}", ranking.rank, ranking.winner, ranking.titles))
+                .collect();
+            Output::new(&rankings, text)
+        }
+        TournamentCommand::Verify { id } => verify(api, &results, &id).await,
+    }
+}
+
+/// Prints the checks when they all pass, fails with the ones that did not otherwise
+async fn verify(api: &ApiClient, results: &[ChainResult], id: &str) -> Result<Output> {
+    let result = chain::find(results, id).ok_or(CommandError::UnknownTournament(id.to_string()))?;
+    let checks = chain::verify(api, results, result).await?;
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|check| !check.passed)
+        .map(|check| check.detail.as_str())
+        .collect();
+    if !failed.is_empty() {
+        bail!(
+            "tournament {} does not match the match history: {}",
+            result.number,
+            failed.join(", ")
+        );
+    }
+    let ranking = chain::rankings(results)
+        .into_iter()
+        .find(|ranking| ranking.winner == result.winner_name());
+    let mut text = vec![
+        format!(
+            "{}\t{}\t{}",
+            result.number,
+            result.address,
+            result.winner_name()
+        ),
+        result.explorer(),
+    ];
+    if let Some(ranking) = &ranking {
+        text.push(format!(
+            "rank {} with {} titles",
+            ranking.rank, ranking.titles
+        ));
+    }
+    text.extend(checks.iter().map(|check| format!("ok\t{}", check.detail)));
+    let verified = Verified {
+        result: result.clone(),
+        explorer: result.explorer(),
+        ranking,
+        checks,
+    };
+    Output::new(&verified, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::mock::{MockServer, temp_path};
+    use crate::tls::TrustPolicy;
+    use serde_json::{Value, json};
+
+    #[tokio::test]
+    async fn failures_have_their_own_exit_code() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let context = server.context();
+        assert_eq!(
+            run(&context, Command::Whoami, false).await,
+            ExitCode::from(EXIT_AUTH)
+        );
+        let unknown = Command::History {
+            user: "nobody".to_string(),
+        };
+        assert_eq!(
+            run(&context, unknown, false).await,
+            ExitCode::from(EXIT_NOT_FOUND)
+        );
+        server.reply("/user/get_best_elo", 500, r#"{"message":"database down"}"#);
+        assert_eq!(
+            run(&context, Command::Leaderboard { count: 3 }, false).await,
+            ExitCode::from(EXIT_FAILURE)
+        );
+        let known = Command::History {
+            user: "alice".to_string(),
+        };
+        assert_eq!(run(&context, known, false).await, ExitCode::SUCCESS);
+
+        let file = temp_path("rejected-session");
+        let context = server.context_with_session(Some(file.clone()));
+        context.session.save(&server.token(alice)).unwrap();
+        server.reply(
+            "/user/get_profile_token",
+            401,
+            r#"{"message":"invalid token"}"#,
+        );
+        assert_eq!(
+            run(&context, Command::Whoami, false).await,
+            ExitCode::from(EXIT_AUTH)
+        );
+        std::fs::remove_file(&file).unwrap();
+
+        let settings = Settings {
+            server: "127.0.0.1:1".to_string(),
+            trust: TrustPolicy::Insecure,
+            ..Settings::default()
+        };
+        let unreachable = Context::new(&settings).unwrap();
+        assert_eq!(
+            run(&unreachable, Command::Leaderboard { count: 3 }, false).await,
+            ExitCode::from(EXIT_UNREACHABLE)
+        );
+    }
+
+    #[tokio::test]
+    async fn json_output_can_be_parsed() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.befriend(alice, bob, false);
+        server.befriend(carol, alice, true);
+        let file = temp_path("json-session");
+        let context = server.context_with_session(Some(file.clone()));
+        context.session.save(&server.token(alice)).unwrap();
+
+        let whoami = execute(&context, Command::Whoami).await.unwrap();
+        let user: Value = serde_json::from_str(&whoami.printed(true)).unwrap();
+        assert_eq!(
+            (&user["id"], &user["name"]),
+            (&json!(alice), &json!("alice"))
+        );
+        for field in ["elo", "games_played", "wins"] {
+            assert!(user[field].is_number(), "{} missing from {}", field, user);
+        }
+        assert_eq!(whoami.printed(false).split('\t').nth(1), Some("alice"));
+
+        let list = execute(&context, Command::Friends(FriendsCommand::List))
+            .await
+            .unwrap();
+        let mut friends: Vec<Value> = serde_json::from_str(&list.printed(true)).unwrap();
+        friends.sort_by_key(|friend| friend["id"].as_u64());
+        assert_eq!(
+            friends,
+            vec![
+                json!({"id": bob, "name": "bob", "pending": false}),
+                json!({"id": carol, "name": "carol", "pending": true}),
+            ]
+        );
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+use crate::commands::Command;
+use crate::session::session_file;
+use crate::tls::{Fingerprint, TrustPolicy};
+use anyhow::{Context as _, Result, anyhow};
+use clap::Parser;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "transcendence_cli";
+const DEFAULT_PROFILE: &str = "default";
+
+/// Play Pong on the ft_transcendence platform from your terminal
+#[derive(Parser)]
+#[command(version, about)]
+pub(crate) struct Cli {
+    /// Server address (host:port), overrides the one set in the profile
+    #[arg(short, long, env = "PONG_SERVER")]
+    pub(crate) server: Option<String>,
+    /// Named server profile from the configuration file
+    #[arg(short, long, env = "PONG_PROFILE")]
+    pub(crate) profile: Option<String>,
+    /// Configuration file to use instead of the default one
+    #[arg(short, long, env = "PONG_CONFIG")]
+    pub(crate) config: Option<PathBuf>,
+    /// PEM file of certificates to trust, such as the stack's transcendence.crt
+    #[arg(long, env = "PONG_CA_FILE", conflicts_with_all = ["pin", "insecure"])]
+    pub(crate) ca_file: Option<PathBuf>,
+    /// Only accept the server certificate with this SHA-256 fingerprint
+    #[arg(long, env = "PONG_PIN", conflicts_with = "insecure")]
+    pub(crate) pin: Option<Fingerprint>,
+    /// known_hosts file used to remember self-signed certificates on first use
+    #[arg(long, env = "PONG_KNOWN_HOSTS")]
+    pub(crate) known_hosts: Option<PathBuf>,
+    /// Accept any certificate, the connection can then be intercepted
+    #[arg(long)]
+    pub(crate) insecure: bool,
+    /// Line oriented prompt instead of the full screen interface, for dumb terminals and screen readers
+    #[arg(long)]
+    pub(crate) shell: bool,
+    /// Verbosity of the log file: off, error, warn, info, debug or trace
+    #[arg(long, env = "PONG_LOG", default_value = "info", global = true)]
+    pub(crate) log_level: String,
+    /// Save a replay of every match played, see the Replays menu
+    #[arg(long, env = "PONG_RECORD")]
+    pub(crate) record: bool,
+    /// Print the result of a command as JSON
+    #[arg(long, global = true)]
+    pub(crate) json: bool,
+    /// Run a single command instead of the full screen interface
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+/// Content of `config.toml`
+///
+/// ```toml
+/// default_profile = "local"
+///
+/// [profiles.local]
+/// server = "172.17.0.1:8443"
+/// ca_file = "transcendence.crt"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    pub(crate) default_profile: Option<String>,
+    pub(crate) profiles: BTreeMap<String, ServerProfile>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ServerProfile {
+    pub(crate) server: Option<String>,
+    /// Relative paths are taken from the configuration file directory
+    pub(crate) ca_file: Option<PathBuf>,
+    pub(crate) pin: Option<String>,
+    pub(crate) known_hosts: Option<PathBuf>,
+    pub(crate) insecure: bool,
+}
+
+impl ConfigFile {
+    /// Reads the configuration file, a missing file is an empty configuration
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+    }
+}
+
+/// Configuration resolved from the command line, the environment and the config file
+#[derive(Default, Clone)]
+pub(crate) struct Settings {
+    pub(crate) server: String,
+    pub(crate) trust: TrustPolicy,
+    pub(crate) session_file: Option<PathBuf>,
+    /// Command history of the shell
+    pub(crate) history_file: Option<PathBuf>,
+    /// Rotated log files
+    pub(crate) log_dir: Option<PathBuf>,
+    /// Recorded matches
+    pub(crate) replay_dir: Option<PathBuf>,
+    pub(crate) record: bool,
+}
+
+impl Settings {
+    /// Command line and environment take precedence over the configuration file
+    pub(crate) fn resolve(cli: &Cli) -> Result<Self> {
+        let path = match &cli.config {
+            Some(path) => path.clone(),
+            None => default_config_path()?,
+        };
+        let file = ConfigFile::load(&path)?;
+        let explicit_profile = cli.profile.is_some();
+        let profile = cli
+            .profile
+            .clone()
+            .or(file.default_profile.clone())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        let server_profile = match file.profiles.get(&profile) {
+            Some(found) => found.clone(),
+            None if explicit_profile => {
+                return Err(anyhow!(
+                    "profile '{}' not found in {}",
+                    profile,
+                    path.display()
+                ));
+            }
+            None => ServerProfile::default(),
+        };
+        let trust = trust_policy(cli, &server_profile, &path)
+            .with_context(|| format!("profile '{}' in {}", profile, path.display()))?;
+        let server = cli
+            .server
+            .clone()
+            .or(server_profile.server)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no server configured: use --server or set one for profile '{}' in {}",
+                    profile,
+                    path.display()
+                )
+            })?;
+        let session_file = Some(session_file(&data_dir()?, &server));
+        Ok(Settings {
+            server,
+            trust,
+            session_file,
+            history_file: Some(data_dir()?.join("history")),
+            log_dir: Some(data_dir()?.join("logs")),
+            replay_dir: Some(data_dir()?.join("replays")),
+            record: cli.record,
+        })
+    }
+}
+
+/// Command line trust options replace the profile ones as a whole,
+/// without any of them certificates are trusted on first use
+fn trust_policy(cli: &Cli, profile: &ServerProfile, config_path: &Path) -> Result<TrustPolicy> {
+    if cli.insecure {
+        return Ok(TrustPolicy::Insecure);
+    }
+    if let Some(pin) = cli.pin {
+        return Ok(TrustPolicy::Pinned(pin));
+    }
+    if let Some(ca_file) = &cli.ca_file {
+        return Ok(TrustPolicy::CaBundle(ca_file.clone()));
+    }
+    let base = config_path.parent().unwrap_or(Path::new(""));
+    let chosen = [
+        profile.insecure,
+        profile.pin.is_some(),
+        profile.ca_file.is_some(),
+    ];
+    if chosen.iter().filter(|set| **set).count() > 1 {
+        return Err(anyhow!("only one of insecure, pin and ca_file can be set"));
+    }
+    if profile.insecure {
+        return Ok(TrustPolicy::Insecure);
+    }
+    if let Some(pin) = &profile.pin {
+        return Ok(TrustPolicy::Pinned(pin.parse()?));
+    }
+    if let Some(ca_file) = &profile.ca_file {
+        return Ok(TrustPolicy::CaBundle(base.join(ca_file)));
+    }
+    let known_hosts = match (&cli.known_hosts, &profile.known_hosts) {
+        (Some(path), _) => path.clone(),
+        (None, Some(path)) => base.join(path),
+        (None, None) => data_dir()?.join("known_hosts"),
+    };
+    Ok(TrustPolicy::Tofu(known_hosts))
+}
+
+fn config_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .ok_or_else(|| anyhow!("could not find a configuration directory"))
+}
+
+fn data_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .ok_or_else(|| anyhow!("could not find a data directory"))
+}
+
+fn default_config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::temp_path;
+    use std::sync::Mutex;
+
+    /// Held by the tests depending on PONG_SERVER and PONG_PROFILE, one of them sets both
+    static ENV: Mutex<()> = Mutex::new(());
+    const PIN: &str = "sha256:00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+    fn cli(args: &[&str]) -> Result<Cli> {
+        Ok(Cli::try_parse_from(["cli_app"].iter().chain(args.iter()))?)
+    }
+
+    /// Writes `content` as the config.toml of a fresh directory
+    fn config(name: &str, content: &str) -> PathBuf {
+        let dir = temp_path(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn resolve(args: &[&str]) -> Result<Settings> {
+        Settings::resolve(&cli(args)?)
+    }
+
+    #[test]
+    fn flags_win_over_the_environment_and_the_profile() {
+        let path = config(
+            "precedence",
+            &format!(
+                r#"
+default_profile = "local"
+
+[profiles.local]
+server = "local:8443"
+pin = "{}"
+
+[profiles.staging]
+server = "staging:443"
+"#,
+                PIN
+            ),
+        );
+        let path = path.to_str().unwrap();
+        let _env = ENV.lock().unwrap();
+        let settings = resolve(&["--config", path]).unwrap();
+        assert_eq!(settings.server, "local:8443");
+        assert!(matches!(settings.trust, TrustPolicy::Pinned(pin) if pin == PIN.parse().unwrap()));
+        // a trust flag replaces the profile's
+        let settings = resolve(&["--config", path, "--insecure"]).unwrap();
+        assert!(matches!(settings.trust, TrustPolicy::Insecure));
+
+        // SAFETY: other tests read the environment through std, which locks it, and the ones
+        // resolving without --profile or --server hold ENV
+        unsafe {
+            std::env::set_var("PONG_SERVER", "env:8443");
+            std::env::set_var("PONG_PROFILE", "staging");
+        }
+        let from_env = resolve(&["--config", path]);
+        let from_flags = resolve(&["--config", path, "--profile", "local", "-s", "flag:8443"]);
+        unsafe {
+            std::env::remove_var("PONG_SERVER");
+            std::env::remove_var("PONG_PROFILE");
+        }
+        let from_env = from_env.unwrap();
+        assert_eq!(from_env.server, "env:8443");
+        // staging sets no trust option, certificates are trusted on first use
+        assert!(matches!(from_env.trust, TrustPolicy::Tofu(_)));
+        let from_flags = from_flags.unwrap();
+        assert_eq!(from_flags.server, "flag:8443");
+        assert!(matches!(from_flags.trust, TrustPolicy::Pinned(_)));
+        std::fs::remove_dir_all(Path::new(path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn without_a_default_profile_the_one_named_default_is_used() {
+        let path = config(
+            "default-profile",
+            "[profiles.default]\nserver = \"default:8443\"\n",
+        );
+        let _env = ENV.lock().unwrap();
+        let settings = resolve(&["--config", path.to_str().unwrap()]).unwrap();
+        assert_eq!(settings.server, "default:8443");
+        // only a profile asked for has to exist
+        let missing = temp_path("missing-config.toml");
+        let error = resolve(&["--config", missing.to_str().unwrap()])
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("no server configured"));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unknown_profiles_are_refused() {
+        let path = config(
+            "unknown-profile",
+            "[profiles.local]\nserver = \"local:8443\"\n",
+        );
+        let error = resolve(&["--config", path.to_str().unwrap(), "--profile", "demo"])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("profile 'demo' not found in {}", path.display())
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn trust_options_exclude_each_other() {
+        assert!(cli(&["--insecure", "--pin", PIN]).is_err());
+        assert!(cli(&["--ca-file", "ca.pem", "--pin", PIN]).is_err());
+        let path = config(
+            "conflicting-trust",
+            &format!(
+                "[profiles.local]\nserver = \"local:8443\"\ninsecure = true\npin = \"{}\"\n",
+                PIN
+            ),
+        );
+        let error = resolve(&["--config", path.to_str().unwrap(), "--profile", "local"])
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            format!(
+                "profile 'local' in {}: only one of insecure, pin and ca_file can be set",
+                path.display()
+            )
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn relative_paths_are_taken_from_the_config_directory() {
+        let path = config(
+            "relative-paths",
+            "[profiles.local]\nserver = \"local:8443\"\nca_file = \"certs/ca.pem\"\n\n\
+             [profiles.tofu]\nserver = \"local:8443\"\nknown_hosts = \"known_hosts\"\n",
+        );
+        let dir = path.parent().unwrap();
+        let config = path.to_str().unwrap();
+        let settings = resolve(&["--config", config, "--profile", "local"]).unwrap();
+        assert!(
+            matches!(settings.trust, TrustPolicy::CaBundle(ca) if ca == dir.join("certs/ca.pem"))
+        );
+        let settings = resolve(&["--config", config, "--profile", "tofu"]).unwrap();
+        assert!(
+            matches!(settings.trust, TrustPolicy::Tofu(known) if known == dir.join("known_hosts"))
+        );
+        // given on the command line, they stay relative to the working directory
+        let settings = resolve(&[
+            "--config",
+            config,
+            "--profile",
+            "local",
+            "--ca-file",
+            "ca.pem",
+        ])
+        .unwrap();
+        assert!(matches!(settings.trust, TrustPolicy::CaBundle(ca) if ca == Path::new("ca.pem")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
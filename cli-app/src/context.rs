@@ -1,28 +1,53 @@
+use crate::api::ApiClient;
+use crate::config::Settings;
+use crate::session::Session;
+use crate::tls;
+use anyhow::Result;
 use reqwest::Client;
+use rustls::ClientConfig;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_tungstenite::Connector;
 
 pub(crate) struct Context {
     pub(crate) location: String,
     pub(crate) client: Client,
+    pub(crate) session: Session,
+    pub(crate) replay_dir: Option<PathBuf>,
+    /// Matches are recorded to `replay_dir`
+    pub(crate) record: bool,
+    tls: Arc<ClientConfig>,
 }
 
 /*
-We must accept invalid ssl certificates in order to work with
-self signed certificates generated by the server
+The server certificate is self signed, the trust policy resolved from
+the settings is shared by the HTTP client and every websocket
 */
 impl Context {
-    pub(crate) fn new(location: String) -> Self {
-        Context {
-            location,
+    pub(crate) fn new(settings: &Settings) -> Result<Self> {
+        let tls = tls::client_config(&settings.trust, &settings.server)?;
+        Ok(Context {
+            location: settings.server.clone(),
             client: Client::builder()
-                .danger_accept_invalid_certs(true)
-                .build()
-                .expect("Impossible to build new client, try again"),
-        }
+                .use_preconfigured_tls((*tls).clone())
+                .build()?,
+            session: Session::new(settings.session_file.clone()),
+            replay_dir: settings.replay_dir.clone(),
+            record: settings.record,
+            tls,
+        })
+    }
+    pub(crate) fn api(&self) -> ApiClient {
+        ApiClient::new(self)
+    }
+    ///TLS connector for websockets, enforcing the same policy as the HTTP client
+    pub(crate) fn connector(&self) -> Connector {
+        Connector::Rustls(self.tls.clone())
     }
 }
 
 impl Default for Context {
     fn default() -> Self {
-        Context::new(String::new())
+        Context::new(&Settings::default()).expect("Impossible to build new client, try again")
     }
 }
@@ -0,0 +1,145 @@
+use crate::api::{ApiClient, Profile};
+use anyhow::{Result, bail};
+
+/// Ids of the users we blocked
+pub(crate) async fn blocked(api: &ApiClient, me: u64) -> Result<Vec<u64>> {
+    Ok(api
+        .blocked_users()
+        .await?
+        .iter()
+        .filter_map(|link| link.other(me))
+        .collect())
+}
+
+/// Names of the users we blocked, their conversations are hidden
+pub(crate) async fn blocked_names(api: &ApiClient, me: u64) -> Result<Vec<String>> {
+    let mut names = vec![];
+    for id in blocked(api, me).await? {
+        names.push(api.profile_by_id(id).await?.name);
+    }
+    Ok(names)
+}
+
+/// Resolves the user to write to
+///
+/// The server answers "Success" to messages for the users we blocked without delivering them,
+/// they are refused here instead
+pub(crate) async fn recipient(api: &ApiClient, me: u64, name: &str) -> Result<Profile> {
+    let profile = api.profile_by_name(name.trim()).await?;
+    if profile.id == me {
+        bail!("You cannot write to yourself");
+    }
+    if blocked(api, me).await?.contains(&profile.id) {
+        bail!("You blocked {}", profile.name);
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{LineKind, Outgoing, join};
+    use crate::mock::MockServer;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn recipient_refuses_blocked_users_and_ourselves() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.block(alice, carol);
+        let api = server.context().api().with_token(&server.token(alice));
+        assert_eq!(recipient(&api, alice, " bob ").await.unwrap().id, bob);
+        let refused = recipient(&api, alice, "carol").await.err().unwrap();
+        assert_eq!(refused.to_string(), "You blocked carol");
+        assert!(recipient(&api, alice, "alice").await.is_err());
+        assert!(recipient(&api, alice, "dave").await.is_err());
+        assert_eq!(blocked_names(&api, alice).await.unwrap(), vec!["carol"]);
+    }
+
+    #[tokio::test]
+    async fn conversations_count_unread_messages() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(alice)).await.unwrap();
+        let bob_room = join(&server.context(), &server.token(bob)).await.unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        assert!(server.wait_chat(bob, true).await);
+        bob_room
+            .outgoing
+            .send(Outgoing::Direct {
+                to: "alice".to_string(),
+                text: "rematch?".to_string(),
+            })
+            .unwrap();
+        timeout(
+            Duration::from_secs(5),
+            room.log.wait_for(|log| log.unread() == 1),
+        )
+        .await
+        .expect("direct message timed out")
+        .unwrap();
+        {
+            let log = room.log.borrow();
+            let conversation = log.conversation("bob").unwrap();
+            assert_eq!(conversation.lines[0].text, "rematch?");
+            assert!(log.lines.is_empty());
+        }
+        room.outgoing
+            .send(Outgoing::Read("bob".to_string()))
+            .unwrap();
+        room.outgoing
+            .send(Outgoing::Direct {
+                to: "bob".to_string(),
+                text: "sure".to_string(),
+            })
+            .unwrap();
+        timeout(
+            Duration::from_secs(5),
+            room.log.wait_for(|log| {
+                log.conversation("bob")
+                    .is_some_and(|conversation| conversation.lines.len() == 2)
+            }),
+        )
+        .await
+        .expect("own message timed out")
+        .unwrap();
+        let log = room.log.borrow();
+        assert_eq!(log.unread(), 0);
+        assert_eq!(
+            log.conversation("bob").unwrap().lines[1].kind,
+            LineKind::Own
+        );
+    }
+
+    #[tokio::test]
+    async fn offline_recipient_is_reported_in_the_conversation() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        server.add_user("bob", "bob@mail.com", "secret");
+        let mut room = join(&server.context(), &server.token(alice)).await.unwrap();
+        room.outgoing
+            .send(Outgoing::Direct {
+                to: "bob".to_string(),
+                text: "hi".to_string(),
+            })
+            .unwrap();
+        timeout(
+            Duration::from_secs(5),
+            room.log.wait_for(|log| {
+                log.conversation("bob")
+                    .is_some_and(|conversation| conversation.lines.len() == 2)
+            }),
+        )
+        .await
+        .expect("direct message timed out")
+        .unwrap();
+        let log = room.log.borrow();
+        let lines = &log.conversation("bob").unwrap().lines;
+        assert_eq!(lines[1].kind, LineKind::Server);
+        assert_eq!(log.unread(), 0);
+    }
+}
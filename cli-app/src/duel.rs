@@ -0,0 +1,132 @@
+use crate::api::{ApiClient, Profile};
+use crate::dm::blocked;
+use anyhow::{Result, anyhow, bail};
+
+/// Duel invitation waiting for an answer
+pub(crate) struct PendingDuel {
+    /// The other player
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    /// Sent to us, we are the one to accept or decline it
+    pub(crate) incoming: bool,
+}
+
+/// Invitations sent and received, with the other player's name resolved
+pub(crate) async fn pending_duels(api: &ApiClient, me: u64) -> Result<Vec<PendingDuel>> {
+    let mut result = vec![];
+    for duel in api.duels().await? {
+        let (id, incoming) = match duel.sender_id == me {
+            true => (duel.id, false),
+            false => (duel.sender_id, true),
+        };
+        result.push(PendingDuel {
+            id,
+            name: api.profile_by_id(id).await?.name,
+            incoming,
+        });
+    }
+    Ok(result)
+}
+
+/// Invites `name` to a duel, the match is announced on the chat socket once accepted
+///
+/// The server answers "invite sent" to the users we blocked without inviting them,
+/// they are refused here instead
+///
+/// #Returns
+/// The invited player
+pub(crate) async fn challenge(api: &ApiClient, me: u64, name: &str) -> Result<Profile> {
+    let profile = api.profile_by_name(name.trim()).await?;
+    if blocked(api, me).await?.contains(&profile.id) {
+        bail!("You blocked {}", profile.name);
+    }
+    api.invite_duel(profile.id).await?;
+    Ok(profile)
+}
+
+/// Accepts the duel sent by `id`, the match is announced on the chat socket
+pub(crate) async fn accept(api: &ApiClient, id: u64) -> Result<()> {
+    let answer = api.accept_duel(id).await?;
+    if answer.id.is_none() {
+        return Err(anyhow!("{}", answer.message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::join;
+    use crate::mock::MockServer;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn pending_duels_are_named_both_ways() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.block(alice, carol);
+        let alice_api = server.context().api().with_token(&server.token(alice));
+        let bob_api = server.context().api().with_token(&server.token(bob));
+        assert_eq!(challenge(&alice_api, alice, "bob").await.unwrap().id, bob);
+        let refused = challenge(&alice_api, alice, "carol").await.err().unwrap();
+        assert_eq!(refused.to_string(), "You blocked carol");
+        let sent = pending_duels(&alice_api, alice).await.unwrap();
+        let received = pending_duels(&bob_api, bob).await.unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!((sent[0].name.as_str(), sent[0].incoming), ("bob", false));
+        assert_eq!((received[0].id, received[0].incoming), (alice, true));
+        assert!(accept(&alice_api, bob).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn accepted_duel_is_announced_to_both_players() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let mut alice_room = join(&server.context(), &server.token(alice)).await.unwrap();
+        let mut bob_room = join(&server.context(), &server.token(bob)).await.unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        assert!(server.wait_chat(bob, true).await);
+        let alice_api = server.context().api().with_token(&server.token(alice));
+        let bob_api = server.context().api().with_token(&server.token(bob));
+        challenge(&alice_api, alice, "bob").await.unwrap();
+        accept(&bob_api, alice).await.unwrap();
+        let to_alice = alice_room.receiver.recv().await.unwrap();
+        let to_bob = bob_room.receiver.recv().await.unwrap();
+        assert_eq!(to_alice.game_id, to_bob.game_id);
+        assert_eq!(
+            (to_alice.mode.as_str(), to_alice.opponent_id),
+            ("duel", bob)
+        );
+        assert_eq!((to_bob.player_side, to_bob.opponent_id), (2, alice));
+        assert!(pending_duels(&bob_api, bob).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn declined_duel_is_flagged_in_the_chat() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let mut alice_room = join(&server.context(), &server.token(alice)).await.unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        let alice_api = server.context().api().with_token(&server.token(alice));
+        let bob_api = server.context().api().with_token(&server.token(bob));
+        challenge(&alice_api, alice, "bob").await.unwrap();
+        bob_api.decline_duel(alice).await.unwrap();
+        timeout(
+            Duration::from_secs(5),
+            alice_room.log.wait_for(|log| {
+                log.lines
+                    .iter()
+                    .any(|line| line.flag.as_deref() == Some("duelDeclined"))
+            }),
+        )
+        .await
+        .expect("decline timed out")
+        .unwrap();
+        assert!(pending_duels(&alice_api, alice).await.unwrap().is_empty());
+    }
+}
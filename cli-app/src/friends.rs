@@ -1,275 +1,166 @@
-use crate::Auth;
-use crate::Context;
-use crate::CurrentScreen;
-use crate::utils::should_exit;
-use crate::utils::{get_id_from_name, get_name_from_id};
+use crate::api::ApiClient;
 use anyhow::{Result, anyhow};
-use crossterm::event::poll;
-use crossterm::event::{self, Event, KeyCode};
-use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
-use std::rc::Rc;
+use serde::Serialize;
 use std::time::Duration;
-use reqwest::header::HeaderMap;
 
-#[derive(Default)]
-pub(crate) struct Friends {
-    auth: Rc<RefCell<Auth>>,
-    context: Rc<Context>,
-    screen: Rc<Cell<CurrentScreen>>,
-    pub(crate) index: usize,
-    pub(crate) index_max: usize,
-    pub(crate) friends_list: Vec<String>,
-    pub(crate) friend_tmp: String,
-    pub(crate) blink: bool,
+#[derive(Serialize)]
+pub(crate) struct Friend {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    /// The friend request was not accepted yet
+    pub(crate) pending: bool,
 }
 
-impl Friends {
-    pub(crate) fn new(
-        context: Rc<Context>,
-        auth: Rc<RefCell<Auth>>,
-        screen: Rc<Cell<CurrentScreen>>,
-    ) -> Self {
-        Friends {
-            auth,
-            context,
-            screen,
-            ..Default::default()
-        }
-    }
-    ///Get friends list with pending attribute if friend is not accepted yet
-    pub(crate) async fn get_indexed_friends(&mut self) -> Result<()> {
-        let friends_list = self.get_all_friends().await?;
-        let mut printable: Vec<String> = vec![];
-        let mut _str_tmp: String = String::new();
-        for element in &friends_list[..] {
-            _str_tmp = element.0.clone();
-            if !element.1 {
-                _str_tmp += " (Pending)";
-            }
-            printable.push(_str_tmp);
-        }
-        self.friends_list = printable;
-        Ok(())
-    }
-    ///Update friends display depending on the terminal's size
-    pub(crate) async fn update_friends_index(
-        &mut self,
-        terminal: &mut ratatui::DefaultTerminal,
-    ) -> Result<()> {
-        self.get_indexed_friends().await?;
-        let height: usize = (terminal.get_frame().area().height - 2) as usize;
-        let len = self.friends_list.len();
-        let modulo: usize = match height {
-            0 => 0,
-            _ => match len % height {
-                0 => 0,
-                _ => 1,
-            },
-        };
-        if height < len && height != 0 {
-            self.index_max = len / height + modulo;
+///Get friends list with pending attribute if friend is not accepted yet, and in game if playing
+pub(crate) async fn get_indexed_friends(api: &ApiClient, me: u64) -> Result<Vec<String>> {
+    let friends_list = get_friends(api, me).await?;
+    // a server without spectators only loses the mark
+    let playing = api.ongoing_games().await.unwrap_or_default();
+    let mut printable: Vec<String> = vec![];
+    for friend in friends_list {
+        if friend.pending {
+            printable.push(friend.name + " (Pending)");
+        } else if playing.iter().any(|game| game.has(friend.id)) {
+            printable.push(friend.name + " (in game)");
         } else {
-            self.index_max = 0;
+            printable.push(friend.name);
         }
-        if self.index > self.index_max {
-            self.index = 0;
-        }
-        Ok(())
     }
-    pub(crate) async fn add_friend(&mut self) -> Result<()> {
-        if poll(Duration::from_millis(500))? {
-            let event = event::read()?;
-            if should_exit(&event)? {
-                self.friend_tmp.clear();
-                self.screen.set(CurrentScreen::FriendsDisplay);
-            } else if let Event::Key(eventkey) = event {
-                match eventkey.code {
-                    KeyCode::Backspace => {
-                        self.friend_tmp.pop();
-                    }
-                    KeyCode::Char(c) => self.friend_tmp.push(c),
-                    KeyCode::Enter => {
-                        self.send_friend_request().await?;
-                        self.get_indexed_friends().await?;
-                    }
-                    _ => {}
-                }
-            }
-        }
-        self.tick();
-        Ok(())
+    Ok(printable)
+}
+
+/// #Returns
+/// The server's answer
+pub(crate) async fn send_friend_request(api: &ApiClient, name: &str) -> Result<String> {
+    let friend = api.profile_by_name(name).await?;
+    Ok(api.send_friend_request(friend.id).await?.message)
+}
+
+/// #Returns
+/// The server's answer
+pub(crate) async fn send_accept_friend_request(api: &ApiClient, name: &str) -> Result<String> {
+    let friend = api.profile_by_name(name).await?;
+    Ok(api.accept_friend(friend.id).await?.message)
+}
+
+/// #Returns
+/// The server's answer
+pub(crate) async fn send_delete_friend_request(api: &ApiClient, name: &str) -> Result<String> {
+    let friend = api.profile_by_name(name).await?;
+    Ok(api.remove_friend(friend.id).await?.message)
+}
+
+/// Friends of the user `me`, with their names resolved
+pub(crate) async fn get_friends(api: &ApiClient, me: u64) -> Result<Vec<Friend>> {
+    let links = tokio::time::timeout(Duration::from_secs(5), api.friends(me)).await??;
+    let mut result: Vec<Friend> = vec![];
+    for link in links {
+        let id = link
+            .other(me)
+            .ok_or_else(|| anyhow!("Friend link does not contain our id"))?;
+        let name = api.profile_by_id(id).await?.name;
+        result.push(Friend {
+            id,
+            name,
+            pending: link.is_pending(),
+        });
     }
-    pub(crate) async fn delete_friend(&mut self) -> Result<()> {
-        if poll(Duration::from_millis(500))? {
-            let event = event::read()?;
-            if should_exit(&event)? {
-                self.friend_tmp.clear();
-                self.screen.set(CurrentScreen::FriendsDisplay);
-            } else if let Event::Key(eventkey) = event {
-                match eventkey.code {
-                    KeyCode::Backspace => {
-                        self.friend_tmp.pop();
-                    }
-                    KeyCode::Char(c) => self.friend_tmp.push(c),
-                    KeyCode::Enter => {
-                        self.send_delete_friend_request().await?;
-                        self.get_indexed_friends().await?;
-                    }
-                    _ => {}
-                }
-            }
-        }
-        self.tick();
-        Ok(())
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[tokio::test]
+    async fn friends_are_named_and_flagged_pending() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.befriend(alice, bob, false);
+        server.befriend(carol, alice, true);
+        let api = server.context().api().with_token(&server.token(alice));
+        let friends = get_indexed_friends(&api, alice).await.unwrap();
+        assert_eq!(friends, vec!["bob", "carol (Pending)"]);
     }
-    async fn send_friend_request(&mut self) -> Result<()> {
-        let mut map = HashMap::new();
-        let mut header = HeaderMap::new();
-        let token = self.auth.borrow().token.to_string();
-        header.insert("Authorization", format!("Bearer {}", &token).parse()?);
-        header.insert("content-type", "application/json".parse()?);
-        let id = get_id_from_name(self.context.clone(), &self.friend_tmp)
-            .await?
-            .to_string();
-        map.insert("friend_id", &id);
-        let url = format!("https://{}/api/friends/send_request", self.context.location);
-        let response = self
-            .context
-            .client
-            .post(url)
-            .headers(header)
-            .json(&map)
-            .send()
-            .await?;
-        self.friend_tmp.clear();
-        match response.status().as_u16() {
-            200 => {
-                self.screen.set(CurrentScreen::FriendsDisplay);
-            }
-            _ => {
-                let message: serde_json::Value = response.json().await?;
-                if let Some(error_message) = message["message"].as_str() {
-                    return Err(anyhow!(error_message.to_string()));
-                }
-            }
-        }
-        Ok(())
+
+    #[tokio::test]
+    async fn friends_playing_are_flagged_in_game() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.befriend(alice, bob, false);
+        server.befriend(alice, carol, false);
+        server.play("watched", (carol, bob));
+        let api = server.context().api().with_token(&server.token(alice));
+        let friends = get_indexed_friends(&api, alice).await.unwrap();
+        assert_eq!(friends, vec!["bob (in game)", "carol (in game)"]);
     }
-    async fn send_delete_friend_request(&mut self) -> Result<()> {
-        let mut map = HashMap::new();
-        let mut header = HeaderMap::new();
-        let token = self.auth.borrow().token.to_string();
-        header.insert("Authorization", format!("Bearer {}", &token).parse()?);
-        header.insert("content-type", "application/json".parse()?);
-        let id = get_id_from_name(self.context.clone(), &self.friend_tmp)
-            .await?
-            .to_string();
-        map.insert("friend_id", &id);
-        let url = format!("https://{}/api/friends/remove", self.context.location);
-        let response = self
-            .context
-            .client
-            .delete(url)
-            .headers(header)
-            .json(&map)
-            .send()
-            .await?;
-        self.friend_tmp.clear();
-        match response.status().as_u16() {
-            200 => {
-                self.screen.set(CurrentScreen::FriendsDisplay);
-            }
-            _ => {
-                let message: serde_json::Value = response.json().await?;
-                if let Some(message) = message["message"].as_str() {
-                    return Err(anyhow!(message.to_string()));
-                }
-            }
-        }
-        Ok(())
+
+    #[tokio::test]
+    async fn requests_go_through_names() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let as_alice = server.context().api().with_token(&server.token(alice));
+        let as_bob = server.context().api().with_token(&server.token(bob));
+        assert_eq!(
+            send_friend_request(&as_alice, "bob").await.unwrap(),
+            "friend request sent"
+        );
+        assert!(get_friends(&as_bob, bob).await.unwrap()[0].pending);
+        send_accept_friend_request(&as_bob, "alice").await.unwrap();
+        assert!(!get_friends(&as_bob, bob).await.unwrap()[0].pending);
+        send_delete_friend_request(&as_bob, "alice").await.unwrap();
+        assert!(get_friends(&as_alice, alice).await.unwrap().is_empty());
     }
-    async fn get_all_friends(&self) -> Result<Vec<(String, bool)>> {
-        let url = format!(
-            "https://{}/api/friends/get?user_id={}",
-            self.context.location,
-            self.auth.borrow().id
+
+    #[tokio::test]
+    async fn unknown_name_is_not_found() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let api = server.context().api().with_token(&server.token(alice));
+        let error = send_friend_request(&api, "nobody").await.err().unwrap();
+        let status = error.downcast_ref::<crate::api::ApiError>().unwrap().status();
+        assert_eq!(status.map(|s| s.as_u16()), Some(404));
+    }
+
+    #[tokio::test]
+    async fn odd_payloads_are_accepted() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        server.add_user("bob", "bob@mail.com", "secret");
+        server.reply(
+            "/friends/get",
+            200,
+            r#"[{"user1_id":2,"user2_id":1,"pending":"1"}]"#,
         );
-        let response = tokio::time::timeout(Duration::from_secs(5), self.context.client.get(url).send()).await??;
-        let mut result: Vec<(String, bool)> = vec![];
-        match response.status().as_u16() {
-            200 => {
-                let response_array: serde_json::Value = response.json().await?;
-                if response_array.is_array() {
-                    let response_array = match response_array.as_array() {
-                        Some(array) => array,
-                        _ => {
-                            return Err(anyhow!("empty array"));
-                        }
-                    };
-                    for object in response_array {
-                        let map = match object.as_object() {
-                            Some(map) => map,
-                            _ => {
-                                continue;
-                            }
-                        };
-                        let name = self.look_for_name(object).await?;
-                        match map["pending"].as_u64() {
-                            Some(0) => {
-                                result.push((name, true));
-                            }
-                            Some(1) => {
-                                result.push((name, false));
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            404 => {
-                return Err(anyhow!("Error 404 from server"))
-            }
-            err => {
-                return Err(anyhow!("Error {} from server :(", err))
-            }
-        }
-        Ok(result)
+        let api = server.context().api();
+        let friends = get_friends(&api, alice).await.unwrap();
+        assert_eq!(friends[0].name, "bob");
+        assert!(friends[0].pending);
     }
-    ///Given a json containing two user_id including ours, return other id name
-    ///
-    /// The server sends a friends list containing 2 ids: ours and the friend's
-    ///
-    /// Function first compares to both id to get the friend's id
-    /// then sends a request to get name from id
-    async fn look_for_name(&self, object: &serde_json::Value) -> Result<String> {
-        let id_to_find = match object["user1_id"].as_u64() {
-            Some(user1) => {
-                let id = self.auth.borrow().id;
-                if user1 != id {
-                    user1
-                } else {
-                    match object["user2_id"].as_u64() {
-                        Some(user2) => {
-                            if user2 != id {
-                                user2
-                            } else {
-                                return Err(anyhow!("from user ids"));
-                            }
-                        }
-                        _ => {
-                            return Err(anyhow!("from user ids"));
-                        }
-                    }
-                }
-            }
-            _ => {
-                return Err(anyhow!("from user ids"));
-            }
-        };
-        get_name_from_id(self.context.clone(), id_to_find).await
+
+    #[tokio::test]
+    async fn link_without_our_id_is_an_error() {
+        let server = MockServer::start().await;
+        server.reply(
+            "/friends/get",
+            200,
+            r#"[{"user1_id":2,"user2_id":3,"pending":0}]"#,
+        );
+        let error = get_friends(&server.context().api(), 1).await.err().unwrap();
+        assert_eq!(error.to_string(), "Friend link does not contain our id");
     }
-    pub(crate) fn tick(&mut self) {
-        self.blink = !self.blink;
+
+    #[tokio::test]
+    async fn server_errors_carry_the_server_message() {
+        let server = MockServer::start().await;
+        server.reply("/friends/get", 500, r#"{"error":"database is locked"}"#);
+        let error = get_friends(&server.context().api(), 1).await.err().unwrap();
+        assert_eq!(error.to_string(), "database is locked (HTTP 500)");
     }
 }
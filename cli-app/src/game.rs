@@ -1,10 +1,14 @@
-use crate::Infos;
-use crate::utils::{get_name_from_id, should_exit};
+use crate::infos::Infos;
+use crate::api::MatchStart;
+use crate::protocol::{self, GameFrame};
+use crate::replay::{Recorder, ReplayInfo};
+use crate::smoothing::Smoother;
+use crate::utils::should_exit;
 use crate::{Auth, Context};
 use crate::infos::GameParams;
-use anyhow::{Result, anyhow, Error};
+use anyhow::{Result, anyhow, bail, Error};
 use bytes::Bytes;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, poll};
+use crossterm::event::{Event, KeyCode, KeyEventKind};
 use futures::stream::StreamExt;
 use futures_util::{
     SinkExt,
@@ -12,21 +16,43 @@ use futures_util::{
 };
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::TcpStream;
 use tokio::sync::{mpsc, watch};
+use tokio::time::timeout;
+use tracing::{debug, error, info, trace, warn};
 use tokio_tungstenite::{
-    Connector, MaybeTlsStream, WebSocketStream, connect_async_tls_with_config,
+    MaybeTlsStream, WebSocketStream, connect_async_tls_with_config,
     tungstenite::{Utf8Bytes, client::IntoClientRequest, protocol::Message},
 };
-use reqwest::header::HeaderMap;
 
-type GameChannel = (
-    watch::Sender<(Option<Bytes>, Option<Utf8Bytes>)>,
-    watch::Receiver<(Option<Bytes>, Option<Utf8Bytes>)>,
-);
 pub(crate) type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+/// Messages queued between the reading task and the screen, about a second of states
+const FRAME_QUEUE: usize = 64;
+/// A paddle key counts as held this long after its last press, terminals rarely report releases
+pub(crate) const HOLD: Duration = Duration::from_millis(150);
+/// Keys moving our paddle and the move sent for each
+const CONTROLS: &[(KeyCode, &str)] = &[(KeyCode::Up, "U"), (KeyCode::Down, "D")];
+/// Both paddles are driven from one keyboard in a local game, each move names its player
+const LOCAL_CONTROLS: &[(KeyCode, &str)] = &[
+    (KeyCode::Char('w'), "1U"),
+    (KeyCode::Char('s'), "1D"),
+    (KeyCode::Up, "2U"),
+    (KeyCode::Down, "2D"),
+];
+
+/// Something that happened on the game websocket
+pub(crate) enum GameUpdate {
+    /// State frame and when the socket received it
+    State(Instant, Bytes),
+    /// The server announced the winner
+    End(Utf8Bytes),
+    /// The match was left or the socket closed
+    Closed,
+    Failed(Error),
+}
+
 #[derive(Default)]
 pub(crate) struct Game {
     auth: Rc<RefCell<Auth>>,
@@ -34,20 +60,29 @@ pub(crate) struct Game {
     pub(crate) game_id: String,
     pub(crate) opponent_name: String,
     player_side: u64,
-    pub(crate) receiver: Option<watch::Receiver<(Option<Bytes>, Option<Utf8Bytes>)>>,
+    mode: String,
+    pub(crate) receiver: Option<mpsc::Receiver<GameUpdate>>,
     pub(crate) game_checker: Option<watch::Receiver<bool>>,
     pub(crate) server_checker: Option<tokio::sync::oneshot::Receiver<Error>>,
     pub(crate) game_stats: GameStats,
     pub(crate) game_sender: Option<mpsc::Sender<u8>>,
+    input: Option<mpsc::UnboundedSender<Event>>,
     pub(crate) parameters: GameParams,
+    /// States received, to draw the field smoothly
+    smoother: Smoother,
+    /// Frames kept for a replay, when recording
+    recorder: Option<Recorder>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
 pub(crate) struct GameStats {
     pub(crate) left_y: f32,
     pub(crate) right_y: f32,
     pub(crate) ball_x: f32,
     pub(crate) ball_y: f32,
+    /// Ball movement per server step, see smoothing::SERVER_RATE
+    pub(crate) speed_x: f32,
+    pub(crate) speed_y: f32,
     pub(crate) player1_score: u8,
     pub(crate) player2_score: u8,
     pub(crate) winner: bool,
@@ -58,39 +93,57 @@ impl Game {
     ///
     /// #Parameters
     /// - info: Main structure
-    /// - value: json game informations sent through websocket to infos.receiver
+    /// - start: match notification sent through websocket to infos.receiver
     ///
     /// #Returns
     /// New game instance
     ///
     /// #Errors
-    /// Returns an error if no gameId, opponentId or playerSide found in request
-    /// Returns an error if get_opponent_name returns an error
+    /// Returns an error if the opponent's profile can't be fetched
     ///
-    pub(crate) async fn new(info: &Infos, value: serde_json::Value, parameters: GameParams) -> Result<Game> {
-        let game_id: String = match value["gameId"].as_str() {
-            Some(id) => id.to_string(),
-            _ => return Err(anyhow!("No game Id in response")),
-        };
-        let opponent_id = match value["opponentId"].as_u64() {
-            Some(id) => id,
-            _ => return Err(anyhow!("No opponent id in response")),
+    pub(crate) async fn new(info: &Infos, start: MatchStart, parameters: GameParams) -> Result<Game> {
+        // the bot's account is an internal one, its profile is not worth a request
+        let opponent_name = match start.mode.as_str() {
+            "bot" => "Bot".to_string(),
+            "local" => "Player 2".to_string(),
+            _ => info.context.api().profile_by_id(start.opponent_id).await?.name,
         };
-        let opponent_name: String = get_name_from_id(info.context.clone(), opponent_id).await?;
-        let player_side: u64 = match value["playerSide"].as_u64() {
-            Some(nbr) => nbr,
-            _ => return Err(anyhow!("No player Id in response")),
+        let recorder = match (&info.context.replay_dir, info.context.record) {
+            (Some(dir), true) => Some(Recorder::new(
+                dir.clone(),
+                ReplayInfo {
+                    game_id: start.game_id.clone(),
+                    mode: start.mode.clone(),
+                    recorded_at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|since| since.as_secs())
+                        .unwrap_or_default(),
+                    player: info.authent.borrow().username.clone(),
+                    opponent: opponent_name.clone(),
+                    side: start.player_side,
+                    params: parameters.clone(),
+                    score: (0, 0),
+                    won: None,
+                },
+            )),
+            _ => None,
         };
         Ok(Game {
             context: info.context.clone(),
             auth: info.authent.clone(),
-            game_id,
-            player_side,
+            game_id: start.game_id,
+            player_side: start.player_side,
+            mode: start.mode,
             opponent_name,
-            parameters: parameters,
+            parameters,
+            recorder,
             ..Default::default()
         })
     }
+    /// Both paddles are played from this terminal
+    pub(crate) fn is_local(&self) -> bool {
+        self.mode == "local"
+    }
     /// Start a new game
     pub(crate) async fn start_game(&mut self) -> Result<tokio::sync::oneshot::Receiver<Error>> {
         let ws_stream = self.connect_wss().await?;
@@ -99,30 +152,19 @@ impl Game {
     }
     ///Initiate websocket connection with game server
     async fn connect_wss(&self) -> Result<WsStream> {
-        let url = format!(
-            "https://{}/api/start-game/{}", // TODO: token must be sent in header
-            self.context.location, self.game_id
-        );
-        let mut header = HeaderMap::new();
-        header.insert("Authorization", format!("Bearer {}", self.auth.borrow().token.clone()).parse()?);
-        let response = self.context.client.post(url).headers(header).send().await?;
-        if response.status() != 200 && response.status() != 201 {
-            return Err(anyhow!("Error starting game"));
-        }
+        let token = self.auth.borrow().token.clone();
+        self.context.api().with_token(&token).start_game(&self.game_id).await?;
         let url = format!(
             "wss://{}/api/game/{}/{}",
             self.context.location, self.game_id, self.player_side
         );
         let mut request = url.into_client_request()?;
         let headers = request.headers_mut();
-        headers.insert("Cookie", format!("jwt_session={}", self.auth.borrow().token.clone()).parse()?);
-        let connector = Connector::NativeTls(
-            native_tls::TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .build()?,
-        );
+        headers.insert("Cookie", format!("jwt_session={}", token).parse()?);
+        let connector = self.context.connector();
         let (ws_stream, _) =
             connect_async_tls_with_config(request, None, false, Some(connector)).await?;
+        info!(game_id = self.game_id, side = self.player_side, mode = self.mode, "game connected");
         Ok(ws_stream)
     }
     ///Split the websocket stream and spawn two async tasks to independently read game state from server and send events
@@ -132,22 +174,34 @@ impl Game {
     async fn split_and_spawn_sockets(&mut self, ws_stream: WsStream) -> Result<tokio::sync::oneshot::Receiver<Error>> {
         let (ws_write, ws_read) = ws_stream.split();
         let (sender, receiver): (mpsc::Sender<u8>, mpsc::Receiver<u8>) = mpsc::channel(1);
-        let (state_sender, state_receiver): GameChannel = watch::channel((None, None));
+        let (input, events) = mpsc::unbounded_channel();
+        let (state_sender, state_receiver) = mpsc::channel(FRAME_QUEUE);
         self.receiver = Some(state_receiver);
         let (game_sender, game_checker): (watch::Sender<bool>, watch::Receiver<bool>) =
             watch::channel(true);
         self.game_checker = Some(game_checker);
         let socket_checker = game_sender.subscribe();
         let (err_tx, err_rx) = tokio::sync::oneshot::channel::<anyhow::Error>();
+        let controls = match self.is_local() {
+            true => LOCAL_CONTROLS,
+            false => CONTROLS,
+        };
         tokio::task::spawn(async move {
-            let _ = Self::send_game(ws_write, receiver, game_sender).await;
+            if let Err(e) = Self::send_game(ws_write, receiver, events, game_sender, controls).await {
+                error!("sending paddle moves failed: {}", e);
+            }
         });
         tokio::spawn(async move {
-            if let Err(e) = Self::read_socket(ws_read, state_sender, socket_checker).await{
-                let _ = err_tx.send(e);
-            };
+            match Self::read_socket(ws_read, state_sender, socket_checker).await {
+                Ok(()) => info!("game connection closed"),
+                Err(e) => {
+                    error!("game connection lost: {}", e);
+                    let _ = err_tx.send(e);
+                }
+            }
         });
         self.game_sender = Some(sender);
+        self.input = Some(input);
         Ok(err_rx)
     }
     /// Get the winner's name and send shutdown signal to spawned task
@@ -155,107 +209,158 @@ impl Game {
     /// #Parameters:
     /// - text: text sent by the server containing the id of the winner
     /// - sender: sender to use to shut down task sending game events to server
+    ///
+    /// #Errors
+    /// Returns an error if the text is not a winner message
     pub(crate) async fn end_game(
         &mut self,
         text: Utf8Bytes,
         sender: mpsc::Sender<u8>,
     ) -> Result<()> {
-        let value = serde_json::to_string(text.as_str())?;
-        match value.find(&self.auth.borrow().id.to_string()) {
-            Some(_) => self.game_stats.winner = true,
-            _ => self.game_stats.winner = false,
+        let message = protocol::decode_message(text.as_str());
+        sender.send(1).await?;
+        let winner = match message? {
+            GameFrame::Winner(winner) => winner,
+            GameFrame::Error(message) => bail!("game server: {}", message),
+            GameFrame::State(_) => bail!("state frame sent as text"),
         };
-        let u: u8 = 1;
-        sender.send(u).await?;
+        self.game_stats.winner = winner == self.auth.borrow().id;
+        info!(
+            game_id = self.game_id,
+            winner = self.game_stats.winner,
+            score = format!("{}-{}", self.game_stats.player1_score, self.game_stats.player2_score),
+            "game over"
+        );
+        self.save_replay(Some(self.game_stats.winner));
         Ok(())
     }
-    pub(crate) fn decode_and_update(&mut self, msg: Bytes) -> Result<()> {
-        if msg.len() == 26 {
-            self.game_stats = Self::decode(msg)?;
+    /// Writes the replay of a recorded match, a failure only loses the replay
+    fn save_replay(&mut self, won: Option<bool>) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        match recorder.save(won) {
+            Ok(Some(path)) => info!(path = %path.display(), "replay saved"),
+            Ok(None) => {}
+            Err(e) => warn!("replay not saved: {:#}", e),
         }
-        Ok(())
     }
-    ///Transform a sequence of bytes into GameStats struct
+    /// Closes the game websocket if a match is running
+    pub(crate) fn stop(&mut self) {
+        self.save_replay(None);
+        if let Some(sender) = self.game_sender.take() {
+            let _ = sender.try_send(1);
+        }
+        self.receiver = None;
+        self.input = None;
+    }
+    /// Hands a terminal event to the task sending paddle moves
+    pub(crate) fn send_event(&self, event: Event) {
+        if let Some(input) = &self.input {
+            let _ = input.send(event);
+        }
+    }
+    /// Waits for the next message of the running match, cancel safe
+    pub(crate) async fn next_update(&mut self) -> GameUpdate {
+        let (Some(state), Some(checker)) = (self.receiver.as_mut(), self.game_checker.as_mut())
+        else {
+            return GameUpdate::Closed;
+        };
+        loop {
+            let server = async {
+                match self.server_checker.as_mut() {
+                    Some(server) => server.await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                update = state.recv() => return update.unwrap_or(GameUpdate::Closed),
+                _ = checker.changed() => return GameUpdate::Closed,
+                failure = server => match failure {
+                    Ok(e) => return GameUpdate::Failed(e),
+                    // the reading task ended without error
+                    Err(_) => self.server_checker = None,
+                },
+            }
+        }
+    }
+    /// Decodes a state frame and keeps it for drawing
     ///
-    /// #Parameters:
-    /// - msg: bytes sent by server containing informations in game positions
+    /// #Parameters
+    /// - received: when the socket received the frame, the UI may read it later
+    /// - msg: the frame
     ///
-    /// #Return:
-    /// New GameStats struct
-    fn decode(msg: Bytes) -> Result<GameStats> {
-        let left_y: f32 = f32::from_le_bytes(msg[0..4].try_into()?);
-        let right_y: f32 = f32::from_le_bytes(msg[4..8].try_into()?);
-        let ball_x: f32 = f32::from_le_bytes(msg[8..12].try_into()?);
-        let ball_y: f32 = f32::from_le_bytes(msg[12..16].try_into()?);
-        let player1_score: u8 = msg[24];
-        let player2_score: u8 = msg[25];
-        Ok(GameStats {
-            left_y,
-            right_y,
-            ball_x,
-            ball_y,
-            player1_score,
-            player2_score,
-            winner: false,
-        })
+    /// #Errors
+    /// Returns an error if the frame does not follow the server's layout
+    pub(crate) fn decode_and_update(&mut self, received: Instant, msg: Bytes) -> Result<()> {
+        let GameFrame::State(stats) = protocol::decode_state(&msg)? else {
+            bail!("state frame without a state");
+        };
+        self.game_stats = stats;
+        self.smoother.push(received, self.game_stats);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(received, &msg);
+        }
+        Ok(())
+    }
+    /// Where to draw the field at `now`, between the states received or just past the newest
+    pub(crate) fn drawn(&self, now: Instant) -> GameStats {
+        self.smoother.sample(now).unwrap_or(self.game_stats)
     }
     ///Send Game events to the server
     ///
     /// #Parameters:
     /// - ws_write: Writing part of the game websocket
     /// - receiver: End_game signal catcher
+    /// - events: Terminal events forwarded by the game screen
     /// - game_sender: Closer of the game websocket's reading part
+    /// - controls: Keys moving the paddles, see CONTROLS
     async fn send_game(
         mut ws_write: SplitSink<WsStream, Message>,
         mut receiver: mpsc::Receiver<u8>,
+        mut events: mpsc::UnboundedReceiver<Event>,
         game_sender: watch::Sender<bool>,
+        controls: &[(KeyCode, &str)],
     ) -> Result<()> {
-        let mut up: (bool, Instant, u128) = (false, std::time::Instant::now(), 0);
-        let mut down: (bool, Instant, u128) = (false, std::time::Instant::now(), 0);
-        let mut to_send = String::new();
+        // last press of each control, None once released
+        let mut held: Vec<Option<Instant>> = vec![None; controls.len()];
         loop {
             if receiver.try_recv().is_ok() {
                 break;
             }
-            to_send.clear();
-            if up.0 {
-                to_send.insert(0, 'U');
-            }
-            if down.0 {
-                to_send.insert(0, 'D');
-            }
+            let to_send: String = controls
+                .iter()
+                .zip(&held)
+                .filter(|(_, at)| at.is_some())
+                .map(|((_, moves), _)| *moves)
+                .collect();
             if !to_send.is_empty() {
                 let send_it = to_send.clone();
+                trace!(moves = send_it, "paddle");
                 ws_write.send(send_it.into()).await?;
             }
-            if poll(Duration::from_millis(16))? {
-                let event = event::read()?;
+            if let Ok(event) = timeout(Duration::from_millis(16), events.recv()).await {
+                let Some(event) = event else {
+                    break;
+                };
                 if should_exit(&event)? {
+                    info!("game left by the player");
                     game_sender.send(true)?;
                     break;
                 } else if let Event::Key(key_event) = event {
-                    match key_event.code {
-                        KeyCode::Up => match key_event.kind {
-                            KeyEventKind::Press => up = (true, std::time::Instant::now(), 150),
-                            KeyEventKind::Repeat => up = (true, std::time::Instant::now(), 150),
-                            KeyEventKind::Release => up = (false, std::time::Instant::now(), 150),
-                        },
-                        KeyCode::Down => match key_event.kind {
-                            KeyEventKind::Press => down = (true, std::time::Instant::now(), 150),
-                            KeyEventKind::Repeat => down = (true, std::time::Instant::now(), 150),
-                            KeyEventKind::Release => down = (false, std::time::Instant::now(), 150),
-                        },
-                        _ => {
-                            continue;
-                        }
-                    }
+                    let Some(index) = control(controls, key_event.code) else {
+                        continue;
+                    };
+                    held[index] = match key_event.kind {
+                        KeyEventKind::Press | KeyEventKind::Repeat => Some(Instant::now()),
+                        KeyEventKind::Release => None,
+                    };
                 };
             }
-            if up.1.elapsed().as_millis() > up.2 {
-                up.0 = false;
-            }
-            if down.1.elapsed().as_millis() > down.2 {
-                down.0 = false;
+            for at in held.iter_mut() {
+                if at.is_some_and(|at| at.elapsed() > HOLD) {
+                    *at = None;
+                }
             }
             tokio::time::sleep(Duration::from_millis(1)).await;
         }
@@ -266,26 +371,32 @@ impl Game {
     ///
     /// #Parameters:
     /// - ws_read: websocket's reading part
-    /// - state_sender: game's state sender
+    /// - state_sender: queue of the frames received, stamped on arrival
     /// - socket_checker: end_game signal's receiver
     async fn read_socket(
         mut ws_read: SplitStream<WsStream>,
-        state_sender: watch::Sender<(Option<Bytes>, Option<Utf8Bytes>)>,
+        state_sender: mpsc::Sender<GameUpdate>,
         socket_checker: watch::Receiver<bool>,
     ) -> Result<()> {
         loop {
             match ws_read.next().await {
                 Some(Ok(Message::Binary(b))) => {
-                    if state_sender.send((Some(b), None)).is_err() {
+                    trace!(len = b.len(), "state frame");
+                    let update = GameUpdate::State(Instant::now(), b);
+                    if state_sender.send(update).await.is_err() {
                         break;
                     }
                 }
                 Some(Ok(Message::Text(s))) => {
-                    if state_sender.send((None, Some(s))).is_err() {
+                    debug!(text = s.as_str(), "game message");
+                    if state_sender.send(GameUpdate::End(s)).await.is_err() {
                         break;
                     }
                 }
-                Some(Ok(Message::Close(_))) => {break},
+                Some(Ok(Message::Close(frame))) => {
+                    debug!(?frame, "closed by the server");
+                    break;
+                },
                 Some(Ok(_)) => {},
                 Some(Err(e)) => {return Err(anyhow!("{}", e))},
                 _ => {break},
@@ -298,3 +409,200 @@ impl Game {
         Ok(())
     }
 }
+
+/// Index of the control bound to a key, letters whatever the case
+fn control(controls: &[(KeyCode, &str)], code: KeyCode) -> Option<usize> {
+    let code = match code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        code => code,
+    };
+    controls.iter().position(|(key, _)| *key == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infos::{create_direct_game, send_post_game_request};
+    use crate::login::{Auth, login};
+    use crate::mock::{MockServer, state_frame, temp_path, winner_frame};
+    use crate::replay::{self, Replay};
+
+    /// Logs alice in and queues her against bob
+    async fn queued(server: &MockServer) -> (Infos, MatchStart, GameParams) {
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let context = server.context();
+        let form = ("alice@mail.com".to_string(), "secret".to_string(), String::new());
+        let credentials = login(context.clone(), form).await.unwrap();
+        let auth = Rc::new(RefCell::new(Auth::default()));
+        auth.borrow_mut().set_credentials(credentials);
+        assert!(server.wait_chat(alice, true).await);
+        let infos = Infos::new(context, auth);
+        server.match_with(bob);
+        let params = send_post_game_request(&infos, "online").await.unwrap();
+        let mut receiver = infos.authent.borrow_mut().receiver.take().unwrap();
+        let start = receiver.recv().await.unwrap();
+        (infos, start, params)
+    }
+
+    #[tokio::test]
+    async fn match_is_played_until_the_winner() {
+        let server = MockServer::start().await;
+        server.script_game(vec![
+            state_frame(40.0, 60.0, 50.0, 25.0, 1.0, -1.0, 3, 1),
+            winner_frame(1),
+        ]);
+        let (infos, start, params) = queued(&server).await;
+        assert_eq!(params.paddle_height, crate::mock::PADDLE_HEIGHT);
+        assert_eq!(start.game_id, crate::mock::GAME_ID);
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        assert_eq!(game.opponent_name, "bob");
+        game.server_checker = Some(game.start_game().await.unwrap());
+        let GameUpdate::State(received, bytes) = game.next_update().await else {
+            panic!("expected a state frame");
+        };
+        game.decode_and_update(received, bytes).unwrap();
+        assert_eq!(game.game_stats.left_y, 40.0);
+        assert_eq!(game.game_stats.ball_y, 25.0);
+        assert_eq!((game.game_stats.speed_x, game.game_stats.speed_y), (1.0, -1.0));
+        assert_eq!((game.game_stats.player1_score, game.game_stats.player2_score), (3, 1));
+        let GameUpdate::End(text) = game.next_update().await else {
+            panic!("expected the winner");
+        };
+        let sender = game.game_sender.clone().unwrap();
+        game.end_game(text, sender).await.unwrap();
+        assert!(game.game_stats.winner);
+    }
+
+    #[test]
+    fn local_games_drive_both_paddles() {
+        let moves = |controls: &[(KeyCode, &'static str)], code| {
+            control(controls, code).map(|index| controls[index].1)
+        };
+        assert_eq!(moves(CONTROLS, KeyCode::Up), Some("U"));
+        assert_eq!(moves(CONTROLS, KeyCode::Char('w')), None);
+        assert_eq!(moves(LOCAL_CONTROLS, KeyCode::Char('W')), Some("1U"));
+        assert_eq!(moves(LOCAL_CONTROLS, KeyCode::Char('s')), Some("1D"));
+        assert_eq!(moves(LOCAL_CONTROLS, KeyCode::Down), Some("2D"));
+    }
+
+    #[tokio::test]
+    async fn bot_game_starts_without_the_queue() {
+        let server = MockServer::start().await;
+        server.script_game(vec![state_frame(50.0, 30.0, 50.0, 50.0, 1.0, 0.0, 0, 2)]);
+        server.add_user("alice", "alice@mail.com", "secret");
+        let context = server.context();
+        let form = ("alice@mail.com".to_string(), "secret".to_string(), String::new());
+        let credentials = login(context.clone(), form).await.unwrap();
+        let auth = Rc::new(RefCell::new(Auth::default()));
+        auth.borrow_mut().set_credentials(credentials);
+        let infos = Infos::new(context, auth);
+        let (start, params) = create_direct_game(&infos, "bot").await.unwrap();
+        assert_eq!((start.player_side, start.mode.as_str()), (1, "bot"));
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        assert_eq!(game.opponent_name, "Bot");
+        game.server_checker = Some(game.start_game().await.unwrap());
+        let GameUpdate::State(received, bytes) = game.next_update().await else {
+            panic!("expected a state frame");
+        };
+        game.decode_and_update(received, bytes).unwrap();
+        assert_eq!((game.game_stats.player1_score, game.game_stats.player2_score), (0, 2));
+
+        // nobody to look up in a local game
+        let (start, params) = create_direct_game(&infos, "local").await.unwrap();
+        assert_eq!(start.opponent_id, 0);
+        let game = Game::new(&infos, start, params).await.unwrap();
+        assert!(game.is_local());
+        assert_eq!(game.opponent_name, "Player 2");
+    }
+
+    #[tokio::test]
+    async fn frames_of_a_burst_all_reach_the_screen() {
+        let server = MockServer::start().await;
+        server.script_game(
+            [10.0, 20.0, 30.0]
+                .map(|x| state_frame(50.0, 50.0, x, 50.0, 0.5, 0.0, 0, 0))
+                .to_vec(),
+        );
+        let (infos, start, params) = queued(&server).await;
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        game.server_checker = Some(game.start_game().await.unwrap());
+        // the screen is busy while the burst arrives
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let read = Instant::now();
+        for x in [10.0, 20.0, 30.0] {
+            let GameUpdate::State(received, bytes) = game.next_update().await else {
+                panic!("expected a state frame");
+            };
+            // stamped by the socket, not by the screen reading it
+            assert!(received < read);
+            game.decode_and_update(received, bytes).unwrap();
+            assert_eq!(game.game_stats.ball_x, x);
+        }
+        assert!(matches!(game.next_update().await, GameUpdate::Closed));
+    }
+
+    #[tokio::test]
+    async fn every_frame_of_a_burst_is_recorded() {
+        let server = MockServer::start().await;
+        let frames: Vec<Message> = (0..10)
+            .map(|x| state_frame(50.0, 50.0, x as f32, 50.0, 0.5, 0.0, 0, 1))
+            .collect();
+        server.script_game(frames.clone());
+        server.add_user("alice", "alice@mail.com", "secret");
+        let dir = temp_path("recorded-burst");
+        let context = server.recording_context(dir.clone());
+        let form = ("alice@mail.com".to_string(), "secret".to_string(), String::new());
+        let credentials = login(context.clone(), form).await.unwrap();
+        let auth = Rc::new(RefCell::new(Auth::default()));
+        auth.borrow_mut().set_credentials(credentials);
+        let infos = Infos::new(context, auth);
+        let (start, params) = create_direct_game(&infos, "bot").await.unwrap();
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        game.server_checker = Some(game.start_game().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        while let GameUpdate::State(received, bytes) = game.next_update().await {
+            game.decode_and_update(received, bytes).unwrap();
+        }
+        game.stop();
+
+        let path = &replay::list(&dir).unwrap()[0];
+        let info = replay::info(path).unwrap();
+        let header = replay::HEADER_LEN + serde_json::to_vec(&info).unwrap().len();
+        let saved = std::fs::read(path).unwrap();
+        let records: Vec<&[u8]> = saved[header..].chunks(4 + protocol::STATE_LEN).collect();
+        assert_eq!(records.len(), frames.len());
+        for (record, frame) in records.iter().zip(frames) {
+            assert_eq!(&record[4..], &frame.into_data()[..]);
+        }
+        let replay = Replay::load(path).unwrap();
+        assert_eq!(replay.at(replay.duration()).ball_x, 9.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn winner_is_matched_on_the_whole_id() {
+        let server = MockServer::start().await;
+        let (infos, start, params) = queued(&server).await;
+        // alice's id written twice, it contains her id without being it
+        let id = infos.authent.borrow().id;
+        server.script_game(vec![winner_frame(id * 11)]);
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        game.server_checker = Some(game.start_game().await.unwrap());
+        let GameUpdate::End(text) = game.next_update().await else {
+            panic!("expected the winner");
+        };
+        let sender = game.game_sender.clone().unwrap();
+        game.end_game(text, sender).await.unwrap();
+        assert!(!game.game_stats.winner);
+    }
+
+    #[tokio::test]
+    async fn closed_socket_ends_the_match() {
+        let server = MockServer::start().await;
+        let (infos, start, params) = queued(&server).await;
+        let mut game = Game::new(&infos, start, params).await.unwrap();
+        game.server_checker = Some(game.start_game().await.unwrap());
+        assert!(matches!(game.next_update().await, GameUpdate::Closed));
+    }
+}
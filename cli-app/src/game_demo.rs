@@ -1,3 +1,6 @@
+/// Share of its distance to the ball the right paddle covers every frame
+pub(crate) const TRACKING: f64 = 0.13;
+
 pub(crate) struct Demo {
     pub(crate) ball_x: f64,
     pub(crate) ball_y: f64,
@@ -21,7 +24,7 @@ impl Demo {
             self.paddle_left_y += (self.ball_y - self.paddle_left_y - 5.0) * 0.12;
             self.paddle_left_y = self.paddle_left_y.clamp(1.0, 90.0);
         } else {
-            self.paddle_right_y += (self.ball_y - self.paddle_right_y - 5.0) * 0.13;
+            self.paddle_right_y += (self.ball_y - self.paddle_right_y - 5.0) * TRACKING;
             self.paddle_right_y = self.paddle_right_y.clamp(1.0, 90.0);
         }
     }
@@ -1,310 +1,51 @@
-use crate::CurrentScreen;
+use crate::api::{ApiClient, GameSettings, MatchStart};
 use crate::context::Context;
-use crate::friends::Friends;
-use crate::game::Game;
 use crate::game_demo::Demo;
-use crate::infos_events::EventHandler;
-use crate::login::Auth;
-use crate::screen_displays::ScreenDisplayer;
-use crate::utils::should_exit;
+use crate::login::{Auth, logout};
 use anyhow::{Result, anyhow};
-use crossterm::event::{self, Event, KeyCode, poll};
-use ratatui::{DefaultTerminal, Frame, buffer::Buffer, layout::Rect, widgets::Widget};
-use reqwest::header::HeaderMap;
-use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::rc::Rc;
-use tokio::time::{timeout, Duration};
+use tokio::time::{Duration, timeout};
 
-#[derive(Default)]
+/// State shared by every screen
 pub(crate) struct Infos {
     pub(crate) context: Rc<Context>,
     pub(crate) authent: Rc<RefCell<Auth>>,
-    pub(crate) friend: Friends,
-    pub(crate) screen: Rc<Cell<CurrentScreen>>,
-    pub(crate) game: Game,
     pub(crate) demo: Demo,
-    pub(crate) post_error_screen: CurrentScreen,
-    pub(crate) error: String,
-    pub(crate) exit: bool,
 }
 
 impl Infos {
-    pub(crate) fn new(
-        context: Rc<Context>,
-        auth: Rc<RefCell<Auth>>,
-        screen: Rc<Cell<CurrentScreen>>,
-        friends: Friends,
-    ) -> Infos {
+    pub(crate) fn new(context: Rc<Context>, auth: Rc<RefCell<Auth>>) -> Infos {
         Infos {
             context,
             authent: auth,
-            screen,
-            friend: friends,
-            ..Default::default()
+            demo: Demo::default(),
         }
     }
-    pub(crate) async fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
-        while !self.exit {
-            if self.screen.get() == CurrentScreen::FriendsDisplay {
-                self.friend.update_friends_index(terminal).await?;
-            }
-            if let Err(e) = terminal.draw(|frame| self.draw(frame)) {
-                self.error(e.to_string());
-            }
-            match self.screen.get() {
-                CurrentScreen::FirstScreen
-                | CurrentScreen::GameChoice
-                | CurrentScreen::SocialLife
-                | CurrentScreen::Welcome => {
-                    self.demo.update();
-                    if event::poll(Duration::from_millis(16))?
-                        && let Err(e) = self.handle_events().await
-                    {
-                        self.error(e.to_string());
-                    }
-                }
-                _ => {
-                    if let Err(e) = self.handle_events().await {
-                        self.error(e.to_string());
-                    }
-                }
-            }
-        }
-        Ok(())
-    }
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.area());
-    }
-    async fn handle_events(&mut self) -> Result<()> {
-        match self.screen.get() {
-            CurrentScreen::FirstScreen => {
-                if let Err(e) = self.handle_first_events().await {
-                    self.authent.borrow_mut().clear();
-                    return Err(e);
-                }
-            }
-            CurrentScreen::SignUp => {
-                if let Err(e) = self.handle_signup_events().await {
-                    self.authent.borrow_mut().clear();
-                    return Err(e);
-                }
-            }
-            CurrentScreen::Login => {
-                if let Err(e) = self.handle_login_events().await {
-                    self.authent.borrow_mut().clear();
-                    return Err(e);
-                }
-            }
-            CurrentScreen::Welcome => self.handle_welcome_events()?,
-            CurrentScreen::GameChoice => self.handle_gamechoice_events()?,
-            CurrentScreen::SocialLife => self.handle_social_events().await?,
-            CurrentScreen::FriendsDisplay => self.handle_friends_events()?,
-            CurrentScreen::StartGame => self.launch_game().await?,
-            CurrentScreen::EndGame => self.handle_endgame()?,
-            CurrentScreen::CreateGame => self.create_game("online").await?,
-            CurrentScreen::PlayGame => self.handle_game_events().await?,
-            CurrentScreen::ErrorScreen => self.handle_errors().await?,
-            CurrentScreen::AddFriend => self.friend.add_friend().await?,
-            CurrentScreen::DeleteFriend => self.friend.delete_friend().await?,
-        }
-        Ok(())
-    }
-    pub(crate) fn error(&mut self, error: String) {
-        self.post_error_screen = match self.screen.get() {
-            CurrentScreen::FirstScreen => CurrentScreen::FirstScreen,
-            CurrentScreen::SignUp => CurrentScreen::SignUp,
-            CurrentScreen::Login => CurrentScreen::Login,
-            CurrentScreen::Welcome => CurrentScreen::Welcome,
-            CurrentScreen::GameChoice => CurrentScreen::GameChoice,
-            CurrentScreen::SocialLife => CurrentScreen::SocialLife,
-            CurrentScreen::FriendsDisplay => CurrentScreen::FriendsDisplay,
-            CurrentScreen::StartGame => CurrentScreen::GameChoice,
-            CurrentScreen::EndGame => CurrentScreen::GameChoice,
-            CurrentScreen::CreateGame => CurrentScreen::GameChoice,
-            CurrentScreen::PlayGame => CurrentScreen::GameChoice,
-            CurrentScreen::ErrorScreen => CurrentScreen::ErrorScreen,
-            CurrentScreen::AddFriend => CurrentScreen::AddFriend,
-            CurrentScreen::DeleteFriend => CurrentScreen::DeleteFriend,
-        };
-        self.error = error;
-        self.screen.set(CurrentScreen::ErrorScreen);
-    }
-    async fn handle_errors(&mut self) -> Result<()> {
-        loop {
-            let event = event::read()?;
-            if let Event::Key(_) = event {
-                break;
-            }
-        }
-        self.screen.set(self.post_error_screen);
-        Ok(())
-    }
-    pub(crate) async fn create_game(&mut self, mode: &str) -> Result<()> {
-        let params = send_post_game_request(self, mode).await?;
-        loop {
-            match poll(Duration::from_millis(16)) {
-                Ok(true) => {
-                    if !self
-                        .authent
-                        .borrow_mut()
-                        .receiver
-                        .as_mut()
-                        .ok_or_else(|| anyhow!("receiver not initialized"))?
-                        .is_empty()
-                    {
-                        break;
-                    }
-                    let event = event::read()?;
-                    if let Ok(true) = should_exit(&event) {
-                        self.send_remove_from_queue_request().await?;
-                        self.screen.set(CurrentScreen::GameChoice);
-                        return Ok(());
-                    }
-                }
-                Ok(false) => {
-                    if !self
-                        .authent
-                        .borrow_mut()
-                        .receiver
-                        .as_mut()
-                        .ok_or_else(|| anyhow!("receiver not initialized"))?
-                        .is_empty()
-                    {
-                        break;
-                    }
-                }
-                _ => return Err(anyhow!("error in poll".to_string())),
-            };
-        }
-        let response = self
-            .authent
-            .borrow_mut()
-            .receiver
-            .as_mut()
-            .ok_or_else(|| anyhow!("receiver not initialized"))?
-            .try_recv()?;
-        let game = Game::new(self, response, params).await?;
-        self.send_start_game(&game.game_id).await?;
-        self.game = game;
-        self.screen.set(crate::CurrentScreen::StartGame);
-        Ok(())
-    }
-    pub(crate) async fn launch_game(&mut self) -> Result<()> {
-        let checker = self.game.start_game().await?;
-        self.game.server_checker = Some(checker);
-        self.screen.set(crate::CurrentScreen::PlayGame);
-        Ok(())
-    }
-    async fn send_start_game(&mut self, game_id: &str) -> Result<()> {
-        let mut headers = HeaderMap::new();
-        // headers.insert("Content-Type", "application/json".parse()?);
-        let token: &str = &self.authent.borrow().token.clone();
-        headers.insert("Authorization", format!("Bearer {}", token).parse()?);
-        let url = format!("https://{}/api/start-game/{}", self.context.location, game_id); // TODO: token must be sent in header
-        let res = self
-            .context
-            .client
-            .post(url)
-            .headers(headers)
-            .send()
-            .await?;
-        if res.status() != 200 {
-            return Err(anyhow!("Error starting game"));
-        }
-        Ok(())
-    }
-    pub(crate) async fn handle_game_events(&mut self) -> Result<()> {
-        let mut state_receiver = match self.game.receiver.clone() {
-            Some(receiver) => receiver,
-            _ => {
-                return Err(anyhow!("State receiver is empty"));
-            }
-        };
-        if let Some(checker) = &mut self.game.game_checker
-            && matches!(checker.has_changed(), Ok(true) | Err(_))
-        {
-            self.screen.set(crate::CurrentScreen::GameChoice);
-            return Ok(());
-        };
-        if let Some(Ok(err)) = self.game.server_checker.as_mut().map(|r| r.try_recv()) {
-            return Err(anyhow!("{}", err));
-        }
-        if let Some(sender) = &self.game.game_sender {
-            match timeout(Duration::from_millis(16), state_receiver.changed()).await {
-                Ok(Err(_)) => {
-                    self.screen.set(crate::CurrentScreen::GameChoice);
-                    return Ok(());
-                }
-                _ => {}
-            }
-            let (bytes, text) = state_receiver.borrow_and_update().clone();
-            match (bytes, text) {
-                (Some(bytes), _none) => {
-                    self.game.decode_and_update(bytes)?;
-                }
-                (_none, Some(text)) => {
-                    self.game.end_game(text, sender.clone()).await?;
-                    self.screen.set(crate::CurrentScreen::EndGame);
-                }
-                _ => {}
-            };
-        }
-        Ok(())
+    /// Shares the context and the session, for the tasks of a screen that outlive its borrow
+    pub(crate) fn handle(&self) -> Infos {
+        Infos::new(self.context.clone(), self.authent.clone())
     }
-    pub(crate) fn handle_endgame(&mut self) -> Result<()> {
-        if poll(Duration::from_millis(16))? {
-            let event = event::read()?;
-            if should_exit(&event)? {
-                self.screen.set(crate::CurrentScreen::GameChoice);
-            } else if let Event::Key(keyevent) = event
-                && keyevent.code == KeyCode::Enter
-            {
-                self.screen.set(crate::CurrentScreen::GameChoice);
-            }
-        }
-        Ok(())
+    /// Client authenticated as the logged in user
+    pub(crate) fn api(&self) -> ApiClient {
+        let token = self.authent.borrow().token.clone();
+        self.context.api().with_token(&token)
     }
-    async fn send_remove_from_queue_request(&self) -> Result<()> {
-        let mut map = HashMap::new();
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", "application/json".parse()?);
-        let id: &str = &self.authent.borrow().id.to_string();
-        map.insert("id", id);
-        let url = format!("https://{}/api/chat/removeQueue", self.context.location);
-        let response = self.context
-            .client
-            .delete(url)
-            .headers(headers)
-            .json(&map)
-            .send()
-            .await?;
-        Ok(())
+    /// Ends the session, the screens showing it are dropped by the router
+    ///
+    /// #Parameters
+    /// - forget: also drop the stored token, see login::logout
+    pub(crate) async fn logout(&mut self, forget: bool) {
+        let _ = timeout(
+            Duration::from_secs(2),
+            logout(&self.context, &self.authent, forget),
+        )
+        .await;
     }
 }
 
-impl Widget for &Infos {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        match self.screen.get() {
-            CurrentScreen::FirstScreen => self.display_first_screen(area, buf),
-            CurrentScreen::SignUp => self.display_signup_screen(area, buf),
-            CurrentScreen::Login => self.display_login_screen(area, buf),
-            CurrentScreen::Welcome => self.display_welcome_screen(area, buf),
-            CurrentScreen::GameChoice => self.display_gamechoice_screen(area, buf),
-            CurrentScreen::SocialLife => self.display_social_screen(area, buf),
-            CurrentScreen::FriendsDisplay => self.display_friends_screen(area, buf),
-            CurrentScreen::StartGame => {}
-            CurrentScreen::EndGame => self.display_endgame(area, buf),
-            CurrentScreen::CreateGame => self.display_waiting_screen(area, buf),
-            CurrentScreen::PlayGame => self.display_played_game(area, buf),
-            CurrentScreen::ErrorScreen => self.display_error_screen(area, buf),
-            CurrentScreen::AddFriend => self.display_addfriends_screen(area, buf),
-            CurrentScreen::DeleteFriend => self.display_delete_friends_screen(area, buf),
-        }
-    }
-}
-
-#[derive(Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub(crate) struct GameParams {
     pub(crate) paddle_height: f64,
     pub(crate) paddle_width: f64,
@@ -312,67 +53,52 @@ pub(crate) struct GameParams {
     pub(crate) ball_size: f64,
 }
 
-impl GameParams {
-    pub(crate) fn new(json: serde_json::Value) -> Result<Self> {
-        let mut paddle_height: f64 = match json["paddleHeight"].as_f64() {
-            Some(id) => id,
-            _ => return Err(anyhow!("No paddleHeight in response")),
-        };
-        if paddle_height >= 100.0 {
-            paddle_height = 100.0;
-        }
-        let mut paddle_width: f64 = match json["paddleWidth"].as_f64() {
-            Some(id) => id,
-            _ => return Err(anyhow!("No paddleWidth in response")),
-        };
-        if paddle_width >= 100.0 {
-            paddle_width = 100.0;
+/// Same defaults as the server's GameInstance, for matches started without create-game such as duels
+impl Default for GameParams {
+    fn default() -> Self {
+        GameParams {
+            paddle_height: 15.0,
+            paddle_width: 2.0,
+            paddle_padding: 2.0,
+            ball_size: 1.5,
         }
-        let mut paddle_padding: f64 = match json["paddlePadding"].as_f64() {
-            Some(id) => id,
-            _ => return Err(anyhow!("No paddlePadding in response")),
-        };
-        if paddle_padding >= 100.0 {
-            paddle_padding = 100.0;
-        }
-        let mut ball_size: f64 = match json["ballSize"].as_f64() {
-            Some(id) => id,
-            _ => return Err(anyhow!("No ballSize in response")),
-        };
-        if ball_size >= 100.0 {
-            ball_size = 100.0;
+    }
+}
+
+/// Server values are percentages of the field, anything above 100 is clamped
+impl From<GameSettings> for GameParams {
+    fn from(settings: GameSettings) -> Self {
+        GameParams {
+            paddle_height: settings.paddle_height.min(100.0),
+            paddle_width: settings.paddle_width.min(100.0),
+            paddle_padding: settings.paddle_padding.min(100.0),
+            ball_size: settings.ball_size.min(100.0),
         }
-        Ok(GameParams {
-            paddle_height,
-            paddle_width,
-            paddle_padding,
-            ball_size
-        })
     }
 }
 
-async fn send_post_game_request(game_main: &Infos, mode: &str) -> Result<GameParams> {
-    let mut map: HashMap<&str, &str> = HashMap::new();
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse()?);
-    map.insert("mode", mode);
-    let token: &str = &game_main.authent.borrow().token.to_string();
-    headers.insert("Authorization", format!("Bearer {}", token).parse()?);
-    let url = format!("https://{}/api/create-game", game_main.context.location);
-    let res = game_main
+pub(crate) async fn send_post_game_request(game_main: &Infos, mode: &str) -> Result<GameParams> {
+    let token = game_main.authent.borrow().token.clone();
+    let response = game_main
         .context
-        .client
-        .post(url)
-        .headers(headers)
-        .json(&map)
-        .send()
+        .api()
+        .with_token(&token)
+        .create_game(mode)
         .await?;
-
-    if res.status() != 201 && res.status() != 202 {
-        return Err(anyhow!("Error creating game"));
-    }
-    let params: serde_json::Value = res.json().await?;
-    let result = GameParams::new(params)?;
-    Ok(result)
+    Ok(GameParams::from(response.settings))
 }
 
+/// Creates a `bot` or `local` game, it starts from the answer instead of the chat socket
+///
+/// Against the bot, the server puts us on side 1 and hands the bot, on side 2, the reversed
+/// state buffer
+pub(crate) async fn create_direct_game(
+    infos: &Infos,
+    mode: &str,
+) -> Result<(MatchStart, GameParams)> {
+    let response = infos.api().create_game(mode).await?;
+    let start = response
+        .match_start(mode)
+        .ok_or_else(|| anyhow!("The server did not start the {} game", mode))?;
+    Ok((start, GameParams::from(response.settings)))
+}
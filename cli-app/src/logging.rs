@@ -0,0 +1,40 @@
+use anyhow::{Context as _, Result};
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+
+/// Daily files, the oldest are removed past this count
+const KEPT_FILES: usize = 7;
+
+/// Writes the traces to `dir`, never to stdout or stderr which belong to the interface
+///
+/// #Parameters
+/// - level: `off`, `error`, `warn`, `info`, `debug` or `trace` for this program,
+///   or a full filter such as `info,reqwest=debug`
+///
+/// #Returns
+/// The guard flushing the file, to keep alive until the program ends
+pub(crate) fn init(dir: &Path, level: &str) -> Result<WorkerGuard> {
+    let filter = if level.contains(['=', ',']) {
+        EnvFilter::try_new(level)
+    } else {
+        EnvFilter::try_new(format!("warn,{}={}", env!("CARGO_CRATE_NAME"), level))
+    }
+    .with_context(|| format!("invalid log level '{}'", level))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("cli")
+        .filename_suffix("log")
+        .max_log_files(KEPT_FILES)
+        .build(dir)
+        .with_context(|| format!("creating the log file in {}", dir.display()))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(false)
+        .init();
+    Ok(guard)
+}
@@ -1,13 +1,12 @@
 use crate::Context;
-use crate::game::WsStream;
-use reqwest::{Client, header::HeaderMap};
+use crate::api::{ApiResult, LoginRequest, MatchStart, SignupRequest};
+use crate::chat::{self, ChatLog, ChatRoom, ChatStatus, Outgoing};
+use crate::utils::Blink;
 use anyhow::{Result, anyhow};
-use futures_util::StreamExt;
-use std::collections::HashMap;
+use std::cell::RefCell;
 use std::rc::Rc;
-use tokio::sync::mpsc;
-use tokio_tungstenite::{Connector, connect_async_tls_with_config, tungstenite::protocol::Message};
-use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{info, warn};
 
 #[derive(Default, PartialEq)]
 pub(crate) enum Field {
@@ -27,8 +26,12 @@ pub(crate) struct Auth {
     pub(crate) totp: String,
     pub(crate) field: Field,
     pub(crate) id: u64,
-    pub(crate) blink: bool,
-    pub(crate) receiver: Option<mpsc::Receiver<serde_json::Value>>,
+    pub(crate) blink: Blink,
+    pub(crate) receiver: Option<mpsc::Receiver<MatchStart>>,
+    pub(crate) chat_status: Option<watch::Receiver<ChatStatus>>,
+    pub(crate) chat_log: Option<watch::Receiver<ChatLog>>,
+    pub(crate) chat_sender: Option<mpsc::UnboundedSender<Outgoing>>,
+    chat_closer: Option<oneshot::Sender<()>>,
 }
 
 impl Auth {
@@ -101,10 +104,10 @@ impl Auth {
         }
     }
     pub(crate) fn tick(&mut self) {
-        self.blink = !self.blink;
+        self.blink.tick();
     }
     pub(crate) fn blinks(&self, field: Field) -> bool {
-        self.blink && field == self.field
+        self.blink.on() && field == self.field
     }
     pub(crate) fn clear(&mut self) {
         self.email.clear();
@@ -129,159 +132,306 @@ impl Auth {
     }
     pub(crate) fn set_credentials(
         &mut self,
-        credentials: (String, u64, mpsc::Receiver<serde_json::Value>),
+        credentials: (String, u64, ChatRoom),
     ) {
         self.token = credentials.0;
         self.id = credentials.1;
-        self.receiver = Some(credentials.2);
+        self.receiver = Some(credentials.2.receiver);
+        self.chat_status = Some(credentials.2.status);
+        self.chat_log = Some(credentials.2.log);
+        self.chat_sender = Some(credentials.2.outgoing);
+        self.chat_closer = Some(credentials.2.closer);
+    }
+    ///Forgets the session and closes the chat websocket
+    ///
+    /// #Returns
+    /// The token the session was using
+    pub(crate) fn end_session(&mut self) -> String {
+        if let Some(closer) = self.chat_closer.take() {
+            let _ = closer.send(());
+        }
+        self.receiver = None;
+        self.chat_status = None;
+        self.chat_log = None;
+        self.chat_sender = None;
+        self.id = 0;
+        self.clear();
+        std::mem::take(&mut self.token)
+    }
+    /// #Returns
+    /// false when logged out
+    pub(crate) fn send_chat(&self, outgoing: Outgoing) -> bool {
+        self.chat_sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(outgoing).is_ok())
+    }
+    /// Direct messages not read yet
+    pub(crate) fn unread(&self) -> usize {
+        self.chat_log
+            .as_ref()
+            .map_or(0, |log| log.borrow().unread())
     }
 }
 
 pub(crate) async fn signup(
     context: Rc<Context>,
     signup_infos: (String, String, String),
-) -> Result<(String, u64, mpsc::Receiver<serde_json::Value>)> {
-    let apiloc = format!("https://{}/api/user/create", context.location);
-    let mut body: HashMap<&str, &str> = HashMap::new();
-    body.insert("username", &signup_infos.0);
-    body.insert("passw", &signup_infos.1);
-    body.insert("email", &signup_infos.2);
-    let response = context
-        .client
-        .post(apiloc)
-        .header("content-type", "application/json")
-        .json(&body)
-        .send()
-        .await?;
-    let body: serde_json::Value = response.json().await.map_err(|_| anyhow!("Server error"))?;
-    if body["token"].as_str().is_some() {
-        login(context, (signup_infos.2, signup_infos.1, String::new())).await
-    } else if let Some(error) = body["message"].as_str() {
-        Err(anyhow!("Error signing up: {}", error))
-    } else {
-        Err(anyhow!("Error signing up"))
-    }
+) -> Result<(String, u64, ChatRoom)> {
+    register(&context, &signup_infos)
+        .await
+        .map_err(|e| anyhow!("Error signing up: {}", e))?;
+    login(context, (signup_infos.2, signup_infos.1, String::new())).await
 }
 
 pub(crate) async fn login(
     context: Rc<Context>,
     login_infos: (String, String, String),
-) -> Result<(String, u64, mpsc::Receiver<serde_json::Value>)> {
-    let apiloc = format!("https://{}/api/user/login", context.location);
-    let mut body: HashMap<&str, &str> = HashMap::new();
-    body.insert("email", &login_infos.0);
-    body.insert("passw", &login_infos.1);
-    if !login_infos.2.is_empty() {
-        body.insert("totp", &login_infos.2);
+) -> Result<(String, u64, ChatRoom)> {
+    let token = request_token(&context, &login_infos)
+        .await
+        .map_err(|e| anyhow!("Error logging in: {}", e))?;
+    let (id, receiver) = get_id_and_launch_chat(context.clone(), token.clone()).await?;
+    Ok((token, id, receiver))
+}
+
+/// Creates the account, the user still has to log in
+///
+/// #Parameters
+/// - signup_infos: username, password and email
+pub(crate) async fn register(
+    context: &Context,
+    signup_infos: &(String, String, String),
+) -> ApiResult<()> {
+    let request = SignupRequest {
+        username: &signup_infos.0,
+        passw: &signup_infos.1,
+        email: &signup_infos.2,
+    };
+    context.api().signup(&request).await?;
+    Ok(())
+}
+
+/// Logs in without joining the chat and stores the token for the next launches
+///
+/// #Parameters
+/// - login_infos: email, password and the 2FA code, empty when 2FA is off
+pub(crate) async fn request_token(
+    context: &Context,
+    login_infos: &(String, String, String),
+) -> ApiResult<String> {
+    let request = LoginRequest {
+        email: &login_infos.0,
+        passw: &login_infos.1,
+        totp: (!login_infos.2.is_empty()).then_some(login_infos.2.as_str()),
+    };
+    let token = context.api().login(&request).await?.token;
+    if let Err(e) = context.session.save(&token) {
+        warn!("could not store the session: {:#}", e);
     }
-    let response = context
-        .client
-        .post(apiloc)
-        .header("content-type", "application/json")
-        .json(&body)
-        .send()
-        .await?;
-    let body: serde_json::Value = response.json().await.map_err(|_| anyhow!("Server error"))?;
-    if let Some(token) = body["token"].as_str() {
-        let (id, receiver) = get_id_and_launch_chat(context.clone(), token.to_string()).await?;
-        Ok((token.to_string(), id, receiver))
-    } else if let Some(error) = body["message"].as_str() {
-        Err(anyhow!("Error logging in: {}", error))
-    } else {
-        Err(anyhow!("Error logging in"))
+    Ok(token)
+}
+
+/// Guest counterpart of `request_token`
+pub(crate) async fn request_guest_token(context: &Context) -> ApiResult<String> {
+    let token = context.api().create_guest().await?.token;
+    if let Err(e) = context.session.save(&token) {
+        warn!("could not store the session: {:#}", e);
     }
+    Ok(token)
 }
 
 pub(crate) async fn get_id_and_launch_chat(
     context: Rc<Context>,
     token: String,
-) -> Result<(u64, mpsc::Receiver<serde_json::Value>)> {
-    let apiloc = format!("https://{}/api/user/get_profile_token", context.location);
-    let mut header = HeaderMap::new();
-    header.insert("Authorization", format!("Bearer {}", &token).parse()?);
-    let res = context
-        .client
-        .post(apiloc)
-        .headers(header)
-        .send()
-        .await?;
-    let value: serde_json::Value = res.json().await?;
-    let player_id = match value["id"].as_u64() {
-        Some(nbr) => nbr,
-        _ => return Err(anyhow!("Error from server, no data received")),
-    };
-    let receiver = enter_chat_room(&context.location, &token).await?;
-    Ok((player_id, receiver))
+) -> Result<(u64, ChatRoom)> {
+    let profile = context.api().with_token(&token).profile_from_token().await?;
+    let receiver = chat::join(&context, &token).await?;
+    Ok((profile.id, receiver))
 }
 
 pub(crate) async fn create_guest_session(
     context: Rc<Context>,
-) -> Result<(String, u64, mpsc::Receiver<serde_json::Value>)> {
-    let apiloc = format!("https://{}/api/user/create_guest", context.location);
-    let res = context.client.post(apiloc).send().await?;
-    let body: serde_json::Value = res.json().await.map_err(|_| anyhow!("Server error"))?;
-    if let Some(token) = body["token"].as_str() {
-        let (id, receiver) = get_id_and_launch_chat(context, token.to_string()).await?;
-        Ok((token.to_string(), id, receiver))
-    } else if let Some(error) = body["message"].as_str() {
-        Err(anyhow!("Error creating guest session: {}", error))
-    } else {
-        Err(anyhow!("Error creating guest session"))
-    }
+) -> Result<(String, u64, ChatRoom)> {
+    let token = request_guest_token(&context)
+        .await
+        .map_err(|e| anyhow!("Error creating guest session: {}", e))?;
+    let (id, receiver) = get_id_and_launch_chat(context.clone(), token.clone()).await?;
+    Ok((token, id, receiver))
 }
 
-async fn enter_chat_room(
-    location: &String,
-    token: &String,
-) -> Result<mpsc::Receiver<serde_json::Value>> {
-    let connector = Connector::NativeTls(
-        native_tls::TlsConnector::builder()
-            .danger_accept_invalid_certs(true)
-            .build()?,
-    );
-    let (token_chat, location_chat) = (token.clone(), location.clone());
-    let mut request = format!("wss://{}/api/chat", location).into_client_request()?;
-    let headers = request.headers_mut();
-    headers.insert("Cookie", format!("jwt_session={}", token_chat).parse()?);
-    let (ws_stream, response) =
-        connect_async_tls_with_config(request, None, false, Some(connector)).await?;
-    let (sender, receiver): (
-        mpsc::Sender<serde_json::Value>,
-        mpsc::Receiver<serde_json::Value>,
-    ) = mpsc::channel(1024);
-    tokio::spawn(async move {
-        let _ = chat(ws_stream, sender, token_chat, location_chat).await;
-    });
-    Ok(receiver)
+/// Ends the session server side and closes the chat websocket
+///
+/// #Parameters
+/// - forget: also drop the stored token, false when quitting so the next launch resumes the session
+pub(crate) async fn logout(context: &Context, auth: &RefCell<Auth>, forget: bool) {
+    let token = auth.borrow_mut().end_session();
+    if forget {
+        let _ = context.session.clear();
+    }
+    if !token.is_empty() {
+        info!(forget, "logging out");
+        if let Err(e) = context.api().with_token(&token).logout().await {
+            warn!("logout failed: {}", e);
+        }
+    }
 }
 
-async fn chat(mut ws_stream: WsStream, sender: mpsc::Sender<serde_json::Value>, token: String, location: String) -> Result<()> {
-        let client = Client::builder()
-                .danger_accept_invalid_certs(true)
-                .build()
-                .expect("Impossible to build new client, try again");
-        while let Some(msg) = ws_stream.next().await {
-        let last_message = match msg {
-            Ok(Message::Text(result)) => result,
-            Ok(_) => {continue},
-            Err(e) => return Err(anyhow!(e.to_string())),
-        };
-        let message: serde_json::Value = serde_json::from_str(last_message.as_str())?;
-        match message["gameId"].as_str() {
-            Some(_) => sender.send(message.clone()).await?,
-            _ => {},
-        };
-        match message["flag"].as_str() {
-            Some(value) if value == "health" => {
-                let mut header = HeaderMap::new();
-                header.insert("Authorization", format!("Bearer {}", token.clone()).parse()?);
-                client.post(format!("https://{}/api/chat/healthCallback", &location))
-                    .headers(header)
-                    .send()
-                    .await?;
-            },
-            _ => {}
+/// Logs back in with the token stored by a previous launch
+///
+/// A token the server rejects is dropped, any failure leaves the user on the login screens
+pub(crate) async fn resume_session(
+    context: Rc<Context>,
+) -> Option<(String, u64, ChatRoom)> {
+    let token = context.session.load()?;
+    let profile = match context.api().with_token(&token).profile_from_token().await {
+        Ok(profile) => profile,
+        Err(e) => {
+            // 400 is an invalid or expired jwt, 404 a deleted account
+            if matches!(e.status().map(|s| s.as_u16()), Some(400 | 401 | 403 | 404)) {
+                info!("stored session rejected: {}", e);
+                let _ = context.session.clear();
+            }
+            return None;
         }
+    };
+    let receiver = match chat::join(&context, &token).await {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            warn!("could not join the chat: {}", e);
+            return None;
+        }
+    };
+    info!(user = profile.id, "session resumed");
+    Some((token, profile.id, receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, temp_path};
+
+    fn form(email: &str, password: &str) -> (String, String, String) {
+        (email.to_string(), password.to_string(), String::new())
+    }
+
+    #[tokio::test]
+    async fn login_returns_the_profile_and_joins_the_chat() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let (token, found, _chat) = login(server.context(), form("alice@mail.com", "secret"))
+            .await
+            .unwrap();
+        assert!(!token.is_empty());
+        assert_eq!(found, id);
+        assert!(server.wait_chat(id, true).await);
+    }
+
+    #[tokio::test]
+    async fn login_reports_the_server_message() {
+        let server = MockServer::start().await;
+        server.add_user("alice", "alice@mail.com", "secret");
+        let error = login(server.context(), form("alice@mail.com", "wrong"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Error logging in: email or password invalid (HTTP 404)"
+        );
+    }
+
+    #[tokio::test]
+    async fn signup_logs_in_and_refuses_duplicates() {
+        let server = MockServer::start().await;
+        let context = server.context();
+        let infos = (
+            "bob".to_string(),
+            "secret".to_string(),
+            "bob@mail.com".to_string(),
+        );
+        let (_, id, _chat) = signup(context.clone(), infos.clone()).await.unwrap();
+        assert!(server.wait_chat(id, true).await);
+        let error = signup(context, infos).await.err().unwrap();
+        assert!(error.to_string().contains("user already exist"));
+    }
+
+    #[tokio::test]
+    async fn guest_session_gets_a_fresh_account() {
+        let server = MockServer::start().await;
+        server.add_user("alice", "alice@mail.com", "secret");
+        let (_, id, _chat) = create_guest_session(server.context()).await.unwrap();
+        assert_eq!(id, 2);
+    }
+
+    #[tokio::test]
+    async fn chat_forwards_match_starts_and_answers_health_checks() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let (_, _, mut chat) = login(server.context(), form("alice@mail.com", "secret"))
+            .await
+            .unwrap();
+        assert!(server.wait_chat(id, true).await);
+        server.send_chat(id, r#"{"username":"<SERVER>","message":"hi","connections":[]}"#);
+        server.send_chat(id, r#"{"username":"<SERVER>","message":"check health","flag":"health"}"#);
+        server.send_chat(
+            id,
+            r#"{"username":"SERVER","message":"START","opponentId":"7","gameId":"g","playerSide":2,"mode":"duel"}"#,
+        );
+        let start = chat.receiver.recv().await.unwrap();
+        assert_eq!(start.game_id, "g");
+        assert_eq!(start.opponent_id, 7);
+        assert_eq!(start.player_side, 2);
+        assert_eq!(start.mode, "duel");
+        assert!(
+            server
+                .requests()
+                .contains(&"POST /chat/healthCallback".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn logout_closes_the_chat_and_forgets_the_session() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let path = temp_path("logout");
+        let context = server.context_with_session(Some(path.clone()));
+        let auth = RefCell::new(Auth::default());
+        let credentials = login(context.clone(), form("alice@mail.com", "secret"))
+            .await
+            .unwrap();
+        auth.borrow_mut().set_credentials(credentials);
+        assert!(server.wait_chat(id, true).await);
+        assert!(context.session.load().is_some());
+        logout(&context, &auth, true).await;
+        assert!(server.wait_chat(id, false).await);
+        assert!(context.session.load().is_none());
+        assert!(auth.borrow().token.is_empty());
+        assert!(server.requests().contains(&"POST /user/logout".to_string()));
+    }
+
+    #[tokio::test]
+    async fn resume_session_keeps_a_valid_token() {
+        let server = MockServer::start().await;
+        let id = server.add_user("alice", "alice@mail.com", "secret");
+        let path = temp_path("resume-valid");
+        let context = server.context_with_session(Some(path.clone()));
+        context.session.save(&server.token(id)).unwrap();
+        let (_, found, _chat) = resume_session(context.clone()).await.unwrap();
+        assert_eq!(found, id);
+        context.session.clear().unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_session_drops_a_rejected_token_only() {
+        let server = MockServer::start().await;
+        let path = temp_path("resume-rejected");
+        let context = server.context_with_session(Some(path.clone()));
+        context.session.save("stale").unwrap();
+        server.reply("/user/get_profile_token", 500, r#"{"error":"db down"}"#);
+        assert!(resume_session(context.clone()).await.is_none());
+        assert!(context.session.load().is_some());
+        server.reply("/user/get_profile_token", 400, r#"{"message":"invalid token"}"#);
+        assert!(resume_session(context.clone()).await.is_none());
+        assert!(context.session.load().is_none());
     }
-    Ok(())
 }
@@ -1,44 +1,93 @@
+mod api;
+mod chain;
+mod chat;
+mod commands;
+mod config;
 mod context;
+mod dm;
+mod duel;
 mod friends;
 mod game;
 mod game_demo;
 mod infos;
-mod infos_events;
+mod logging;
 mod login;
-mod screen_displays;
+#[cfg(test)]
+mod mock;
+mod practice;
+mod protocol;
+mod replay;
+mod screens;
+mod session;
+mod shell;
+mod smoothing;
+mod spectate;
+mod tls;
+mod tournament;
 mod utils;
 
 use anyhow::{Result, anyhow};
+use clap::Parser;
+use config::{Cli, Settings};
 use context::Context;
-use friends::Friends;
 use infos::Infos;
-use login::Auth;
-use std::cell::{Cell, RefCell};
+use login::{Auth, resume_session};
+use std::cell::RefCell;
+use std::process::ExitCode;
 use std::rc::Rc;
-use utils::{CurrentScreen, LOGO, get_location};
+use screens::{FirstScreen, Router, Screen, Welcome};
+use utils::LOGO;
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{Duration, timeout};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let settings = Settings::resolve(&cli)?;
+    let _log_guard = match &settings.log_dir {
+        Some(dir) => Some(logging::init(dir, &cli.log_level)?),
+        None => None,
+    };
+    tracing::info!(server = %settings.server, version = env!("CARGO_PKG_VERSION"), "starting");
+    let context = Rc::new(Context::new(&settings)?);
+    if let Some(command) = cli.command {
+        return Ok(commands::run(&context, command, cli.json).await);
+    }
+    if cli.shell {
+        shell::run(context, settings.history_file).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sighup = signal(SignalKind::hangup())?;
-    let location = match get_location() {
-        Ok(result) => result,
-        Err(e) => {
-            return Err(anyhow!("{}", e));
+    let auth = Rc::new(RefCell::new(Auth::default()));
+    let resumed = timeout(Duration::from_secs(5), resume_session(context.clone())).await;
+    let first: Box<dyn Screen> = match resumed {
+        Ok(Some(credentials)) => {
+            auth.borrow_mut().set_credentials(credentials);
+            Box::<Welcome>::default()
         }
+        _ => Box::<FirstScreen>::default(),
     };
-    let context = Rc::new(Context::new(location.clone()));
-    let auth = Rc::new(RefCell::new(Auth::default()));
-    let screen = Rc::new(Cell::new(CurrentScreen::default()));
-    let friends = Friends::new(context.clone(), auth.clone(), screen.clone());
     let mut terminal = ratatui::init();
-    let game_main = Infos::new(context, auth, screen, friends);
-    tokio::select! {
-      result = game_main.run(&mut terminal) => result,
-      _ = sigterm.recv() => Ok(()),
-      _ = sighup.recv() => Ok(()),
-    }.map_err(|e| anyhow!("{}", e))?;
+    let mut infos = Infos::new(context, auth);
+    let mut router = Router::new(first);
+    let result = tokio::select! {
+      result = router.run(&mut infos, &mut terminal) => result,
+      _ = sigterm.recv() => {
+        tracing::info!("terminated by SIGTERM");
+        Ok(())
+      }
+      _ = sighup.recv() => {
+        tracing::info!("terminal hung up");
+        Ok(())
+      }
+    };
+    router.leave(&mut infos).await;
+    // the stored token is kept so the next launch resumes the session
+    infos.logout(false).await;
     ratatui::restore();
-    Ok(())
+    if let Err(e) = &result {
+        tracing::error!("interface stopped: {}", e);
+    }
+    result.map(|_| ExitCode::SUCCESS).map_err(|e| anyhow!("{}", e))
 }
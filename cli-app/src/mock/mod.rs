@@ -0,0 +1,428 @@
+//! In-process stand-in for the backend, served over TLS on a random local port
+//!
+//! Every test starts its own server, scripts it (users, friendships, canned replies,
+//! the game stream) and checks what the client did through `requests`.
+
+mod routes;
+
+use crate::config::Settings;
+use crate::context::Context;
+use crate::tls::{Fingerprint, TrustPolicy};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use rustls::ServerConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Same values as the server's GameInstance
+pub(crate) const PADDLE_HEIGHT: f64 = 15.0;
+pub(crate) const PADDLE_WIDTH: f64 = 2.0;
+pub(crate) const PADDLE_PADDING: f64 = 2.0;
+pub(crate) const BALL_SIZE: f64 = 1.5;
+pub(crate) const GAME_ID: &str = "mock-game";
+
+#[derive(Clone)]
+struct User {
+    id: u64,
+    name: String,
+    email: String,
+    password: String,
+}
+
+struct Link {
+    user1_id: u64,
+    user2_id: u64,
+    pending: bool,
+    sender_id: u64,
+}
+
+/// Tournament lobby, every player's socket gets an `UPDATE` on each change
+struct Lobby {
+    id: String,
+    owner: u64,
+    players: Vec<u64>,
+    started: bool,
+    sockets: HashMap<u64, mpsc::UnboundedSender<Message>>,
+}
+
+/// Answer forced for a route, whatever the request
+#[derive(Clone)]
+struct Reply {
+    status: u16,
+    body: String,
+}
+
+#[derive(Default)]
+struct State {
+    users: Vec<User>,
+    tokens: HashMap<String, u64>,
+    links: Vec<Link>,
+    /// (blocked_by, blocked)
+    blocks: Vec<(u64, u64)>,
+    /// (sender, invited)
+    duels: Vec<(u64, u64)>,
+    lobbies: Vec<Lobby>,
+    /// Match history rows: (player1, player2, score1, score2), the row id is the index + 1
+    games: Vec<(u64, u64, u64, u64)>,
+    /// Finished tournaments on the blockchain: (contract address, winner)
+    chain: Vec<(String, u64)>,
+    replies: HashMap<String, Reply>,
+    chats: HashMap<u64, mpsc::UnboundedSender<Message>>,
+    /// Matched with whoever joins the online queue
+    opponent: Option<u64>,
+    /// Sent on every game websocket, then the socket is closed
+    game: Vec<Message>,
+    /// Matches open to spectators: (game id, player 1, player 2)
+    playing: Vec<(String, u64, u64)>,
+    /// `METHOD /path` of every request, without the `/api` prefix
+    requests: Vec<String>,
+    /// Text frames sent by the clients on their chat websocket
+    said: Vec<String>,
+}
+
+impl State {
+    fn add_user(&mut self, name: &str, email: &str, password: &str) -> u64 {
+        let id = self.users.len() as u64 + 1;
+        self.users.push(User {
+            id,
+            name: name.to_string(),
+            email: email.to_string(),
+            password: password.to_string(),
+        });
+        id
+    }
+    fn token(&mut self, id: u64) -> String {
+        let token = format!("mock-token-{}-{}", id, self.tokens.len());
+        self.tokens.insert(token.clone(), id);
+        token
+    }
+    fn user(&self, id: u64) -> Option<&User> {
+        self.users.iter().find(|user| user.id == id)
+    }
+    fn name(&self, id: u64) -> String {
+        self.user(id)
+            .map(|user| user.name.clone())
+            .unwrap_or_default()
+    }
+    /// Sends a server notice on the user's chat websocket, as `chat.serverMsg` does
+    fn notify(&self, id: u64, text: &str, flag: Option<&str>) {
+        let connected: Vec<u64> = self.chats.keys().copied().collect();
+        let frame = serde_json::json!({
+            "username": "<SERVER>",
+            "message": text,
+            "connections": connected,
+            "flag": flag,
+        });
+        if let Some(chat) = self.chats.get(&id) {
+            let _ = chat.send(Message::text(frame.to_string()));
+        }
+    }
+    /// Sends the START frame of a match on the player's chat websocket
+    fn announce(&self, player: u64, opponent: u64, side: u64, mode: &str) {
+        let start = serde_json::json!({
+            "username": "SERVER",
+            "message": "START",
+            "opponentId": opponent,
+            "gameId": GAME_ID,
+            "playerSide": side,
+            "mode": mode,
+        });
+        if let Some(chat) = self.chats.get(&player) {
+            let _ = chat.send(Message::text(start.to_string()));
+        }
+    }
+    /// State of the lobby as `Lobby.getLobbyState` builds it
+    fn lobby_state(&self, lobby: &Lobby, message: &str) -> serde_json::Value {
+        let players: Vec<serde_json::Value> = lobby
+            .players
+            .iter()
+            .map(|id| serde_json::json!({ "id": id, "name": self.name(*id), "elo": 1000.0 }))
+            .collect();
+        serde_json::json!({
+            "message": message,
+            "lobbyId": lobby.id,
+            "ownerId": lobby.owner,
+            "ownerName": self.name(lobby.owner),
+            "players": players,
+            "state": lobby.started as u8,
+        })
+    }
+    fn broadcast_lobby(&self, index: usize) {
+        let lobby = &self.lobbies[index];
+        let update = self.lobby_state(lobby, "UPDATE").to_string();
+        for socket in lobby.sockets.values() {
+            let _ = socket.send(Message::text(update.clone()));
+        }
+    }
+    /// Removes the player from a waiting lobby, the next one becomes the owner
+    fn leave_lobby(&mut self, user: u64, id: &str) -> bool {
+        let Some(index) = self.lobbies.iter().position(|lobby| lobby.id == id) else {
+            return false;
+        };
+        let lobby = &mut self.lobbies[index];
+        if lobby.started || !lobby.players.contains(&user) {
+            return false;
+        }
+        lobby.players.retain(|player| *player != user);
+        lobby.sockets.remove(&user);
+        match lobby.players.first() {
+            None => {
+                self.lobbies.remove(index);
+            }
+            Some(first) => {
+                if lobby.owner == user {
+                    lobby.owner = *first;
+                }
+                self.broadcast_lobby(index);
+            }
+        }
+        true
+    }
+}
+
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    fingerprint: Fingerprint,
+    /// PEM of the self-signed certificate
+    certificate: String,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub(crate) async fn start() -> Self {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .expect("mock certificate");
+        let cert = CertificateDer::from(certified.cert.der().to_vec());
+        let certificate = certified.cert.pem();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+            certified.signing_key.serialize_der(),
+        ));
+        let fingerprint = Fingerprint::of(&cert);
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("mock protocol versions")
+                .with_no_client_auth()
+                .with_single_cert(vec![cert], key)
+                .expect("mock server config");
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("mock bind");
+        let addr = listener.local_addr().expect("mock address");
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn(serve(listener, acceptor, state.clone()));
+        MockServer {
+            addr,
+            fingerprint,
+            certificate,
+            state,
+            task,
+        }
+    }
+    /// host:port to connect to
+    pub(crate) fn address(&self) -> String {
+        self.addr.to_string()
+    }
+    pub(crate) fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+    /// The certificate as a CA bundle would list it
+    pub(crate) fn certificate(&self) -> &str {
+        &self.certificate
+    }
+    /// Client context trusting this server only, nothing is written to disk
+    pub(crate) fn context(&self) -> Rc<Context> {
+        self.context_with_session(None)
+    }
+    pub(crate) fn context_with_session(&self, session_file: Option<PathBuf>) -> Rc<Context> {
+        let settings = Settings {
+            server: self.address(),
+            trust: TrustPolicy::Pinned(self.fingerprint),
+            session_file,
+            ..Settings::default()
+        };
+        Rc::new(Context::new(&settings).expect("mock context"))
+    }
+    /// Context saving a replay of every match to `replay_dir`
+    pub(crate) fn recording_context(&self, replay_dir: PathBuf) -> Rc<Context> {
+        let settings = Settings {
+            server: self.address(),
+            trust: TrustPolicy::Pinned(self.fingerprint),
+            replay_dir: Some(replay_dir),
+            record: true,
+            ..Settings::default()
+        };
+        Rc::new(Context::new(&settings).expect("mock context"))
+    }
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("mock state")
+    }
+    /// #Returns
+    /// The id of the new user
+    pub(crate) fn add_user(&self, name: &str, email: &str, password: &str) -> u64 {
+        self.state().add_user(name, email, password)
+    }
+    /// A valid token for the user, as if they logged in elsewhere
+    pub(crate) fn token(&self, id: u64) -> String {
+        self.state().token(id)
+    }
+    pub(crate) fn befriend(&self, sender: u64, receiver: u64, pending: bool) {
+        self.state().links.push(Link {
+            user1_id: sender,
+            user2_id: receiver,
+            pending,
+            sender_id: sender,
+        });
+    }
+    pub(crate) fn block(&self, by: u64, blocked: u64) {
+        self.state().blocks.push((by, blocked));
+    }
+    /// Adds a finished game to both players' history
+    pub(crate) fn record_game(&self, players: (u64, u64), score: (u64, u64)) {
+        self.state()
+            .games
+            .push((players.0, players.1, score.0, score.1));
+    }
+    /// Adds a finished tournament to the blockchain records
+    pub(crate) fn record_chain(&self, address: &str, winner: u64) {
+        self.state().chain.push((address.to_string(), winner));
+    }
+    /// Forces the answer of a route, such as `/user/login`, to inject errors or odd payloads
+    pub(crate) fn reply(&self, path: &str, status: u16, body: &str) {
+        self.state().replies.insert(
+            path.to_string(),
+            Reply {
+                status,
+                body: body.to_string(),
+            },
+        );
+    }
+    /// The next player joining the online queue plays against `opponent`
+    pub(crate) fn match_with(&self, opponent: u64) {
+        self.state().opponent = Some(opponent);
+    }
+    pub(crate) fn script_game(&self, messages: Vec<Message>) {
+        self.state().game = messages;
+    }
+    /// Lists a match in progress, its spectators get the scripted frames
+    pub(crate) fn play(&self, game_id: &str, players: (u64, u64)) {
+        self.state()
+            .playing
+            .push((game_id.to_string(), players.0, players.1));
+    }
+    /// Sends a raw frame on the user's chat websocket
+    ///
+    /// #Returns
+    /// false if the user is not connected
+    pub(crate) fn send_chat(&self, user: u64, frame: &str) -> bool {
+        match self.state().chats.get(&user) {
+            Some(chat) => chat.send(Message::text(frame)).is_ok(),
+            None => false,
+        }
+    }
+    /// Closes the user's chat websocket, as a server restart would
+    pub(crate) fn disconnect_chat(&self, user: u64) {
+        self.state().chats.remove(&user);
+    }
+    pub(crate) fn chat_connected(&self, user: u64) -> bool {
+        self.state()
+            .chats
+            .get(&user)
+            .is_some_and(|chat| !chat.is_closed())
+    }
+    /// The chat socket is registered by a task of its own, shortly after the handshake
+    ///
+    /// #Returns
+    /// Whether the chat reached the expected state within a second
+    pub(crate) async fn wait_chat(&self, user: u64, connected: bool) -> bool {
+        for _ in 0..100 {
+            if self.chat_connected(user) == connected {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    }
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.state().requests.clone()
+    }
+    /// Waits for the `count`th text frame received on any chat websocket
+    pub(crate) async fn wait_said(&self, count: usize) -> Vec<String> {
+        for _ in 0..100 {
+            if self.state().said.len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.state().said.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, acceptor: TlsAcceptor, state: Arc<Mutex<State>>) {
+    while let Ok((tcp, _)) = listener.accept().await {
+        let acceptor = acceptor.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let Ok(tls) = acceptor.accept(tcp).await else {
+                return;
+            };
+            let service = service_fn(move |request| routes::handle(state.clone(), request));
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(tls), service)
+                .with_upgrades()
+                .await;
+        });
+    }
+}
+
+/// Unique path in the temporary directory, removed by the caller
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "transcendence-test-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+/// 26-byte state frame, laid out as GameState.ts does
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn state_frame(
+    left_y: f32,
+    right_y: f32,
+    ball_x: f32,
+    ball_y: f32,
+    speed_x: f32,
+    speed_y: f32,
+    player1_score: u8,
+    player2_score: u8,
+) -> Message {
+    let mut frame = Vec::with_capacity(26);
+    for value in [left_y, right_y, ball_x, ball_y, speed_x, speed_y] {
+        frame.extend_from_slice(&value.to_le_bytes());
+    }
+    frame.push(player1_score);
+    frame.push(player2_score);
+    Message::binary(frame)
+}
+
+/// End of game message sent by GameServer.ts
+pub(crate) fn winner_frame(winner: u64) -> Message {
+    Message::text(format!(r#"{{"type":"winner","winner":{}}}"#, winner))
+}
@@ -0,0 +1,719 @@
+use super::{
+    BALL_SIZE, GAME_ID, Link, Lobby, PADDLE_HEIGHT, PADDLE_PADDING, PADDLE_WIDTH, Reply, State,
+    User,
+};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::{
+    AUTHORIZATION, CONNECTION, COOKIE, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE,
+};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+type Shared = Arc<Mutex<State>>;
+
+pub(super) async fn handle(
+    state: Shared,
+    mut request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let method = request.method().clone();
+    let path = request
+        .uri()
+        .path()
+        .strip_prefix("/api")
+        .unwrap_or(request.uri().path())
+        .to_string();
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let token = bearer(&request);
+    let reply = {
+        let mut state = state.lock().expect("mock state");
+        state.requests.push(format!("{} {}", method, path));
+        state.replies.get(&path).cloned()
+    };
+    if let Some(Reply { status, body }) = reply {
+        return Ok(respond(status, body));
+    }
+    if request.headers().contains_key(SEC_WEBSOCKET_KEY) {
+        return Ok(upgrade(state, &mut request, &path, &query));
+    }
+    let body = match request.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(_) => return Ok(respond(400, message("unreadable body"))),
+    };
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let response = {
+        let mut state = state.lock().expect("mock state");
+        let user = token.and_then(|token| state.tokens.get(&token).copied());
+        route(&mut state, &method, &path, &query, &body, user)
+    };
+    if path == "/tournament/start" {
+        std::future::pending::<()>().await;
+    }
+    Ok(response)
+}
+
+fn route(
+    state: &mut State,
+    method: &Method,
+    path: &str,
+    query: &str,
+    body: &Value,
+    user: Option<u64>,
+) -> Response<Full<Bytes>> {
+    let field = |name: &str| body[name].as_str().unwrap_or_default().to_string();
+    let friend_id = body["friend_id"].as_u64().unwrap_or_default();
+    match (method.as_str(), path) {
+        ("POST", "/user/create") => {
+            let (name, email) = (field("username"), field("email"));
+            if state
+                .users
+                .iter()
+                .any(|user| user.name == name || user.email == email)
+            {
+                return respond(400, message("user already exist"));
+            }
+            let id = state.add_user(&name, &email, &field("passw"));
+            respond(200, json!({ "token": state.token(id) }).to_string())
+        }
+        ("POST", "/user/login") => {
+            let (email, password) = (field("email"), field("passw"));
+            let found = state
+                .users
+                .iter()
+                .find(|user| user.email == email && user.password == password)
+                .map(|user| user.id);
+            match found {
+                Some(id) => respond(200, json!({ "token": state.token(id) }).to_string()),
+                None => respond(404, message("email or password invalid")),
+            }
+        }
+        ("POST", "/user/create_guest") => {
+            let name = format!("guest{}", state.users.len() + 1);
+            let id = state.add_user(&name, "", "");
+            respond(200, json!({ "token": state.token(id) }).to_string())
+        }
+        ("POST", "/user/get_profile_token") => match user.and_then(|id| state.user(id)) {
+            Some(user) => respond(200, profile(user)),
+            None => respond(400, message("invalid token")),
+        },
+        ("GET", "/user/get_profile_id") => {
+            let id = param(query, "user_id").parse().unwrap_or_default();
+            match state.user(id) {
+                Some(user) => respond(200, profile(user)),
+                None => respond(404, message("user not found")),
+            }
+        }
+        ("GET", "/user/get_profile_name") => {
+            let name = param(query, "profile_name");
+            match state.users.iter().find(|user| user.name == name) {
+                Some(user) => respond(200, profile(user)),
+                None => respond(404, message("user not found")),
+            }
+        }
+        ("POST", "/user/logout") => {
+            if let Some(id) = user {
+                state.chats.remove(&id);
+            }
+            respond(200, message("logged out"))
+        }
+        ("POST", "/user/blocked_users") => {
+            let Some(id) = user else {
+                return respond(400, message("token is invalid"));
+            };
+            let rows: Vec<Value> = state
+                .blocks
+                .iter()
+                .filter(|(by, _)| *by == id)
+                .map(|(by, blocked)| {
+                    json!({
+                        "user1_id": by.min(blocked),
+                        "user2_id": by.max(blocked),
+                        "blocked_by": by,
+                    })
+                })
+                .collect();
+            respond(200, Value::from(rows).to_string())
+        }
+        ("GET", "/friends/get") => {
+            let id: u64 = param(query, "user_id").parse().unwrap_or_default();
+            let links: Vec<Value> = state
+                .links
+                .iter()
+                .filter(|link| link.user1_id == id || link.user2_id == id)
+                .map(|link| {
+                    json!({
+                        "user1_id": link.user1_id,
+                        "user2_id": link.user2_id,
+                        "pending": link.pending as u64,
+                        "sender_id": link.sender_id,
+                    })
+                })
+                .collect();
+            respond(200, Value::from(links).to_string())
+        }
+        ("POST", "/friends/send_request") => {
+            let Some(id) = user else {
+                return respond(400, message("invalid token"));
+            };
+            if state.user(friend_id).is_none() {
+                return respond(404, message("user not found"));
+            }
+            state.links.push(Link {
+                user1_id: id,
+                user2_id: friend_id,
+                pending: true,
+                sender_id: id,
+            });
+            respond(200, message("friend request sent"))
+        }
+        ("POST", "/friends/accept") => {
+            let link = state.links.iter_mut().find(|link| {
+                link.sender_id == friend_id && user == Some(link.user2_id) && link.pending
+            });
+            match link {
+                Some(link) => {
+                    link.pending = false;
+                    respond(200, message("friend request accepted"))
+                }
+                None => respond(404, message("friend request not found")),
+            }
+        }
+        ("DELETE", "/friends/remove") => {
+            let Some(id) = user else {
+                return respond(400, message("invalid token"));
+            };
+            let before = state.links.len();
+            state.links.retain(|link| {
+                !((link.user1_id == id && link.user2_id == friend_id)
+                    || (link.user2_id == id && link.user1_id == friend_id))
+            });
+            match state.links.len() < before {
+                true => respond(200, message("friend removed")),
+                false => respond(404, message("friend not found")),
+            }
+        }
+        ("POST", "/create-game") => {
+            let Some(id) = user else {
+                return respond(400, json!({ "error": "Invalid token" }).to_string());
+            };
+            // bot and local games start right away, against the bot's own account or nobody
+            let mode = field("mode");
+            if mode == "bot" || mode == "local" {
+                let bot = state.users.iter().find(|user| user.name == "bot");
+                let opponent = match (mode.as_str(), bot.map(|bot| bot.id)) {
+                    ("local", _) => 0,
+                    (_, Some(bot)) => bot,
+                    (_, None) => state.add_user("bot", "bot@localhost", ""),
+                };
+                let answer = json!({
+                    "gameId": GAME_ID,
+                    "opponentId": opponent,
+                    "playerSide": "1",
+                    "paddleHeight": PADDLE_HEIGHT,
+                    "paddleWidth": PADDLE_WIDTH,
+                    "paddlePadding": PADDLE_PADDING,
+                    "ballSize": BALL_SIZE,
+                });
+                return respond(201, answer.to_string());
+            }
+            if let Some(opponent) = state.opponent.take()
+                && let Some(chat) = state.chats.get(&id)
+            {
+                let start = json!({
+                    "username": "SERVER",
+                    "message": "START",
+                    "opponentId": opponent,
+                    "gameId": GAME_ID,
+                    "playerSide": 1,
+                    "mode": field("mode"),
+                });
+                let _ = chat.send(Message::text(start.to_string()));
+            }
+            let answer = json!({
+                "message": "added to queue",
+                "paddleHeight": PADDLE_HEIGHT,
+                "paddleWidth": PADDLE_WIDTH,
+                "paddlePadding": PADDLE_PADDING,
+                "ballSize": BALL_SIZE,
+            });
+            respond(202, answer.to_string())
+        }
+        ("POST", start) if start.starts_with("/start-game/") => respond(200, String::new()),
+        ("GET", "/game/ongoing") => {
+            if user.is_none() {
+                return respond(401, json!({ "error": "Invalid token" }).to_string());
+            }
+            let games: Vec<Value> = state
+                .playing
+                .iter()
+                .map(|(id, player1, player2)| {
+                    json!({
+                        "gameId": id,
+                        "mode": "online",
+                        "player1Id": player1,
+                        "player2Id": player2,
+                    })
+                })
+                .collect();
+            respond(200, Value::from(games).to_string())
+        }
+        ("DELETE", "/chat/removeQueue") => respond(200, message("removed from queue")),
+        ("POST", "/chat/healthCallback") => respond(200, message("ok")),
+        ("POST", "/duel/list") => {
+            let Some(id) = user else {
+                return respond(400, message("invalid token"));
+            };
+            let duels: Vec<Value> = state
+                .duels
+                .iter()
+                .filter(|(sender, invited)| *sender == id || *invited == id)
+                .map(|(sender, invited)| json!({ "senderId": sender, "id": invited }))
+                .collect();
+            respond(200, Value::from(duels).to_string())
+        }
+        ("POST", "/duel/invite") => {
+            let (Some(id), Some(invited)) = (user, body["id"].as_u64()) else {
+                return respond(400, message("invalid token"));
+            };
+            if state.user(invited).is_none() {
+                return respond(404, message("user not found"));
+            }
+            if !state.duels.contains(&(id, invited)) {
+                state.duels.push((id, invited));
+                let text = format!("{} is inviting you for a duel", state.name(id));
+                state.notify(invited, &text, None);
+            }
+            respond(200, message("invite sent"))
+        }
+        ("POST", "/duel/accept") => {
+            let (Some(id), Some(sender)) = (user, body["id"].as_u64()) else {
+                return respond(400, message("invalid token"));
+            };
+            let Some(index) = state.duels.iter().position(|duel| *duel == (sender, id)) else {
+                return respond(404, message("invite not found"));
+            };
+            state.duels.remove(index);
+            state.announce(sender, id, 1, "duel");
+            state.announce(id, sender, 2, "duel");
+            respond(
+                200,
+                json!({ "id": GAME_ID, "message": "starting game" }).to_string(),
+            )
+        }
+        ("POST", "/duel/decline") => {
+            let (Some(id), Some(other)) = (user, body["id"].as_u64()) else {
+                return respond(400, message("invalid token"));
+            };
+            let before = state.duels.len();
+            state
+                .duels
+                .retain(|duel| *duel != (id, other) && *duel != (other, id));
+            if state.duels.len() == before {
+                return respond(404, message("invite not found"));
+            }
+            let text = format!("{} has declined the invite.", state.name(id));
+            state.notify(other, &text, Some("duelDeclined"));
+            respond(200, message("invite has been declined"))
+        }
+        ("POST", "/chat/dm") => {
+            let Some(sender) = user
+                .and_then(|id| state.user(id))
+                .map(|user| user.name.clone())
+            else {
+                return respond(400, message("invalid token"));
+            };
+            let Some(id) = state
+                .users
+                .iter()
+                .find(|user| user.name == field("username"))
+                .map(|user| user.id)
+            else {
+                return respond(404, message("user does not exist"));
+            };
+            let blocked = state.blocks.iter().any(|(by, blocked)| {
+                (Some(*by), *blocked) == (user, id) || (*by, Some(*blocked)) == (id, user)
+            });
+            if blocked {
+                return respond(200, message("Success"));
+            }
+            let frame =
+                json!({ "username": sender, "message": format!("[dm] -> {}", field("msg")) });
+            match state.chats.get(&id) {
+                Some(chat) if chat.send(Message::text(frame.to_string())).is_ok() => {
+                    respond(200, message("Success"))
+                }
+                _ => respond(200, message("user is offline")),
+            }
+        }
+        ("GET", path) if path.starts_with("/user/get_history_name/") => {
+            let name = path.trim_start_matches("/user/get_history_name/");
+            let Some(id) = state
+                .users
+                .iter()
+                .find(|user| user.name == name)
+                .map(|user| user.id)
+            else {
+                return respond(404, message("user not found"));
+            };
+            let rows: Vec<Value> = state
+                .games
+                .iter()
+                .enumerate()
+                .filter(|(_, game)| game.0 == id || game.1 == id)
+                .map(|(index, &(player1, player2, score1, score2))| {
+                    json!({
+                        "id": index + 1,
+                        "tournament_id": "-1",
+                        "player1_id": player1,
+                        "player2_id": player2,
+                        "winner_id": if score1 > score2 { player1 } else { player2 },
+                        "score1": score1,
+                        "score2": score2,
+                        "played_at": "2026-01-01 12:00:00",
+                    })
+                })
+                .collect();
+            match rows.is_empty() {
+                true => respond(404, message("no games :(")),
+                false => respond(200, Value::Array(rows).to_string()),
+            }
+        }
+        ("GET", "/tournament/list") => {
+            let open: Vec<Value> = state
+                .lobbies
+                .iter()
+                .filter(|lobby| !lobby.started)
+                .map(|lobby| {
+                    json!({
+                        "id": lobby.id,
+                        "ownerName": state.name(lobby.owner),
+                        "playerCount": lobby.players.len(),
+                        "type": "tournament",
+                    })
+                })
+                .collect();
+            respond(200, Value::Array(open).to_string())
+        }
+        ("GET", "/tournament/blockchain") => {
+            let records: Vec<Value> = state
+                .chain
+                .iter()
+                .map(|(address, winner)| json!({ "address": address, "winner": winner }))
+                .collect();
+            respond(200, Value::Array(records).to_string())
+        }
+        // the answer is dropped by `handle`, as the server never sends one
+        ("POST", "/tournament/start") => {
+            let id = field("lobbyId");
+            let lobby = state
+                .lobbies
+                .iter_mut()
+                .find(|lobby| lobby.id == id && !lobby.started && Some(lobby.owner) == user);
+            let Some(lobby) = lobby else {
+                return respond(403, message("you are not the owner of the tournament"));
+            };
+            lobby.started = true;
+            let players = lobby.players.clone();
+            for player in &players {
+                state.notify(*player, "The tournament is starting! Good luck!", None);
+            }
+            for pair in players.chunks(2) {
+                match pair {
+                    [first, second] => {
+                        state.announce(*first, *second, 1, "online");
+                        state.announce(*second, *first, 2, "online");
+                    }
+                    [alone] => state.announce(*alone, 0, 1, "bot"),
+                    _ => {}
+                }
+            }
+            respond(200, message("Success"))
+        }
+        ("POST", "/tournament/leave") => {
+            let Some(id) = user else {
+                return respond(400, message("invalid token"));
+            };
+            state.leave_lobby(id, &field("lobbyId"));
+            respond(200, message("Success"))
+        }
+        _ => respond(404, message("route not found")),
+    }
+}
+
+/// Accepts `/chat`, `/game/{id}/{side}` and `/spectate/{id}` websockets
+fn upgrade(
+    state: Shared,
+    request: &mut Request<Incoming>,
+    path: &str,
+    query: &str,
+) -> Response<Full<Bytes>> {
+    let user = cookie_token(request).and_then(|token| {
+        let state = state.lock().expect("mock state");
+        state.tokens.get(&token).copied()
+    });
+    let Some(user) = user else {
+        return respond(400, message("invalid token"));
+    };
+    let lobby = match path {
+        "/tournament/create" => Some(None),
+        "/tournament/join" => Some(Some(param(query, "lobbyId"))),
+        _ => None,
+    };
+    let chat = path == "/chat";
+    let watched = path.strip_prefix("/spectate/").map(str::to_string);
+    if !chat && lobby.is_none() && watched.is_none() && !path.starts_with("/game/") {
+        return respond(404, message("route not found"));
+    }
+    let key = request.headers()[SEC_WEBSOCKET_KEY].as_bytes().to_vec();
+    let upgrade = hyper::upgrade::on(request);
+    tokio::spawn(async move {
+        let Ok(upgraded) = upgrade.await else {
+            return;
+        };
+        let ws = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+        if chat {
+            chat_socket(state, user, ws).await;
+        } else if let Some(lobby) = lobby {
+            lobby_socket(state, user, lobby, ws).await;
+        } else if let Some(game_id) = watched {
+            spectate_socket(state, game_id, ws).await;
+        } else {
+            game_socket(state, ws).await;
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, derive_accept_key(&key))
+        .body(Full::new(Bytes::new()))
+        .expect("mock upgrade response")
+}
+
+async fn chat_socket(
+    state: Shared,
+    user: u64,
+    ws: WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+) {
+    let (sender, mut outgoing) = mpsc::unbounded_channel();
+    let registered = sender.downgrade();
+    state.lock().expect("mock state").chats.insert(user, sender);
+    let (mut write, mut read) = ws.split();
+    loop {
+        tokio::select! {
+            frame = outgoing.recv() => match frame {
+                Some(frame) => {
+                    if write.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                // logged out, the server drops the connection
+                None => {
+                    let _ = write.close().await;
+                    break;
+                }
+            },
+            incoming = read.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(Message::Text(text))) => {
+                    state.lock().expect("mock state").said.push(text.to_string());
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    // a reconnection may already have replaced this socket
+    if let Some(sender) = registered.upgrade() {
+        let mut state = state.lock().expect("mock state");
+        if state
+            .chats
+            .get(&user)
+            .is_some_and(|chat| chat.same_channel(&sender))
+        {
+            state.chats.remove(&user);
+        }
+    }
+}
+
+/// Creates a lobby, or joins `lobby` when given, then forwards its updates
+///
+/// Refusals are silent, as on the server, closing the socket leaves the lobby
+async fn lobby_socket(
+    state: Shared,
+    user: u64,
+    lobby: Option<String>,
+    ws: WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+) {
+    let (sender, mut outgoing) = mpsc::unbounded_channel();
+    let joined = {
+        let mut state = state.lock().expect("mock state");
+        let busy = state
+            .lobbies
+            .iter()
+            .any(|lobby| lobby.players.contains(&user));
+        match (busy, lobby) {
+            (true, _) => None,
+            (false, None) => {
+                let id = format!("lobby-{}", user);
+                state.lobbies.push(Lobby {
+                    id: id.clone(),
+                    owner: user,
+                    players: vec![user],
+                    started: false,
+                    sockets: HashMap::from([(user, sender)]),
+                });
+                let created = state.lobby_state(state.lobbies.last().expect("lobby"), "created");
+                let _ = state.lobbies.last().expect("lobby").sockets[&user]
+                    .send(Message::text(created.to_string()));
+                Some(id)
+            }
+            (false, Some(id)) => {
+                let index = state
+                    .lobbies
+                    .iter()
+                    .position(|lobby| lobby.id == id && !lobby.started);
+                index.map(|index| {
+                    let lobby = &mut state.lobbies[index];
+                    lobby.players.push(user);
+                    lobby.sockets.insert(user, sender);
+                    state.broadcast_lobby(index);
+                    id
+                })
+            }
+        }
+    };
+    let (mut write, mut read) = ws.split();
+    let Some(id) = joined else {
+        while let Some(Ok(frame)) = read.next().await {
+            if frame.is_close() {
+                break;
+            }
+        }
+        return;
+    };
+    loop {
+        tokio::select! {
+            frame = outgoing.recv() => match frame {
+                Some(frame) => {
+                    if write.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                // left through the API, the server drops the connection
+                None => {
+                    let _ = write.close().await;
+                    break;
+                }
+            },
+            incoming = read.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    state.lock().expect("mock state").leave_lobby(user, &id);
+}
+
+/// Plays the scripted frames in one burst then closes, paddle moves from the client are ignored
+async fn game_socket(state: Shared, ws: WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>) {
+    let frames = state.lock().expect("mock state").game.clone();
+    let (mut write, mut read) = ws.split();
+    tokio::spawn(async move { while let Some(Ok(_)) = read.next().await {} });
+    for frame in frames {
+        if write.feed(frame).await.is_err() {
+            return;
+        }
+    }
+    let _ = write.close().await;
+}
+
+/// Refuses matches that are not listed, as the server does for unknown and local games
+async fn spectate_socket(
+    state: Shared,
+    game_id: String,
+    mut ws: WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+) {
+    let listed = state
+        .lock()
+        .expect("mock state")
+        .playing
+        .iter()
+        .any(|(id, _, _)| *id == game_id);
+    if listed {
+        return game_socket(state, ws).await;
+    }
+    let refusal = json!({ "type": "error", "message": "Game not found" });
+    let _ = ws.send(Message::text(refusal.to_string())).await;
+    let _ = ws.close(None).await;
+}
+
+fn respond(status: u16, body: String) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("mock response")
+}
+
+fn message(text: &str) -> String {
+    json!({ "message": text }).to_string()
+}
+
+fn profile(user: &User) -> String {
+    json!({
+        "id": user.id,
+        "name": user.name,
+        "status": 1,
+        "is_login": 1,
+        "elo": 1000.0,
+        "games_played": 0,
+        "wins": 0,
+        "source": 0,
+    })
+    .to_string()
+}
+
+fn bearer<B>(request: &Request<B>) -> Option<String> {
+    let value = request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(str::to_string)
+}
+
+fn cookie_token<B>(request: &Request<B>) -> Option<String> {
+    let cookies = request.headers().get(COOKIE)?.to_str().ok()?;
+    cookies
+        .split(';')
+        .find_map(|cookie| cookie.trim().strip_prefix("jwt_session="))
+        .map(str::to_string)
+}
+
+/// Value of a query parameter, percent decoded
+fn param(query: &str, name: &str) -> String {
+    let raw = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .unwrap_or_default();
+    let mut decoded = Vec::new();
+    let mut bytes = raw.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                decoded.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+            }
+            _ => decoded.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,315 @@
+use crate::game::GameStats;
+use crate::game_demo::TRACKING;
+use crate::infos::GameParams;
+use crate::smoothing::SERVER_RATE;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Speeds are in percents of the field per second, the server's defaults
+const BALL_SPEED: f64 = 100.0;
+/// Added to the ball speed on every paddle hit
+const BALL_SPEED_INCREMENT: f64 = 5.0;
+const PADDLE_SPEED: f64 = 70.0;
+const POINTS_TO_WIN: u8 = 11;
+/// Vertical speed for a hit on the edge of a paddle, relative to the horizontal one
+const MAX_ANGLE: f64 = 1.0;
+/// Steepest share of the speed kept when bouncing on a wall
+const MAX_WALL_ANGLE: f64 = 0.65;
+/// Steepest serve, relative to the horizontal speed
+const MAX_SERVE_ANGLE: f64 = 0.2;
+/// Pause before the ball is served after a point
+const SERVE_DELAY: Duration = Duration::from_millis(500);
+/// Frame the demo paddles cover `TRACKING` of their distance to the ball in
+const DEMO_FRAME: f64 = 0.016;
+
+/// Skill of the paddle we play against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Level {
+    /// Only follows the ball once it crossed the middle
+    Easy,
+    /// Follows the ball as the menu demo does
+    Normal,
+    /// Quicker, and waits where the ball will arrive rather than where it is
+    Hard,
+}
+
+impl Level {
+    pub(crate) const ALL: [Level; 3] = [Level::Easy, Level::Normal, Level::Hard];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Level::Easy => "Easy",
+            Level::Normal => "Normal",
+            Level::Hard => "Hard",
+        }
+    }
+    /// Share of its distance to the target the paddle covers every demo frame
+    fn tracking(self) -> f64 {
+        match self {
+            Level::Easy => TRACKING * 0.5,
+            Level::Normal => TRACKING,
+            Level::Hard => TRACKING * 2.5,
+        }
+    }
+}
+
+/// Match against a computer paddle, simulated here without any server
+///
+/// We play the left paddle, positions are centers in percents of the field as in the server state
+pub(crate) struct Practice {
+    params: GameParams,
+    level: Level,
+    ball: (f64, f64),
+    speed: (f64, f64),
+    /// Length of `speed`, grows with every hit
+    pace: f64,
+    paddles: [f64; 2],
+    scores: [u8; 2],
+    /// Left before the ball moves again
+    serve_in: Duration,
+    /// Time not simulated yet, less than a step
+    lag: Duration,
+    /// xorshift state for the serves
+    seed: u64,
+}
+
+impl Practice {
+    pub(crate) fn new(params: GameParams, level: Level) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |now| now.as_nanos() as u64);
+        Self::with_seed(params, level, seed)
+    }
+    fn with_seed(params: GameParams, level: Level, seed: u64) -> Self {
+        let mut practice = Practice {
+            params,
+            level,
+            ball: (50.0, 50.0),
+            speed: (0.0, 0.0),
+            pace: BALL_SPEED,
+            paddles: [50.0; 2],
+            scores: [0; 2],
+            serve_in: SERVE_DELAY,
+            lag: Duration::ZERO,
+            seed: seed | 1,
+        };
+        let toward_us = practice.random() < 0.5;
+        practice.serve(toward_us);
+        practice
+    }
+    pub(crate) fn params(&self) -> &GameParams {
+        &self.params
+    }
+    /// Winning side once the match is over, 0 for us
+    pub(crate) fn winner(&self) -> Option<usize> {
+        self.scores.iter().position(|&score| score >= POINTS_TO_WIN)
+    }
+    /// State in the shape the game screen draws
+    pub(crate) fn stats(&self) -> GameStats {
+        GameStats {
+            left_y: self.paddles[0] as f32,
+            right_y: self.paddles[1] as f32,
+            ball_x: self.ball.0 as f32,
+            ball_y: self.ball.1 as f32,
+            speed_x: (self.speed.0 / SERVER_RATE) as f32,
+            speed_y: (self.speed.1 / SERVER_RATE) as f32,
+            player1_score: self.scores[0],
+            player2_score: self.scores[1],
+            winner: self.winner() == Some(0),
+        }
+    }
+    /// Runs the match for the time elapsed since the last call
+    ///
+    /// #Parameters
+    /// - elapsed: simulated in fixed steps, the rest is kept for the next call
+    /// - direction: of our paddle, -1 up, 1 down, 0 still
+    pub(crate) fn advance(&mut self, elapsed: Duration, direction: f64) {
+        let step = Duration::from_secs_f64(1.0 / SERVER_RATE);
+        self.lag += elapsed;
+        while self.lag >= step && self.winner().is_none() {
+            self.lag -= step;
+            self.step(step, direction);
+        }
+    }
+    fn step(&mut self, step: Duration, direction: f64) {
+        let dt = step.as_secs_f64();
+        let half = self.params.paddle_height / 2.0;
+        self.paddles[0] =
+            (self.paddles[0] + direction * PADDLE_SPEED * dt).clamp(half, 100.0 - half);
+        self.move_computer(dt);
+        if !self.serve_in.is_zero() {
+            self.serve_in = self.serve_in.saturating_sub(step);
+            return;
+        }
+        self.ball.0 += self.speed.0 * dt;
+        self.ball.1 += self.speed.1 * dt;
+        let radius = self.params.ball_size / 2.0;
+        let face = self.params.paddle_padding + self.params.paddle_width;
+        if self.ball.0 < 0.0 || self.ball.0 > 100.0 {
+            // the side that missed receives the next serve
+            let missed = usize::from(self.ball.0 > 100.0);
+            self.scores[1 - missed] += 1;
+            self.serve(missed == 0);
+        } else if self.speed.0 < 0.0 && self.hits(0, face + radius) {
+            self.bounce(0, face + radius);
+        } else if self.speed.0 > 0.0 && self.hits(1, 100.0 - face - radius) {
+            self.bounce(1, 100.0 - face - radius);
+        } else if (self.ball.1 < radius && self.speed.1 < 0.0)
+            || (self.ball.1 > 100.0 - radius && self.speed.1 > 0.0)
+        {
+            self.ball.1 = self.ball.1.clamp(radius, 100.0 - radius);
+            self.bounce_wall();
+        }
+    }
+    /// The ball reached the face of a paddle, and is not already behind it
+    fn hits(&self, side: usize, face: f64) -> bool {
+        let reached = match side {
+            0 => self.ball.0 <= face && self.ball.0 >= face - self.params.paddle_width,
+            _ => self.ball.0 >= face && self.ball.0 <= face + self.params.paddle_width,
+        };
+        let reach = self.params.paddle_height / 2.0 + self.params.ball_size / 2.0;
+        reached && (self.ball.1 - self.paddles[side]).abs() <= reach
+    }
+    /// The further from the center of the paddle, the steeper the ball leaves
+    fn bounce(&mut self, side: usize, face: f64) {
+        self.pace += BALL_SPEED_INCREMENT;
+        let offset = (self.ball.1 - self.paddles[side]) / (self.params.paddle_height / 2.0);
+        let away = if side == 0 { 1.0 } else { -1.0 };
+        self.ball.0 = face;
+        self.aim(away, offset.clamp(-1.0, 1.0) * MAX_ANGLE);
+    }
+    fn bounce_wall(&mut self) {
+        self.speed.1 = -self.speed.1;
+        let steepest = self.pace * MAX_WALL_ANGLE;
+        if self.speed.1.abs() > steepest {
+            self.speed.1 = steepest.copysign(self.speed.1);
+            self.speed.0 = (self.pace * self.pace - steepest * steepest)
+                .sqrt()
+                .copysign(self.speed.0);
+        }
+    }
+    fn serve(&mut self, toward_us: bool) {
+        self.ball = (50.0, 50.0);
+        self.pace = BALL_SPEED;
+        self.serve_in = SERVE_DELAY;
+        let slope = (self.random() * 2.0 - 1.0) * MAX_SERVE_ANGLE;
+        self.aim(if toward_us { -1.0 } else { 1.0 }, slope);
+    }
+    /// Sets the direction of the ball, keeping its pace
+    fn aim(&mut self, x: f64, y: f64) {
+        let length = (x * x + y * y).sqrt();
+        self.speed = (x / length * self.pace, y / length * self.pace);
+    }
+    fn move_computer(&mut self, dt: f64) {
+        let coming = self.speed.0 > 0.0 && self.serve_in.is_zero();
+        let target = match self.level {
+            Level::Easy if coming && self.ball.0 > 50.0 => self.ball.1,
+            Level::Normal if coming => self.ball.1,
+            Level::Hard if coming => self.landing(),
+            _ => 50.0,
+        };
+        // the demo's tracking is per frame, spread it over the steps of a frame
+        let share = 1.0 - (1.0 - self.level.tracking()).powf(dt / DEMO_FRAME);
+        let reach = PADDLE_SPEED * dt;
+        let half = self.params.paddle_height / 2.0;
+        let moved = ((target - self.paddles[1]) * share).clamp(-reach, reach);
+        self.paddles[1] = (self.paddles[1] + moved).clamp(half, 100.0 - half);
+    }
+    /// Height at which the ball will reach the right paddle, bouncing off the walls
+    fn landing(&self) -> f64 {
+        let radius = self.params.ball_size / 2.0;
+        let face = 100.0 - self.params.paddle_padding - self.params.paddle_width - radius;
+        let time = (face - self.ball.0) / self.speed.0;
+        let span = 100.0 - 2.0 * radius;
+        // unfold the walls: the height bounces back every `span`
+        let unfolded = (self.ball.1 - radius + self.speed.1 * time).rem_euclid(2.0 * span);
+        radius
+            + if unfolded > span {
+                2.0 * span - unfolded
+            } else {
+                unfolded
+            }
+    }
+    /// Uniform in [0, 1)
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(5);
+
+    fn practice(level: Level) -> Practice {
+        let mut practice = Practice::with_seed(GameParams::default(), level, 42);
+        practice.serve_in = Duration::ZERO;
+        practice
+    }
+
+    #[test]
+    fn paddles_send_the_ball_back_faster() {
+        let mut practice = practice(Level::Normal);
+        practice.ball = (6.0, 52.0);
+        practice.aim(-1.0, 0.0);
+        practice.advance(STEP * 4, 0.0);
+        assert!(practice.speed.0 > 0.0);
+        assert_eq!(practice.pace, BALL_SPEED + BALL_SPEED_INCREMENT);
+        // hit below the center of the paddle, the ball leaves downwards
+        assert!(practice.speed.1 > 0.0);
+        assert_eq!(practice.scores, [0, 0]);
+    }
+
+    #[test]
+    fn missed_balls_score_and_are_served_to_whoever_missed() {
+        let mut practice = practice(Level::Normal);
+        practice.ball = (2.0, 90.0);
+        practice.aim(-1.0, 0.0);
+        practice.advance(STEP * 10, 0.0);
+        assert_eq!(practice.scores, [0, 1]);
+        assert_eq!(practice.ball, (50.0, 50.0));
+        assert_eq!(practice.pace, BALL_SPEED);
+        assert!(practice.speed.0 < 0.0);
+        // the ball waits before the serve
+        practice.advance(SERVE_DELAY / 2, 0.0);
+        assert_eq!(practice.ball, (50.0, 50.0));
+    }
+
+    #[test]
+    fn walls_bounce_the_ball() {
+        let mut practice = practice(Level::Normal);
+        practice.ball = (50.0, 1.0);
+        practice.aim(1.0, -0.5);
+        practice.advance(STEP * 2, 0.0);
+        assert!(practice.speed.1 > 0.0);
+        assert!(practice.speed.1 <= practice.pace * MAX_WALL_ANGLE + 1e-9);
+    }
+
+    #[test]
+    fn hard_computer_waits_where_the_ball_lands() {
+        let mut practice = practice(Level::Hard);
+        practice.ball = (50.0, 50.0);
+        practice.aim(1.0, 0.6);
+        let landing = practice.landing();
+        // bounced once off the bottom wall
+        assert!(landing < 100.0 && landing > 50.0);
+        practice.advance(Duration::from_millis(400), 0.0);
+        assert!((practice.paddles[1] - landing).abs() < 1.0);
+    }
+
+    #[test]
+    fn idle_player_loses_to_every_level() {
+        for level in Level::ALL {
+            let mut practice = practice(level);
+            for _ in 0..10_000 {
+                practice.advance(Duration::from_millis(16), 0.0);
+            }
+            assert_eq!(practice.winner(), Some(1), "{:?}", level);
+            assert_eq!(practice.scores[1], POINTS_TO_WIN);
+            assert!(!practice.stats().winner);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Messages of the game websocket, as written by the backend's GameState.ts and GameServer.ts
+//!
+//! The tests hold frames laid out by hand from GameState.ts: a change of the server's layout
+//! makes them fail instead of drawing a garbled field.
+
+use crate::game::GameStats;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Byte offsets of the fields of a state frame, for one version of GameState.ts
+#[derive(Debug)]
+pub(crate) struct Layout {
+    /// Bumped whenever GameState.ts moves, adds or removes a field
+    pub(crate) version: u8,
+    pub(crate) left_y: usize,
+    pub(crate) right_y: usize,
+    pub(crate) ball_x: usize,
+    pub(crate) ball_y: usize,
+    pub(crate) speed_x: usize,
+    pub(crate) speed_y: usize,
+    pub(crate) player1_score: usize,
+    pub(crate) player2_score: usize,
+    /// GameState.BUFFER_SIZE
+    pub(crate) len: usize,
+}
+
+/// FLOAT_BYTE_SIZE, FLOAT_NB and INT_NB of GameState.ts
+const FLOAT_BYTE_SIZE: usize = 4;
+const FLOAT_NB: usize = 6;
+const INT_NB: usize = 2;
+
+/// Offset of the float at this StateIndex, little endian f32s come first
+const fn float(index: usize) -> usize {
+    index * FLOAT_BYTE_SIZE
+}
+
+/// Offset of the byte at this StateIndex, the u8s follow the floats
+const fn int(index: usize) -> usize {
+    FLOAT_NB * FLOAT_BYTE_SIZE + index
+}
+
+/// Layout the server sends, from the StateIndex enum of GameState.ts
+pub(crate) const LAYOUT: Layout = Layout {
+    version: 1,
+    left_y: float(0),
+    right_y: float(1),
+    ball_x: float(2),
+    ball_y: float(3),
+    speed_x: float(4),
+    speed_y: float(5),
+    player1_score: int(0),
+    player2_score: int(1),
+    len: FLOAT_NB * FLOAT_BYTE_SIZE + INT_NB,
+};
+/// Size of a state frame in `LAYOUT`
+pub(crate) const STATE_LEN: usize = LAYOUT.len;
+
+#[derive(Error, Debug, PartialEq)]
+pub(crate) enum ProtocolError {
+    #[error(
+        "state frame of {0} bytes, layout version {version} has {len} bytes",
+        version = LAYOUT.version,
+        len = LAYOUT.len
+    )]
+    Length(usize),
+    #[error("state frame with an invalid value at byte {0}")]
+    Value(usize),
+    #[error("unexpected game message: {0}")]
+    Message(String),
+}
+
+/// Anything the game server sends on the websocket
+#[derive(Debug, PartialEq)]
+pub(crate) enum GameFrame {
+    /// Binary state of the field, sent 60 times a second
+    State(GameStats),
+    /// The match is over, the id is 0 when player 2 wins a local game
+    Winner(u64),
+    /// The game was not found, the socket is closed right after
+    Error(String),
+}
+
+/// Text messages of GameServer.ts
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Text {
+    Winner { winner: u64 },
+    Error { message: String },
+}
+
+fn read_float(frame: &[u8], offset: usize) -> Result<f32, ProtocolError> {
+    let value = f32::from_le_bytes([
+        frame[offset],
+        frame[offset + 1],
+        frame[offset + 2],
+        frame[offset + 3],
+    ]);
+    match value.is_finite() {
+        true => Ok(value),
+        false => Err(ProtocolError::Value(offset)),
+    }
+}
+
+/// Decodes a binary state frame laid out as `LAYOUT`
+///
+/// Side 2 receives the reversed buffer: paddles and scores swapped, ball mirrored, so each
+/// player sees itself on the left
+pub(crate) fn decode_state(frame: &[u8]) -> Result<GameFrame, ProtocolError> {
+    if frame.len() != LAYOUT.len {
+        return Err(ProtocolError::Length(frame.len()));
+    }
+    Ok(GameFrame::State(GameStats {
+        left_y: read_float(frame, LAYOUT.left_y)?,
+        right_y: read_float(frame, LAYOUT.right_y)?,
+        ball_x: read_float(frame, LAYOUT.ball_x)?,
+        ball_y: read_float(frame, LAYOUT.ball_y)?,
+        speed_x: read_float(frame, LAYOUT.speed_x)?,
+        speed_y: read_float(frame, LAYOUT.speed_y)?,
+        player1_score: frame[LAYOUT.player1_score],
+        player2_score: frame[LAYOUT.player2_score],
+        winner: false,
+    }))
+}
+
+/// Decodes a text message of the game server
+pub(crate) fn decode_message(text: &str) -> Result<GameFrame, ProtocolError> {
+    match serde_json::from_str(text) {
+        Ok(Text::Winner { winner }) => Ok(GameFrame::Winner(winner)),
+        Ok(Text::Error { message }) => Ok(GameFrame::Error(message)),
+        Err(_) => Err(ProtocolError::Message(text.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// stateBuffer of GameState.ts: paddles at 40 and 60, ball at (25, 50) moving at
+    /// (0.5, -1), player 1 leading 3-1
+    const STATE: [u8; LAYOUT.len] = [
+        0x00, 0x00, 0x20, 0x42, // left paddle 40
+        0x00, 0x00, 0x70, 0x42, // right paddle 60
+        0x00, 0x00, 0xc8, 0x41, // ball x 25
+        0x00, 0x00, 0x48, 0x42, // ball y 50
+        0x00, 0x00, 0x00, 0x3f, // speed x 0.5
+        0x00, 0x00, 0x80, 0xbf, // speed y -1
+        3, 1,
+    ];
+    /// reversedStateBuffer of the same state, as sent to side 2
+    const REVERSED: [u8; LAYOUT.len] = [
+        0x00, 0x00, 0x70, 0x42, // left paddle 60
+        0x00, 0x00, 0x20, 0x42, // right paddle 40
+        0x00, 0x00, 0x96, 0x42, // ball x 100 - 25
+        0x00, 0x00, 0x48, 0x42, // ball y 50
+        0x00, 0x00, 0x00, 0xbf, // speed x -0.5
+        0x00, 0x00, 0x80, 0xbf, // speed y -1
+        1, 3,
+    ];
+
+    fn fields(frame: GameFrame) -> ([f32; 6], (u8, u8)) {
+        let GameFrame::State(stats) = frame else {
+            panic!("expected a state, got {:?}", frame);
+        };
+        (
+            [
+                stats.left_y,
+                stats.right_y,
+                stats.ball_x,
+                stats.ball_y,
+                stats.speed_x,
+                stats.speed_y,
+            ],
+            (stats.player1_score, stats.player2_score),
+        )
+    }
+
+    #[test]
+    fn layout_matches_game_state() {
+        // StateIndex, the floats are 4 bytes wide and the scores follow them
+        let floats = [
+            LAYOUT.left_y,
+            LAYOUT.right_y,
+            LAYOUT.ball_x,
+            LAYOUT.ball_y,
+            LAYOUT.speed_x,
+            LAYOUT.speed_y,
+        ];
+        assert_eq!(floats, [0, 4, 8, 12, 16, 20]);
+        assert_eq!((LAYOUT.player1_score, LAYOUT.player2_score), (24, 25));
+        assert_eq!((LAYOUT.version, LAYOUT.len), (1, 26));
+    }
+
+    #[test]
+    fn state_frames_follow_the_server_layout() {
+        assert_eq!(
+            fields(decode_state(&STATE).unwrap()),
+            ([40.0, 60.0, 25.0, 50.0, 0.5, -1.0], (3, 1))
+        );
+        assert_eq!(
+            fields(decode_state(&REVERSED).unwrap()),
+            ([60.0, 40.0, 75.0, 50.0, -0.5, -1.0], (1, 3))
+        );
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // a field more or less than this layout version
+        let mut longer = STATE.to_vec();
+        longer.extend_from_slice(&[0; FLOAT_BYTE_SIZE]);
+        assert_eq!(
+            decode_state(&longer).unwrap_err(),
+            ProtocolError::Length(LAYOUT.len + FLOAT_BYTE_SIZE)
+        );
+        assert_eq!(
+            decode_state(&STATE[FLOAT_BYTE_SIZE..]).unwrap_err(),
+            ProtocolError::Length(LAYOUT.len - FLOAT_BYTE_SIZE)
+        );
+        assert_eq!(decode_state(&[]).unwrap_err(), ProtocolError::Length(0));
+        let mut nan = STATE;
+        nan[LAYOUT.ball_x..LAYOUT.ball_y].copy_from_slice(&f32::NAN.to_le_bytes());
+        assert_eq!(
+            decode_state(&nan).unwrap_err(),
+            ProtocolError::Value(LAYOUT.ball_x)
+        );
+    }
+
+    #[test]
+    fn text_messages_are_typed() {
+        assert_eq!(
+            decode_message(r#"{"type":"winner","winner":12}"#),
+            Ok(GameFrame::Winner(12))
+        );
+        assert_eq!(
+            decode_message(r#"{"type":"error","message":"Game not found"}"#),
+            Ok(GameFrame::Error("Game not found".to_string()))
+        );
+        for text in [r#"{"type":"pause"}"#, r#"{"type":"winner"}"#, "12"] {
+            assert!(matches!(
+                decode_message(text),
+                Err(ProtocolError::Message(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::game::GameStats;
+use crate::infos::GameParams;
+use crate::protocol::{self, GameFrame, LAYOUT, STATE_LEN};
+use anyhow::{Context as _, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"PRPL";
+/// Bumped whenever the layout below changes
+const VERSION: u8 = 2;
+/// Magic, version, state layout version and length of the info
+pub(crate) const HEADER_LEN: usize = 10;
+pub(crate) const EXTENSION: &str = "replay";
+
+/// Who played a recorded match and how it ended
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ReplayInfo {
+    pub(crate) game_id: String,
+    pub(crate) mode: String,
+    /// Seconds since the Unix epoch
+    pub(crate) recorded_at: u64,
+    pub(crate) player: String,
+    pub(crate) opponent: String,
+    pub(crate) side: u64,
+    pub(crate) params: GameParams,
+    /// Last score received, left paddle first
+    pub(crate) score: (u8, u8),
+    /// None when the match was left before its end
+    pub(crate) won: Option<bool>,
+}
+
+/// State frames of a match kept as received, written once the match is over
+///
+/// A replay file holds the magic, the version, the version of protocol::LAYOUT its frames follow,
+/// the length of the JSON info and the info, then for every frame the milliseconds since the first one (u32 LE) and the frame itself
+pub(crate) struct Recorder {
+    dir: PathBuf,
+    info: ReplayInfo,
+    start: Option<Instant>,
+    frames: Vec<(u32, [u8; STATE_LEN])>,
+}
+
+impl Recorder {
+    pub(crate) fn new(dir: PathBuf, info: ReplayInfo) -> Self {
+        Recorder {
+            dir,
+            info,
+            start: None,
+            frames: vec![],
+        }
+    }
+    /// Keeps a frame already checked by protocol::decode_state
+    pub(crate) fn record(&mut self, at: Instant, frame: &[u8]) {
+        let Ok(frame) = frame.try_into() else {
+            return;
+        };
+        let start = *self.start.get_or_insert(at);
+        let millis = at.saturating_duration_since(start).as_millis();
+        self.frames
+            .push((millis.min(u32::MAX as u128) as u32, frame));
+    }
+    /// Writes the replay file, nothing is written when no state arrived
+    ///
+    /// #Returns
+    /// The path of the file
+    pub(crate) fn save(mut self, won: Option<bool>) -> Result<Option<PathBuf>> {
+        let Some((_, last)) = self.frames.last() else {
+            return Ok(None);
+        };
+        self.info.score = (last[LAYOUT.player1_score], last[LAYOUT.player2_score]);
+        self.info.won = won;
+        let id: String = self
+            .info
+            .game_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+        let path = self
+            .dir
+            .join(format!("{}-{}.{}", self.info.recorded_at, id, EXTENSION));
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        std::fs::write(&path, self.encode()?)
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(Some(path))
+    }
+    fn encode(&self) -> Result<Vec<u8>> {
+        let info = serde_json::to_vec(&self.info)?;
+        let mut bytes =
+            Vec::with_capacity(HEADER_LEN + info.len() + self.frames.len() * (4 + STATE_LEN));
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(LAYOUT.version);
+        bytes.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&info);
+        for (millis, frame) in &self.frames {
+            bytes.extend_from_slice(&millis.to_le_bytes());
+            bytes.extend_from_slice(frame);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Recorded match, decoded for playback
+pub(crate) struct Replay {
+    pub(crate) info: ReplayInfo,
+    frames: Vec<(Duration, GameStats)>,
+}
+
+impl Replay {
+    pub(crate) fn load(path: &Path) -> Result<Replay> {
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::decode(&bytes).with_context(|| format!("reading {}", path.display()))
+    }
+    fn decode(mut bytes: &[u8]) -> Result<Replay> {
+        let info = read_info(&mut bytes)?;
+        let mut frames = vec![];
+        for record in bytes.chunks(4 + STATE_LEN) {
+            let Some((millis, frame)) = record.split_first_chunk::<4>() else {
+                bail!("truncated frame");
+            };
+            let at = Duration::from_millis(u32::from_le_bytes(*millis) as u64);
+            let GameFrame::State(stats) = protocol::decode_state(frame)? else {
+                bail!("frame without a state");
+            };
+            frames.push((at, stats));
+        }
+        if frames.is_empty() {
+            bail!("no frame recorded");
+        }
+        Ok(Replay { info, frames })
+    }
+    pub(crate) fn duration(&self) -> Duration {
+        self.frames.last().map(|(at, _)| *at).unwrap_or_default()
+    }
+    /// The field as it was `position` into the match
+    pub(crate) fn at(&self, position: Duration) -> GameStats {
+        let next = self.frames.partition_point(|(at, _)| *at <= position);
+        self.frames[next.saturating_sub(1)].1
+    }
+}
+
+/// Reads the info of a replay file, without its frames
+pub(crate) fn info(path: &Path) -> Result<ReplayInfo> {
+    let read = || -> Result<ReplayInfo> {
+        let mut file = std::fs::File::open(path)?;
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let mut info = vec![0; info_len(&header)?];
+        file.read_exact(&mut info)?;
+        Ok(serde_json::from_slice(&info)?)
+    };
+    read().with_context(|| format!("reading {}", path.display()))
+}
+
+/// Replay files of a directory, newest first, a missing directory holds none
+pub(crate) fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+    };
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            paths.push(path);
+        }
+    }
+    // file names start with the recording time
+    paths.sort_by(|a, b| b.cmp(a));
+    Ok(paths)
+}
+
+fn info_len(header: &[u8; HEADER_LEN]) -> Result<usize> {
+    if &header[..4] != MAGIC {
+        bail!("not a replay file");
+    }
+    if header[4] != VERSION {
+        bail!("replay version {} is not supported", header[4]);
+    }
+    if header[5] != LAYOUT.version {
+        bail!("state layout version {} is not supported", header[5]);
+    }
+    Ok(u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize)
+}
+
+fn read_info(bytes: &mut &[u8]) -> Result<ReplayInfo> {
+    let Some((header, rest)) = bytes.split_first_chunk::<HEADER_LEN>() else {
+        bail!("not a replay file");
+    };
+    let length = info_len(header)?;
+    if rest.len() < length {
+        bail!("truncated info");
+    }
+    let (info, rest) = rest.split_at(length);
+    *bytes = rest;
+    Ok(serde_json::from_slice(info)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{state_frame, temp_path};
+
+    fn frame(ball_x: f32, scores: (u8, u8)) -> Vec<u8> {
+        state_frame(50.0, 50.0, ball_x, 50.0, 0.5, 0.0, scores.0, scores.1)
+            .into_data()
+            .to_vec()
+    }
+
+    fn recorder(dir: &Path) -> Recorder {
+        let info = ReplayInfo {
+            game_id: "42/../x".to_string(),
+            mode: "online".to_string(),
+            recorded_at: 1_700_000_000,
+            player: "alice".to_string(),
+            opponent: "bob".to_string(),
+            side: 2,
+            params: GameParams::default(),
+            score: (0, 0),
+            won: None,
+        };
+        Recorder::new(dir.to_path_buf(), info)
+    }
+
+    #[test]
+    fn recorded_matches_play_back() {
+        let dir = temp_path("replays");
+        let start = Instant::now();
+        let mut recorder = recorder(&dir);
+        recorder.record(start, &frame(10.0, (0, 0)));
+        recorder.record(start + Duration::from_millis(500), &frame(20.0, (1, 0)));
+        recorder.record(start + Duration::from_millis(1000), &frame(30.0, (1, 2)));
+        let path = recorder.save(Some(false)).unwrap().unwrap();
+        assert_eq!(path, dir.join("1700000000-42x.replay"));
+        assert_eq!(list(&dir).unwrap(), vec![path.clone()]);
+
+        let info = info(&path).unwrap();
+        assert_eq!((info.score, info.won, info.side), ((1, 2), Some(false), 2));
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!(replay.info, info);
+        assert_eq!(replay.duration(), Duration::from_secs(1));
+        assert_eq!(replay.at(Duration::ZERO).ball_x, 10.0);
+        assert_eq!(replay.at(Duration::from_millis(700)).ball_x, 20.0);
+        assert_eq!(replay.at(Duration::from_secs(9)).player2_score, 2);
+
+        // a file cut in the middle of a frame
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(Replay::decode(&bytes[..bytes.len() - 3]).is_err());
+        assert!(Replay::decode(b"PRPL\x07").is_err());
+        // frames of another state layout
+        let mut other = bytes.clone();
+        other[5] = LAYOUT.version + 1;
+        assert!(Replay::decode(&other).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_saved_without_states() {
+        let dir = temp_path("empty-replays");
+        assert!(recorder(&dir).save(None).unwrap().is_none());
+        assert!(list(&dir).unwrap().is_empty());
+    }
+}
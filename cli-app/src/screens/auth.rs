@@ -0,0 +1,268 @@
+use super::menus::Welcome;
+use super::{Screen, Task, Transition};
+use crate::chat::ChatRoom;
+use crate::infos::Infos;
+use crate::login::{Field, login, signup};
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    prelude::Color,
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+/// Token, user id and chat of a session being opened
+pub(super) type Credentials = (String, u64, ChatRoom);
+
+/// Opens the session once its request answered, the form is cleared on failure
+pub(super) fn logged_in(
+    infos: &Infos,
+    request: &mut Task<Result<Credentials>>,
+) -> Result<Transition> {
+    match request.take() {
+        None => Ok(Transition::Stay),
+        Some(Ok(credentials)) => {
+            infos.authent.borrow_mut().set_credentials(credentials);
+            Ok(Transition::Reset(Box::<Welcome>::default()))
+        }
+        Some(Err(e)) => {
+            infos.authent.borrow_mut().clear();
+            Err(e)
+        }
+    }
+}
+
+/// Bottom line of the forms, the request replaces the keys while it runs
+fn form_keys(
+    request: &Task<Result<Credentials>>,
+    pending: &'static str,
+    keys: &'static str,
+) -> Line<'static> {
+    match request.is_running() {
+        true => pending.bold().yellow().into_centered_line(),
+        false => keys.bold().into_centered_line(),
+    }
+}
+
+/// Sign up form, the fields live in `Auth`
+#[derive(Default)]
+pub(super) struct SignupScreen {
+    request: Task<Result<Credentials>>,
+}
+
+#[async_trait(?Send)]
+impl Screen for SignupScreen {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let authent = infos.authent.borrow();
+        let mail = format!(
+            "{}{}",
+            authent.email,
+            if authent.blinks(Field::Mail) { "|" } else { "" }
+        );
+        let username = format!(
+            "{}{}",
+            authent.username,
+            if authent.blinks(Field::Username) {
+                "|"
+            } else {
+                ""
+            }
+        );
+        let mut password = "*".repeat(authent.password.len());
+        if authent.blinks(Field::Password) {
+            password.push('|')
+        }
+        let content = vec![
+            Line::from(Span::styled(
+                "Create an account",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Email:     ", Style::default().fg(Color::Gray)),
+                Span::raw(mail),
+            ]),
+            Line::from(vec![
+                Span::styled("Username:  ", Style::default().fg(Color::Gray)),
+                Span::raw(username),
+            ]),
+            Line::from(vec![
+                Span::styled("Password:  ", Style::default().fg(Color::Gray)),
+                Span::raw(password),
+            ]),
+        ];
+        Paragraph::new(content)
+            .block(
+                Block::default()
+                    .title("Signup")
+                    .borders(Borders::ALL)
+                    .title_bottom(form_keys(
+                        &self.request,
+                        "Signing up…",
+                        "Menu: ESC. Quit  Enter. Ok",
+                    )),
+            )
+            .alignment(Alignment::Left)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            infos.authent.borrow_mut().clear();
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(eventkey) = event else {
+            return Ok(Transition::Stay);
+        };
+        if self.request.is_running() {
+            return Ok(Transition::Stay);
+        }
+        let submitted = {
+            let mut authent = infos.authent.borrow_mut();
+            match eventkey.code {
+                KeyCode::Enter if authent.field == Field::Password => {
+                    Some(authent.get_signup_infos())
+                }
+                KeyCode::Up => {
+                    authent.up_field_signup();
+                    None
+                }
+                KeyCode::Down | KeyCode::Tab | KeyCode::Enter => {
+                    authent.down_field_signup();
+                    None
+                }
+                KeyCode::Char(c) => {
+                    authent.add(c);
+                    None
+                }
+                KeyCode::Backspace => {
+                    authent.pop();
+                    None
+                }
+                _ => None,
+            }
+        };
+        let Some(form) = submitted else {
+            return Ok(Transition::Stay);
+        };
+        self.request.start(signup(infos.context.clone(), form));
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.request.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.authent.borrow_mut().tick();
+        logged_in(infos, &mut self.request)
+    }
+}
+
+/// Login form, the fields live in `Auth`
+#[derive(Default)]
+pub(super) struct LoginScreen {
+    request: Task<Result<Credentials>>,
+}
+
+#[async_trait(?Send)]
+impl Screen for LoginScreen {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let authent = infos.authent.borrow();
+        let mail = format!(
+            "{}{}",
+            authent.email,
+            if authent.blinks(Field::Mail) { "|" } else { "" }
+        );
+        let mut password = "*".repeat(authent.password.len());
+        if authent.blinks(Field::Password) {
+            password.push('|')
+        }
+        let totp = format!(
+            "{}{}",
+            authent.totp,
+            if authent.blinks(Field::Totp) { "|" } else { "" }
+        );
+        let content = vec![
+            Line::from(Span::styled(
+                "Login as user",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Email:     ", Style::default().fg(Color::Gray)),
+                Span::raw(mail),
+            ]),
+            Line::from(vec![
+                Span::styled("Password:  ", Style::default().fg(Color::Gray)),
+                Span::raw(password),
+            ]),
+            Line::from(vec![
+                Span::styled("2FA Code:  ", Style::default().fg(Color::Gray)),
+                Span::raw(totp),
+            ]),
+        ];
+        Paragraph::new(content)
+            .block(
+                Block::default()
+                    .title("Login".bold())
+                    .borders(Borders::ALL)
+                    .title_bottom(form_keys(
+                        &self.request,
+                        "Logging in…",
+                        "Menu: Enter. Ok  ESC. Quit",
+                    )),
+            )
+            .alignment(Alignment::Left)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            infos.authent.borrow_mut().clear();
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(eventkey) = event else {
+            return Ok(Transition::Stay);
+        };
+        if self.request.is_running() {
+            return Ok(Transition::Stay);
+        }
+        let submitted = {
+            let mut authent = infos.authent.borrow_mut();
+            match eventkey.code {
+                KeyCode::Enter if authent.field == Field::Totp => Some(authent.get_login_infos()),
+                KeyCode::Up => {
+                    authent.up_field_login();
+                    None
+                }
+                KeyCode::Down | KeyCode::Tab | KeyCode::Enter => {
+                    authent.down_field_login();
+                    None
+                }
+                KeyCode::Char(c) => {
+                    authent.add(c);
+                    None
+                }
+                KeyCode::Backspace => {
+                    authent.pop();
+                    None
+                }
+                _ => None,
+            }
+        };
+        let Some(form) = submitted else {
+            return Ok(Transition::Stay);
+        };
+        self.request.start(login(infos.context.clone(), form));
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.request.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.authent.borrow_mut().tick();
+        logged_in(infos, &mut self.request)
+    }
+}
@@ -0,0 +1,205 @@
+use crate::tournament::{Bracket, Pairing};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget},
+};
+
+/// Narrowest column that still shows a name and its score
+const MIN_COLUMN: u16 = 14;
+/// Width of the score written after each name
+const SCORE_WIDTH: usize = 3;
+
+/// Rounds side by side, each pairing halfway between the two it comes from
+///
+/// Falls back to a list, round by round, when the terminal cannot fit the first round
+pub(super) struct BracketView<'a> {
+    pub(super) bracket: &'a Bracket,
+    /// Highlighted wherever we appear
+    pub(super) me: &'a str,
+    /// Shown after the final once known
+    pub(super) champion: Option<&'a str>,
+}
+
+fn round_name(pairings: usize) -> String {
+    match pairings {
+        1 => "Final".to_string(),
+        2 => "Semi-finals".to_string(),
+        4 => "Quarter-finals".to_string(),
+        _ => format!("Round of {}", pairings * 2),
+    }
+}
+
+impl BracketView<'_> {
+    fn style(&self, pairing: &Pairing, side: usize) -> Style {
+        let name = pairing.players[side].as_deref();
+        let style = match pairing.winner {
+            Some(winner) if winner == side => Style::new().fg(Color::Green).bold(),
+            Some(_) => Style::new().fg(Color::DarkGray),
+            None if name.is_none() => Style::new().fg(Color::Gray),
+            None => Style::new(),
+        };
+        match name == Some(self.me) {
+            true => style.add_modifier(Modifier::UNDERLINED).fg(Color::Yellow),
+            false => style,
+        }
+    }
+    /// One side of a pairing: the name, then its score once played
+    fn seat(&self, pairing: &Pairing, side: usize, width: usize) -> Span<'static> {
+        let name = pairing.players[side].as_deref().unwrap_or("?");
+        let score = match pairing.score {
+            Some((left, right)) => [left, right][side].to_string(),
+            None => String::new(),
+        };
+        let room = width.saturating_sub(SCORE_WIDTH + 1);
+        let name: String = name.chars().take(room).collect();
+        Span::styled(
+            format!("{:<room$} {:>SCORE_WIDTH$}", name, score),
+            self.style(pairing, side),
+        )
+    }
+    fn render_tree(&self, area: Rect, buf: &mut Buffer) {
+        let rounds = &self.bracket.rounds;
+        let width = area.width / (rounds.len() as u16 + 1);
+        let top = area.y + 1;
+        let body = area.height.saturating_sub(1) as usize;
+        for (index, round) in rounds.iter().enumerate() {
+            let x = area.x + index as u16 * width;
+            buf.set_string(x, area.y, round_name(round.len()), Style::new().bold());
+            let share = body / round.len().max(1);
+            for (i, pairing) in round.iter().enumerate() {
+                let y = top + (i * share + share / 2).saturating_sub(1) as u16;
+                for side in 0..2 {
+                    let seat = self.seat(pairing, side, width.saturating_sub(2) as usize);
+                    buf.set_span(x, y + side as u16, &seat, width.saturating_sub(2));
+                }
+                let edge = x + width.saturating_sub(2);
+                buf.set_string(edge, y, "┐", Style::new().fg(Color::Gray));
+                buf.set_string(edge, y + 1, "┘", Style::new().fg(Color::Gray));
+            }
+        }
+        let x = area.x + rounds.len() as u16 * width;
+        buf.set_string(x, area.y, "Champion", Style::new().bold());
+        if let Some(champion) = self.champion {
+            let y = top + (body / 2).saturating_sub(1) as u16;
+            let style = Style::new().fg(Color::Yellow).bold();
+            buf.set_stringn(x, y, format!("★ {}", champion), width as usize, style);
+        }
+    }
+    fn render_list(&self, area: Rect, buf: &mut Buffer) {
+        let mut lines = vec![];
+        for round in &self.bracket.rounds {
+            lines.push(Line::from(round_name(round.len()).bold()));
+            for pairing in round {
+                let name = |side: usize| {
+                    Span::styled(
+                        pairing.players[side].clone().unwrap_or("?".to_string()),
+                        self.style(pairing, side),
+                    )
+                };
+                let middle = match pairing.score {
+                    Some((left, right)) => format!(" {} - {} ", left, right),
+                    None => " vs ".to_string(),
+                };
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    name(0),
+                    Span::raw(middle),
+                    name(1),
+                ]));
+            }
+        }
+        if let Some(champion) = self.champion {
+            lines.push(Line::from(vec![
+                "Champion: ".bold(),
+                champion.to_string().fg(Color::Yellow).bold(),
+            ]));
+        }
+        Paragraph::new(lines).render(area, buf);
+    }
+}
+
+impl Widget for BracketView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rounds = &self.bracket.rounds;
+        let first = rounds.first().map_or(0, Vec::len);
+        let columns = rounds.len() as u16 + 1;
+        // two rows per pairing of the first round, under the round names
+        if area.width < columns * MIN_COLUMN || (area.height as usize) < first * 2 + 1 {
+            self.render_list(area, buf);
+        } else {
+            self.render_tree(area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bracket() -> Bracket {
+        let played = Pairing {
+            players: [Some("alice".to_string()), Some("bob".to_string())],
+            score: Some((5, 2)),
+            winner: Some(0),
+        };
+        Bracket {
+            rounds: vec![vec![played, Pairing::default()], vec![Pairing::default()]],
+        }
+    }
+
+    fn text(buf: &Buffer) -> Vec<String> {
+        (0..buf.area.height)
+            .map(|y| {
+                (0..buf.area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wide_terminals_get_the_tree() {
+        let bracket = bracket();
+        let area = Rect::new(0, 0, 60, 9);
+        let mut buf = Buffer::empty(area);
+        BracketView {
+            bracket: &bracket,
+            me: "bob",
+            champion: None,
+        }
+        .render(area, &mut buf);
+        let rows = text(&buf);
+        assert!(rows[0].starts_with("Semi-finals"));
+        assert!(rows[0].contains("Final") && rows[0].contains("Champion"));
+        assert!(rows[2].starts_with("alice") && rows[2].contains("5┐"));
+        assert!(rows[3].starts_with("bob") && rows[3].contains("2┘"));
+        assert!(rows[6].starts_with("?"));
+        // the final sits between the two semi-finals
+        assert!(rows[4].contains("?"));
+        assert_eq!(buf[(0, 3)].fg, Color::Yellow);
+    }
+
+    #[test]
+    fn narrow_terminals_get_a_list() {
+        let bracket = bracket();
+        let area = Rect::new(0, 0, 30, 9);
+        let mut buf = Buffer::empty(area);
+        BracketView {
+            bracket: &bracket,
+            me: "bob",
+            champion: Some("alice"),
+        }
+        .render(area, &mut buf);
+        let rows = text(&buf);
+        assert_eq!(rows[0], "Semi-finals");
+        assert_eq!(rows[1], "  alice 5 - 2 bob");
+        assert_eq!(rows[2], "  ? vs ?");
+        assert_eq!(rows[3], "Final");
+        assert_eq!(rows[5], "Champion: alice");
+    }
+}
@@ -0,0 +1,169 @@
+use super::{Screen, Task, Transition};
+use crate::chain::{self, ChainResult, Check, Ranking};
+use crate::infos::Infos;
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget, Wrap},
+};
+
+/// Finished tournaments as recorded on the blockchain, their winners and how they rank
+#[derive(Default)]
+pub(super) struct ChainResults {
+    results: Vec<ChainResult>,
+    rankings: Vec<Ranking>,
+    selected: usize,
+    /// Checks of a result against the match history, by result number
+    checks: Option<(usize, Vec<Check>)>,
+    verifying: Task<Result<(usize, Vec<Check>)>>,
+}
+
+impl ChainResults {
+    fn render_details(&self, result: &ChainResult, area: Rect, buf: &mut Buffer) {
+        let mut lines = vec![
+            Line::from(format!("Contract {}", result.address)),
+            Line::from(result.explorer().fg(Color::Cyan)),
+        ];
+        match &self.checks {
+            Some((number, checks)) if *number == result.number => {
+                for check in checks {
+                    lines.push(match check.passed {
+                        true => Line::from(format!("✓ {}", check.detail).fg(Color::Green)),
+                        false => Line::from(format!("✗ {}", check.detail).fg(Color::Red)),
+                    });
+                }
+            }
+            _ if self.verifying.is_running() => {
+                lines.push(Line::from("Checking the match history…".fg(Color::Yellow)))
+            }
+            _ => lines.push(Line::from("Enter to check it against the match history")),
+        }
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(format!(" Tournament {} ", result.number)))
+            .render(area, buf);
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for ChainResults {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " ↑ ↓ Select ".bold(),
+            " Enter. Verify ".bold(),
+            " ESC. Back ".bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from("Blockchain results").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let Some(result) = self.results.get(self.selected) else {
+            Paragraph::new("No tournament recorded on the blockchain yet")
+                .centered()
+                .render(inner, buf);
+            return;
+        };
+        let [top, details] = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(7)])
+            .areas(inner);
+        let [list, ranks] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .areas(top);
+
+        let height = list.height.saturating_sub(2) as usize;
+        let skip = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = self
+            .results
+            .iter()
+            .skip(skip)
+            .map(|result| {
+                let line = Line::from(format!(
+                    "{:>3}. won by {}",
+                    result.number,
+                    result.winner_name()
+                ));
+                match result.number == self.results[self.selected].number {
+                    true => line.reversed(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .block(Block::bordered().title(" Tournaments "))
+            .render(list, buf);
+
+        let lines: Vec<Line> = self
+            .rankings
+            .iter()
+            .map(|ranking| {
+                let line = Line::from(format!(
+                    "{:>3}. {} ({})",
+                    ranking.rank, ranking.winner, ranking.titles
+                ));
+                match ranking.winner == result.winner_name() {
+                    true => line.fg(Color::Yellow).bold(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .block(Block::bordered().title(" Titles "))
+            .render(ranks, buf);
+
+        self.render_details(result, details, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press || self.verifying.is_running() {
+            return Ok(Transition::Stay);
+        }
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.results.len() => self.selected += 1,
+            KeyCode::Enter => {
+                if let Some(result) = self.results.get(self.selected) {
+                    let (api, results, result) =
+                        (infos.api(), self.results.clone(), result.clone());
+                    self.verifying.start(async move {
+                        let checks = chain::verify(&api, &results, &result).await?;
+                        Ok((result.number, checks))
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.verifying.wait().await
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        if let Some(checks) = self.verifying.take() {
+            self.checks = Some(checks?);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.results = chain::results(&infos.api()).await?;
+        self.rankings = chain::rankings(&self.results);
+        // the latest tournament
+        self.selected = self.results.len().saturating_sub(1);
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use super::{Screen, Transition};
+use crate::chat::{ChatLine, ChatLog, ChatStatus, LineKind, MAX_MESSAGE_LEN, Outgoing};
+use crate::infos::Infos;
+use crate::utils::{Blink, should_exit};
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Widget},
+};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use unicode_width::UnicodeWidthChar;
+
+/// Same palette idea as the web chat, a name always gets the same color
+const NAME_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Blue,
+    Color::Green,
+    Color::White,
+    Color::Magenta,
+    Color::Yellow,
+];
+
+/// Global chat room or a direct conversation: scrollback above, input line below
+#[derive(Default)]
+pub(super) struct ChatScreen {
+    /// The user of a direct conversation, the global room otherwise
+    with: Option<String>,
+    input: String,
+    /// Rows scrolled up from the newest message
+    scroll: usize,
+    /// Our name, to highlight the messages mentioning us
+    me: String,
+    /// Lines of the log already shown, new ones shift the view when scrolled up
+    seen: u64,
+    /// Why the last message was not sent
+    notice: Option<String>,
+    /// Rows available at the last render
+    height: Cell<usize>,
+    blink: Blink,
+}
+
+fn name_color(name: &str) -> Color {
+    let total: usize = name.chars().map(|c| c as usize).sum();
+    NAME_COLORS[total % NAME_COLORS.len()]
+}
+
+/// Whether `name` appears as a whole word, ignoring case and a leading `@`
+fn mentions(text: &str, name: &str) -> bool {
+    !name.is_empty()
+        && text
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .any(|word| word.eq_ignore_ascii_case(name))
+}
+
+impl ChatScreen {
+    pub(super) fn direct(with: String) -> Self {
+        ChatScreen {
+            with: Some(with),
+            ..ChatScreen::default()
+        }
+    }
+    /// Lines of this room and how many were ever received
+    fn lines<'l>(&self, log: &'l ChatLog) -> (Option<&'l VecDeque<ChatLine>>, u64) {
+        match &self.with {
+            None => (Some(&log.lines), log.received),
+            Some(with) => match log.conversation(with) {
+                Some(conversation) => (Some(&conversation.lines), conversation.received),
+                None => (None, 0),
+            },
+        }
+    }
+    fn spans(&self, line: &ChatLine) -> Vec<Span<'static>> {
+        match line.kind {
+            LineKind::Server => vec![Span::styled(
+                format!("-- {}", line.text),
+                Style::new().fg(Color::Cyan).italic(),
+            )],
+            LineKind::Own => vec![
+                Span::styled(format!("{}: ", self.me), Style::new().bold()),
+                Span::styled(line.text.clone(), Style::new().fg(Color::Gray)),
+            ],
+            LineKind::Player => {
+                // everything is meant for us in a direct conversation
+                let text = match self.with.is_none() && mentions(&line.text, &self.me) {
+                    true => Style::new().fg(Color::Black).bg(Color::Yellow).bold(),
+                    false => Style::new(),
+                };
+                vec![
+                    Span::styled(
+                        format!("{}: ", line.author),
+                        Style::new().fg(name_color(&line.author)).bold(),
+                    ),
+                    Span::styled(line.text.clone(), text),
+                ]
+            }
+        }
+    }
+}
+
+/// Splits styled text into rows of at most `width` columns
+fn wrap(spans: Vec<Span<'static>>, width: usize) -> Vec<Line<'static>> {
+    let mut rows: Vec<Line> = vec![];
+    let mut row: Vec<Span> = vec![];
+    let mut used = 0;
+    for span in spans {
+        let mut chunk = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && used > 0 {
+                row.push(Span::styled(std::mem::take(&mut chunk), span.style));
+                rows.push(Line::from(std::mem::take(&mut row)));
+                used = 0;
+            }
+            chunk.push(c);
+            used += w;
+        }
+        if !chunk.is_empty() {
+            row.push(Span::styled(chunk, span.style));
+        }
+    }
+    rows.push(Line::from(row));
+    rows
+}
+
+#[async_trait(?Send)]
+impl Screen for ChatScreen {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(3)])
+            .split(area);
+        let authent = infos.authent.borrow();
+        let Some(log) = authent.chat_log.as_ref() else {
+            return;
+        };
+        let log = log.borrow();
+        let messages = match &self.with {
+            None => Block::bordered()
+                .title(Line::from(" Chat ").bold())
+                .title(Line::from(format!(" {} online ", log.connections)).right_aligned()),
+            Some(with) => Block::bordered().title(Line::from(format!(" {} ", with)).bold()),
+        }
+        .border_set(border::THICK);
+        let inner = messages.inner(layout[0]);
+        let (width, height) = (inner.width as usize, inner.height as usize);
+        self.height.set(height);
+        // only the rows on screen are wrapped, from the newest line up
+        let mut rows: Vec<Line> = vec![];
+        let (lines, _) = self.lines(&log);
+        for line in lines.into_iter().flatten().rev() {
+            if rows.len() >= height + self.scroll {
+                break;
+            }
+            let mut wrapped = wrap(self.spans(line), width.max(1));
+            wrapped.append(&mut rows);
+            rows = wrapped;
+        }
+        let end = rows.len().saturating_sub(self.scroll.min(rows.len()));
+        let start = end.saturating_sub(height);
+        Paragraph::new(rows[start..end].to_vec())
+            .block(messages)
+            .render(layout[0], buf);
+
+        let cursor = if self.blink.on() { "|" } else { "" };
+        let shown = inner.width.saturating_sub(2) as usize;
+        let skip = self.input.chars().count().saturating_sub(shown);
+        let input: String = self.input.chars().skip(skip).collect();
+        let mut block = Block::bordered()
+            .title(Line::from(" Message ").bold())
+            .title_bottom(
+                " Send: Enter  Scroll: ↑ ↓ PgUp PgDn End  ESC. Back "
+                    .bold()
+                    .into_centered_line(),
+            );
+        if let Some(notice) = &self.notice {
+            block = block.title(Line::from(format!(" {} ", notice).red()).right_aligned());
+        }
+        Paragraph::new(format!("{}{}", input, cursor))
+            .block(block)
+            .render(layout[1], buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind == KeyEventKind::Release {
+            return Ok(Transition::Stay);
+        }
+        let page = self.height.get().max(1);
+        match key.code {
+            KeyCode::Char(c) if self.input.chars().count() < MAX_MESSAGE_LEN => {
+                self.input.push(c);
+                self.notice = None;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Up => self.scroll += 1,
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll += page,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::End => self.scroll = 0,
+            KeyCode::Enter => self.send(infos),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        self.blink.tick();
+        let authent = infos.authent.borrow();
+        let Some(log) = authent.chat_log.as_ref() else {
+            return Ok(Transition::Stay);
+        };
+        let log = log.borrow();
+        let (lines, received) = self.lines(&log);
+        if self.scroll > 0 {
+            self.scroll += received.saturating_sub(self.seen) as usize;
+        }
+        // never further up than the oldest line
+        self.scroll = self.scroll.min(lines.map_or(0, |lines| lines.len() * 2));
+        self.seen = received;
+        if let Some(with) = &self.with
+            && log.conversation(with).is_some_and(|c| c.unread > 0)
+        {
+            authent.send_chat(Outgoing::Read(with.clone()));
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        if self.me.is_empty() {
+            self.me = infos.api().profile_from_token().await?.name;
+        }
+        Ok(())
+    }
+}
+
+impl ChatScreen {
+    fn send(&mut self, infos: &Infos) {
+        let message = self.input.trim();
+        if message.is_empty() {
+            return;
+        }
+        let authent = infos.authent.borrow();
+        let connected = authent
+            .chat_status
+            .as_ref()
+            .is_some_and(|status| *status.borrow() == ChatStatus::Connected);
+        let outgoing = match &self.with {
+            None => Outgoing::Global(message.to_string()),
+            Some(with) => Outgoing::Direct {
+                to: with.clone(),
+                text: message.to_string(),
+            },
+        };
+        let sent = connected && authent.send_chat(outgoing);
+        if sent {
+            self.input.clear();
+            self.scroll = 0;
+            self.notice = None;
+        } else {
+            self.notice = Some("Not connected, message kept".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_match_whole_names_only() {
+        assert!(mentions("hi @Alice!", "alice"));
+        assert!(mentions("alice: gg", "alice"));
+        assert!(!mentions("malice", "alice"));
+        assert!(!mentions("anything", ""));
+    }
+
+    #[test]
+    fn wrap_splits_on_columns_and_keeps_styles() {
+        let spans = vec![
+            Span::styled("bob: ", Style::new().bold()),
+            Span::raw("hello world"),
+        ];
+        let rows = wrap(spans, 8);
+        let text: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+        assert_eq!(text, vec!["bob: hel", "lo world"]);
+        assert_eq!(rows[0].spans[0].style, Style::new().bold());
+        assert_eq!(wrap(vec![Span::raw("")], 8).len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+use super::chat::ChatScreen;
+use super::{Screen, Task, Transition};
+use crate::api::Profile;
+use crate::dm::{blocked_names, recipient};
+use crate::friends::get_friends;
+use crate::infos::Infos;
+use crate::utils::{Blink, should_exit};
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Widget},
+};
+use std::cell::Cell;
+
+/// Looks the recipient up, the conversation opens once the server answered
+fn look_up(infos: &Infos, lookup: &mut Task<Result<Profile>>, name: String) {
+    let (api, me) = (infos.api(), infos.authent.borrow().id);
+    lookup.start(async move { recipient(&api, me, &name).await });
+}
+
+/// Conversations, most recent first, then the friends not written to yet
+#[derive(Default)]
+pub(super) struct Conversations {
+    friends: Vec<String>,
+    /// Hidden from the list, the server drops their messages anyway
+    blocked: Vec<String>,
+    selected: usize,
+    /// Lines available at the last render, to keep the selection visible
+    height: Cell<usize>,
+    opening: Task<Result<Profile>>,
+}
+
+impl Conversations {
+    /// Names to show with their unread messages
+    fn entries(&self, infos: &Infos) -> Vec<(String, usize)> {
+        let authent = infos.authent.borrow();
+        let mut entries: Vec<(String, usize)> = match authent.chat_log.as_ref() {
+            Some(log) => log
+                .borrow()
+                .conversations
+                .iter()
+                .map(|c| (c.with.clone(), c.unread))
+                .collect(),
+            None => vec![],
+        };
+        for friend in &self.friends {
+            if !entries.iter().any(|(name, _)| name == friend) {
+                entries.push((friend.clone(), 0));
+            }
+        }
+        entries.retain(|(name, _)| !self.blocked.contains(name));
+        entries
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Conversations {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = match self.opening.is_running() {
+            true => Line::from(" Opening the conversation… ".bold().yellow()),
+            false => Line::from(vec![
+                " ↑ ↓ Select ".bold(),
+                " Enter. Open ".bold(),
+                " → Write to... ".bold(),
+                " ESC. Back ".bold(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(Line::from("Messages").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        let height = area.height.saturating_sub(2) as usize;
+        self.height.set(height);
+        let entries = self.entries(infos);
+        let skip = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = entries
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(height)
+            .map(|(index, (name, unread))| {
+                let mut spans = vec![Span::raw(name.clone()).bold()];
+                if *unread > 0 {
+                    spans.push(Span::styled(
+                        format!(" ({})", unread),
+                        Style::new().fg(Color::Yellow).bold(),
+                    ));
+                }
+                match index == self.selected {
+                    true => Line::from(spans).reversed(),
+                    false => Line::from(spans),
+                }
+            })
+            .collect();
+        if lines.is_empty() {
+            Paragraph::new("No conversation yet, → to write to someone")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press || self.opening.is_running() {
+            return Ok(Transition::Stay);
+        }
+        let entries = self.entries(infos);
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < entries.len() => self.selected += 1,
+            KeyCode::Right => return Ok(Transition::Push(Box::new(RecipientForm::default()))),
+            KeyCode::Enter => {
+                if let Some((name, _)) = entries.get(self.selected) {
+                    look_up(infos, &mut self.opening, name.clone());
+                }
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.opening.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        // a new conversation may have pushed the others down
+        self.selected = self
+            .selected
+            .min(self.entries(infos).len().saturating_sub(1));
+        Ok(match self.opening.take() {
+            Some(profile) => Transition::Push(Box::new(ChatScreen::direct(profile?.name))),
+            None => Transition::Stay,
+        })
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        let me = infos.authent.borrow().id;
+        let api = infos.api();
+        self.blocked = blocked_names(&api, me).await?;
+        self.friends = get_friends(&api, me)
+            .await?
+            .into_iter()
+            .filter(|friend| !friend.pending)
+            .map(|friend| friend.name)
+            .collect();
+        Ok(())
+    }
+}
+
+/// Asks for the name of the user to write to
+#[derive(Default)]
+struct RecipientForm {
+    name: String,
+    blink: Blink,
+    lookup: Task<Result<Profile>>,
+}
+
+#[async_trait(?Send)]
+impl Screen for RecipientForm {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let name = format!("{}{}", self.name, if self.blink.on() { "|" } else { "" });
+        let content = vec![
+            Line::from("Write to".bold()),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Username:   ", Style::default().fg(Color::Gray)),
+                Span::raw(name),
+            ]),
+        ];
+        Paragraph::new(content)
+            .block(
+                Block::bordered()
+                    .title("New conversation".bold())
+                    .title_bottom(match self.lookup.is_running() {
+                        true => "Looking the user up…".bold().yellow().into_centered_line(),
+                        false => "Menu: Enter. Ok  ESC. Quit ".bold().into_centered_line(),
+                    }),
+            )
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        if let Event::Key(key) = event
+            && key.kind == KeyEventKind::Press
+            && !self.lookup.is_running()
+        {
+            match key.code {
+                KeyCode::Backspace => {
+                    self.name.pop();
+                }
+                KeyCode::Char(c) => self.name.push(c),
+                KeyCode::Enter => {
+                    let name = std::mem::take(&mut self.name);
+                    look_up(infos, &mut self.lookup, name);
+                }
+                _ => {}
+            }
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.lookup.wait().await
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        self.blink.tick();
+        Ok(match self.lookup.take() {
+            Some(profile) => Transition::Replace(Box::new(ChatScreen::direct(profile?.name))),
+            None => Transition::Stay,
+        })
+    }
+}
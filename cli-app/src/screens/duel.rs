@@ -0,0 +1,312 @@
+use super::friends::{FriendAction, FriendForm};
+use super::game::start_match;
+use super::{Screen, Task, Transition, received};
+use crate::api::{MatchStart, Profile};
+use crate::chat::ChatStatus;
+use crate::duel::{PendingDuel, accept, pending_duels};
+use crate::infos::{GameParams, Infos};
+use crate::utils::should_exit;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Pending invitations: the received ones are accepted or declined, the sent ones cancelled
+#[derive(Default)]
+pub(super) struct Duels {
+    duels: Vec<PendingDuel>,
+    selected: usize,
+    /// Chat lines received at the last refresh, invitations arrive as server notices
+    seen: u64,
+    fetching: Task<Result<Vec<PendingDuel>>>,
+    /// Name of the challenger once the server took our answer
+    accepting: Task<Result<String>>,
+}
+
+impl Duels {
+    /// Declines the duel first when there is one, the list is fetched again after
+    fn refresh(&mut self, infos: &Infos, decline: Option<u64>) {
+        let (api, me) = (infos.api(), infos.authent.borrow().id);
+        self.fetching.start(async move {
+            if let Some(id) = decline {
+                api.decline_duel(id).await?;
+            }
+            pending_duels(&api, me).await
+        });
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Duels {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = match self.accepting.is_running() {
+            true => Line::from(" Accepting the duel… ".bold().yellow()),
+            false => Line::from(vec![
+                " ↑ ↓ Select ".bold(),
+                " Enter. Accept ".bold(),
+                " Del. Decline ".bold(),
+                " → Challenge ".bold(),
+                " ESC. Back ".bold(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(Line::from("Duels").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        if self.duels.is_empty() {
+            Paragraph::new("No pending duel, → to challenge someone")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        let height = area.height.saturating_sub(2) as usize;
+        let skip = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = self
+            .duels
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, duel)| {
+                let line = match duel.incoming {
+                    true => Line::from(format!("{} challenges you", duel.name).bold()),
+                    false => {
+                        Line::from(format!("{} has not answered yet", duel.name).fg(Color::Gray))
+                    }
+                };
+                match index == self.selected {
+                    true => line.reversed(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press || self.accepting.is_running() {
+            return Ok(Transition::Stay);
+        }
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.duels.len() => self.selected += 1,
+            KeyCode::Right => {
+                return Ok(Transition::Push(Box::new(FriendForm::new(
+                    FriendAction::Challenge,
+                ))));
+            }
+            KeyCode::Enter => {
+                if let Some(duel) = self.duels.get(self.selected)
+                    && duel.incoming
+                {
+                    let (api, id, name) = (infos.api(), duel.id, duel.name.clone());
+                    self.accepting.start(async move {
+                        accept(&api, id).await?;
+                        Ok(name)
+                    });
+                }
+            }
+            KeyCode::Delete | KeyCode::Backspace => {
+                if let Some(duel) = self.duels.get(self.selected) {
+                    let id = duel.id;
+                    self.refresh(infos, Some(id));
+                }
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        tokio::select! {
+            _ = self.fetching.wait() => {}
+            _ = self.accepting.wait() => {}
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        if let Some(accepted) = self.accepting.take() {
+            return Ok(Transition::Push(Box::new(DuelWaiting::accepted(accepted?))));
+        }
+        if let Some(duels) = self.fetching.take() {
+            self.duels = duels?;
+            self.selected = self.selected.min(self.duels.len().saturating_sub(1));
+        }
+        let received = received(infos);
+        if received != self.seen {
+            self.seen = received;
+            self.refresh(infos, None);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.seen = received(infos);
+        self.refresh(infos, None);
+        Ok(())
+    }
+}
+
+/// Waits for the match of a duel, either for the invited player to answer or for the
+/// server to announce the duel we accepted
+pub(super) struct DuelWaiting {
+    opponent: String,
+    /// Id of the invited player while the invitation is open, leaving cancels it
+    invited: Option<u64>,
+    declined: bool,
+    /// Borrowed from `Auth` while the screen is active
+    receiver: Option<mpsc::Receiver<MatchStart>>,
+    start: Option<MatchStart>,
+    /// Chat lines already checked for the decline notice
+    seen: u64,
+    connecting: Task<Result<Box<dyn Screen>>>,
+}
+
+impl DuelWaiting {
+    pub(super) fn invited(opponent: Profile) -> Self {
+        DuelWaiting {
+            opponent: opponent.name,
+            invited: Some(opponent.id),
+            declined: false,
+            receiver: None,
+            start: None,
+            seen: 0,
+            connecting: Task::default(),
+        }
+    }
+    fn accepted(opponent: String) -> Self {
+        DuelWaiting {
+            opponent,
+            invited: None,
+            declined: false,
+            receiver: None,
+            start: None,
+            seen: 0,
+            connecting: Task::default(),
+        }
+    }
+    /// Looks for the notice the server sends when our invitation is declined
+    fn check_declined(&mut self, infos: &Infos) {
+        let authent = infos.authent.borrow();
+        let Some(log) = authent.chat_log.as_ref() else {
+            return;
+        };
+        let log = log.borrow();
+        let declined = log.since(self.seen).any(|line| {
+            line.flag.as_deref() == Some("duelDeclined") && line.text.starts_with(&self.opponent)
+        });
+        self.seen = log.received;
+        if declined && self.invited.is_some() {
+            self.invited = None;
+            self.declined = true;
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for DuelWaiting {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title_bottom("Menu: ESC. Back".bold().into_centered_line())
+            .border_set(border::THICK);
+        let text = match (self.declined, self.invited) {
+            (true, _) => format!("{} declined the duel", self.opponent),
+            (false, Some(_)) => format!("Waiting for {} to accept the duel", self.opponent),
+            (false, None) => format!("Starting the duel against {}", self.opponent),
+        };
+        Paragraph::new(Line::from(text.bold()))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        let Some(receiver) = self.receiver.as_mut() else {
+            return self.connecting.wait().await;
+        };
+        tokio::select! {
+            start = receiver.recv() => match start {
+                Some(start) => self.start = Some(start),
+                None => self.receiver = None,
+            },
+            _ = self.connecting.wait() => {}
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        let status = infos
+            .authent
+            .borrow()
+            .chat_status
+            .as_ref()
+            .map(|s| s.borrow().clone());
+        match status {
+            Some(ChatStatus::Rejected) => return Err(anyhow!("Session expired, log in again")),
+            // the server cancels the duels of the players it disconnects
+            Some(ChatStatus::Reconnecting { .. }) if !self.declined => {
+                self.invited = None;
+                return Err(anyhow!("Chat connection lost, the duel was cancelled"));
+            }
+            _ => {}
+        }
+        if let Some(screen) = self.connecting.take() {
+            return Ok(Transition::Replace(screen?));
+        }
+        self.check_declined(infos);
+        let Some(start) = self.start.take() else {
+            if self.receiver.is_none() && !self.connecting.is_running() {
+                return Err(anyhow!("Chat connection closed"));
+            }
+            return Ok(Transition::Stay);
+        };
+        self.invited = None;
+        let infos = infos.handle();
+        self.connecting
+            .start(async move { start_match(&infos, start, GameParams::default()).await });
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.seen = received(infos);
+        self.receiver = Some(
+            infos
+                .authent
+                .borrow_mut()
+                .receiver
+                .take()
+                .ok_or_else(|| anyhow!("receiver not initialized"))?,
+        );
+        Ok(())
+    }
+    async fn on_exit(&mut self, infos: &mut Infos) {
+        if let Some(id) = self.invited.take()
+            && let Err(e) = infos.api().decline_duel(id).await
+        {
+            warn!("cancelling the duel failed: {}", e);
+        }
+        let mut authent = infos.authent.borrow_mut();
+        if authent.receiver.is_none() {
+            authent.receiver = self.receiver.take();
+        }
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
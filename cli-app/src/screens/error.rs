@@ -0,0 +1,45 @@
+use super::{Screen, Transition};
+use crate::infos::Infos;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::Event;
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+};
+
+/// Shows the error until a key is pressed, then goes back to the previous screen
+pub(super) struct ErrorScreen {
+    message: String,
+}
+
+impl ErrorScreen {
+    pub(super) fn new(message: String) -> Self {
+        ErrorScreen { message }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for ErrorScreen {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered().border_set(border::THICK);
+        let linelist: Vec<Line> = vec![
+            ("Error: ".bold() + self.message.as_str().bold()),
+            Line::from("Press any key to continue".bold()),
+        ];
+        Paragraph::new(linelist)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        match event {
+            Event::Key(_) => Ok(Transition::Pop),
+            _ => Ok(Transition::Stay),
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use super::duel::DuelWaiting;
+use super::spectate::Spectating;
+use super::{Screen, Task, Transition};
+use crate::api::Profile;
+use crate::duel::challenge;
+use crate::friends::{get_indexed_friends, send_delete_friend_request, send_friend_request};
+use crate::infos::Infos;
+use crate::spectate::{Spectator, game_of, watch};
+use crate::utils::{Blink, should_exit};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    prelude::Color,
+    style::{Modifier, Style, Stylize},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+use std::cell::Cell;
+
+/// Friends list, one page per screen height
+#[derive(Default)]
+pub(super) struct FriendsList {
+    index: usize,
+    friends_list: Vec<String>,
+    /// Lines available at the last render, to know how many pages there are
+    height: Cell<usize>,
+    fetching: Task<Result<Vec<String>>>,
+}
+
+impl FriendsList {
+    fn index_max(&self) -> usize {
+        let height = self.height.get();
+        let len = self.friends_list.len();
+        let modulo: usize = match height {
+            0 => 0,
+            _ => match len % height {
+                0 => 0,
+                _ => 1,
+            },
+        };
+        if height < len && height != 0 {
+            len / height + modulo
+        } else {
+            0
+        }
+    }
+    fn refresh(&mut self, infos: &Infos) {
+        let (api, me) = (infos.api(), infos.authent.borrow().id);
+        self.fetching
+            .start(async move { get_indexed_friends(&api, me).await });
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for FriendsList {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " Menu: ↑ Add friend ".bold(),
+            " ↓ Delete friend ".bold(),
+            " Enter. Challenge ".bold(),
+            " W. Watch ".bold(),
+            " ← Previous ".bold(),
+            " → Next ".bold(),
+            " ESC. Back ".bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from("Your Friends").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        let height: usize = area.height.saturating_sub(2) as usize;
+        self.height.set(height);
+        let max: usize = match (self.index * height + height + 1) >= self.friends_list.len() {
+            true => self.friends_list.len(),
+            false => (self.index * height) + height + 1,
+        };
+        let min = match self.index * height < self.friends_list.len() {
+            true => self.index * height,
+            false => self.friends_list.len(),
+        };
+        let lines: Vec<Line> = self.friends_list[min..max]
+            .iter()
+            .map(|friend| Line::from(friend.clone().bold()))
+            .collect();
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        match event {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Up => {
+                    return Ok(Transition::Push(Box::new(FriendForm::new(
+                        FriendAction::Add,
+                    ))));
+                }
+                KeyCode::Down => {
+                    return Ok(Transition::Push(Box::new(FriendForm::new(
+                        FriendAction::Delete,
+                    ))));
+                }
+                KeyCode::Enter => {
+                    return Ok(Transition::Push(Box::new(FriendForm::new(
+                        FriendAction::Challenge,
+                    ))));
+                }
+                KeyCode::Char('w') => {
+                    return Ok(Transition::Push(Box::new(FriendForm::new(
+                        FriendAction::Watch,
+                    ))));
+                }
+                KeyCode::Right if self.index < self.index_max() => self.index += 1,
+                KeyCode::Left if self.index > usize::MIN => self.index -= 1,
+                _ => {}
+            },
+            Event::Resize(_, _) => self.index = 0,
+            _ => {}
+        }
+        self.refresh(infos);
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.fetching.wait().await
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        if let Some(friends) = self.fetching.take() {
+            self.friends_list = friends?;
+            if self.index > self.index_max() {
+                self.index = 0;
+            }
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.refresh(infos);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum FriendAction {
+    Add,
+    Delete,
+    /// Invite to a duel, then wait for the answer
+    Challenge,
+    /// Follow the match the friend is playing
+    Watch,
+}
+
+/// What the form leads to once the server answered
+enum Sent {
+    Done,
+    Invited(Profile),
+    Watching(Spectator),
+}
+
+/// Asks for a username to send a friend request to, to remove from the friends, to challenge or to watch
+pub(super) struct FriendForm {
+    action: FriendAction,
+    friend_tmp: String,
+    blink: Blink,
+    sending: Task<Result<Sent>>,
+}
+
+impl FriendForm {
+    pub(super) fn new(action: FriendAction) -> Self {
+        FriendForm {
+            action,
+            friend_tmp: String::new(),
+            blink: Blink::default(),
+            sending: Task::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for FriendForm {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let (title, block_title) = match self.action {
+            FriendAction::Add => ("Add a friend", "Add Friend"),
+            FriendAction::Delete => ("Delete a friend", "Delete friend"),
+            FriendAction::Challenge => ("Challenge to a duel", "Challenge"),
+            FriendAction::Watch => ("Watch a friend's match", "Watch"),
+        };
+        let friend = format!(
+            "{}{}",
+            self.friend_tmp,
+            if self.blink.on() { "|" } else { "" }
+        );
+        let content = vec![
+            Line::from(Span::styled(
+                title,
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Friend:     ", Style::default().fg(Color::Gray)),
+                Span::raw(friend),
+            ]),
+        ];
+        Paragraph::new(content)
+            .block(
+                Block::default()
+                    .title(block_title.bold())
+                    .title_bottom(match self.sending.is_running() {
+                        true => "Sending…".bold().yellow().into_centered_line(),
+                        false => "Menu: Enter. Ok  ESC. Quit ".bold().into_centered_line(),
+                    })
+                    .borders(Borders::ALL),
+            )
+            .alignment(Alignment::Left)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        if let Event::Key(eventkey) = event
+            && !self.sending.is_running()
+        {
+            match eventkey.code {
+                KeyCode::Backspace => {
+                    self.friend_tmp.pop();
+                }
+                KeyCode::Char(c) => self.friend_tmp.push(c),
+                KeyCode::Enter => {
+                    let name = std::mem::take(&mut self.friend_tmp);
+                    let (api, me, action) = (infos.api(), infos.authent.borrow().id, self.action);
+                    let handle = infos.handle();
+                    self.sending.start(async move {
+                        match action {
+                            FriendAction::Add => send_friend_request(&api, &name).await?,
+                            FriendAction::Delete => send_delete_friend_request(&api, &name).await?,
+                            FriendAction::Challenge => {
+                                return Ok(Sent::Invited(challenge(&api, me, &name).await?));
+                            }
+                            FriendAction::Watch => {
+                                let friend = api.profile_by_name(&name).await?;
+                                let game = game_of(&api, friend.id)
+                                    .await?
+                                    .ok_or_else(|| anyhow!("{} is not playing", friend.name))?;
+                                let token = handle.authent.borrow().token.clone();
+                                let spectator = watch(&handle.context, &token, game).await?;
+                                return Ok(Sent::Watching(spectator));
+                            }
+                        };
+                        Ok(Sent::Done)
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.sending.wait().await
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        self.blink.tick();
+        Ok(match self.sending.take() {
+            Some(Ok(Sent::Invited(invited))) => {
+                Transition::Replace(Box::new(DuelWaiting::invited(invited)))
+            }
+            Some(Ok(Sent::Watching(spectator))) => {
+                Transition::Replace(Box::new(Spectating::new(spectator)))
+            }
+            Some(Ok(Sent::Done)) => Transition::Pop,
+            Some(Err(e)) => return Err(e),
+            None => Transition::Stay,
+        })
+    }
+}
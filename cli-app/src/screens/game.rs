@@ -0,0 +1,378 @@
+use super::{Screen, Transition};
+use crate::api::MatchStart;
+use crate::chat::ChatStatus;
+use crate::game::{Game, GameStats, GameUpdate};
+use crate::infos::{GameParams, Infos, create_direct_game, send_post_game_request};
+use crate::utils::should_exit;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    prelude::{Color, Constraint, Direction, Layout},
+    style::Stylize,
+    symbols::{Marker, border},
+    text::{Line, Span},
+    widgets::{
+        Block, Paragraph, Widget,
+        canvas::{Canvas, Circle, Rectangle},
+    },
+};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Waits in the queue until the chat websocket announces a match
+pub(super) struct Matchmaking {
+    mode: &'static str,
+    params: Option<GameParams>,
+    queued: bool,
+    /// Borrowed from `Auth` while the screen is active
+    receiver: Option<mpsc::Receiver<MatchStart>>,
+    start: Option<MatchStart>,
+    /// The server dropped us from the queue with the chat socket, join again once it is back
+    requeue: bool,
+}
+
+impl Matchmaking {
+    pub(super) fn new(mode: &'static str) -> Self {
+        Matchmaking {
+            mode,
+            params: None,
+            queued: false,
+            receiver: None,
+            start: None,
+            requeue: false,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Matchmaking {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title_bottom("Menu: ESC. Quit".bold().into_centered_line())
+            .border_set(border::THICK);
+        let text = match self.requeue {
+            true => "Connection lost, back in the queue once it returns",
+            false => "Searching for opponent",
+        };
+        Paragraph::new(Line::from(text.bold()))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        let Some(receiver) = self.receiver.as_mut() else {
+            return std::future::pending().await;
+        };
+        match receiver.recv().await {
+            Some(start) => self.start = Some(start),
+            None => self.receiver = None,
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        let status = infos.authent.borrow().chat_status.as_ref().map(|s| s.borrow().clone());
+        match status {
+            Some(ChatStatus::Rejected) => return Err(anyhow!("Session expired, log in again")),
+            Some(ChatStatus::Reconnecting { .. }) if self.queued => {
+                self.queued = false;
+                self.requeue = true;
+            }
+            Some(ChatStatus::Connected) if self.requeue => {
+                self.requeue = false;
+                self.params = Some(send_post_game_request(infos, self.mode).await?);
+                self.queued = true;
+            }
+            _ => {}
+        }
+        let Some(start) = self.start.take() else {
+            if self.receiver.is_none() {
+                return Err(anyhow!("Chat connection closed"));
+            }
+            return Ok(Transition::Stay);
+        };
+        self.queued = false;
+        let params = self.params.take().unwrap_or_default();
+        Ok(Transition::Replace(start_match(infos, start, params).await?))
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.receiver = Some(
+            infos
+                .authent
+                .borrow_mut()
+                .receiver
+                .take()
+                .ok_or_else(|| anyhow!("receiver not initialized"))?,
+        );
+        self.params = Some(send_post_game_request(infos, self.mode).await?);
+        self.queued = true;
+        Ok(())
+    }
+    async fn on_exit(&mut self, infos: &mut Infos) {
+        if self.queued {
+            self.queued = false;
+            if let Err(e) = send_remove_from_queue_request(infos).await {
+                warn!("leaving the queue failed: {}", e);
+            }
+        }
+        let mut authent = infos.authent.borrow_mut();
+        if authent.receiver.is_none() {
+            authent.receiver = self.receiver.take();
+        }
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
+
+/// Connects to the game announced on the chat socket
+pub(super) async fn start_match(
+    infos: &Infos,
+    start: MatchStart,
+    params: GameParams,
+) -> Result<Box<dyn Screen>> {
+    let game = connect_match(infos, start, params).await?;
+    Ok(Box::new(GameScreen {
+        game,
+        update: None,
+        then: "Continue",
+    }))
+}
+
+/// Creates a game against the bot or for two players on this keyboard, no queue involved
+pub(super) async fn start_direct_match(infos: &Infos, mode: &str) -> Result<Box<dyn Screen>> {
+    let (start, params) = create_direct_game(infos, mode).await?;
+    start_match(infos, start, params).await
+}
+
+/// Same as `start_match`, the end screen leads back to the bracket
+pub(super) async fn start_tournament_match(
+    infos: &Infos,
+    start: MatchStart,
+) -> Result<Box<dyn Screen>> {
+    let game = connect_match(infos, start, GameParams::default()).await?;
+    Ok(Box::new(GameScreen {
+        game,
+        update: None,
+        then: "see the bracket",
+    }))
+}
+
+async fn connect_match(infos: &Infos, start: MatchStart, params: GameParams) -> Result<Game> {
+    let mut game = Game::new(infos, start, params).await?;
+    send_start_game(infos, &game.game_id).await?;
+    let checker = game.start_game().await?;
+    game.server_checker = Some(checker);
+    Ok(game)
+}
+
+/// Field of a match and its score, for server and offline games alike
+///
+/// #Parameters
+/// - names: shown next to the left and right scores
+/// - controls: keys reminder under the field
+pub(super) fn display_played_game(
+    stats: &GameStats,
+    params: &GameParams,
+    names: [&str; 2],
+    controls: Option<&str>,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Fill(1), Constraint::Max(3)])
+        .split(area);
+    let mut block = Block::bordered().title("Pong".bold());
+    if let Some(controls) = controls {
+        block = block.title_bottom(controls.bold().into_centered_line());
+    }
+    Canvas::default()
+        .block(block)
+        .marker(Marker::Braille)
+        .x_bounds([0.0, 100.0])
+        .y_bounds([0.0, 100.0])
+        .paint(|ctx| {
+            ctx.draw(&Circle {
+                x: stats.ball_x as f64,
+                y: (100.0 - stats.ball_y) as f64,
+                radius: params.ball_size / 2.0,
+                color: Color::Yellow,
+            });
+            ctx.draw(&Rectangle {
+                x: params.paddle_padding,
+                y: (100.0 - stats.left_y as f64) - params.paddle_height / 2.0,
+                width: params.paddle_width,
+                height: params.paddle_height,
+                color: Color::Green,
+            });
+            ctx.draw(&Rectangle {
+                x: 100.0 - params.paddle_padding - params.paddle_width,
+                y: (100.0 - stats.right_y as f64) - params.paddle_height / 2.0,
+                width: params.paddle_width,
+                height: params.paddle_height,
+                color: Color::Green,
+            });
+        })
+        .render(layout[0], buf);
+    let line = Line::from(vec![
+        format!("{}: {}", names[0], stats.player1_score).bold(),
+        "    |     ".bold(),
+        format!("{}: {}", names[1], stats.player2_score).bold(),
+    ]);
+    Paragraph::new(line)
+        .block(
+            Block::bordered()
+                .border_set(border::THICK)
+                .title("Score".bold()),
+        )
+        .centered()
+        .render(layout[1], buf);
+}
+
+/// Match in progress, paddle keys are forwarded to the game websocket
+struct GameScreen {
+    game: Game,
+    update: Option<GameUpdate>,
+    /// What Enter leads to on the end screen
+    then: &'static str,
+}
+
+#[async_trait(?Send)]
+impl Screen for GameScreen {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let game = &self.game;
+        let (me, controls) = match game.is_local() {
+            true => ("Player 1", Some(" Player 1: W S   Player 2: ↑ ↓ ")),
+            false => ("You", None),
+        };
+        display_played_game(
+            &game.drawn(Instant::now()),
+            &game.parameters,
+            [me, &game.opponent_name],
+            controls,
+            area,
+            buf,
+        );
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        self.game.send_event(event);
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        self.update = Some(self.game.next_update().await);
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        match self.update.take() {
+            None => {}
+            Some(GameUpdate::State(received, bytes)) => {
+                self.game.decode_and_update(received, bytes)?
+            }
+            Some(GameUpdate::End(text)) => {
+                let Some(sender) = self.game.game_sender.clone() else {
+                    return Ok(Transition::Pop);
+                };
+                self.game.end_game(text, sender).await?;
+                let winner = self.game.game_stats.winner;
+                let local = self.game.is_local();
+                let then = self.then;
+                return Ok(Transition::Replace(Box::new(EndGame {
+                    winner,
+                    local,
+                    then,
+                })));
+            }
+            Some(GameUpdate::Closed) => return Ok(Transition::Pop),
+            Some(GameUpdate::Failed(e)) => return Err(e),
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_exit(&mut self, _infos: &mut Infos) {
+        self.game.stop();
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
+
+pub(super) struct EndGame {
+    /// Player 1 won, in a local game
+    winner: bool,
+    local: bool,
+    then: &'static str,
+}
+
+impl EndGame {
+    pub(super) fn new(winner: bool, then: &'static str) -> Self {
+        EndGame {
+            winner,
+            local: false,
+            then,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for EndGame {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let sentence: &str = match (self.local, self.winner) {
+            (true, true) => "Player 1 wins!",
+            (true, false) => "Player 2 wins!",
+            (false, true) => "You Win :)",
+            (false, false) => "You lose :(",
+        };
+        let block = Block::bordered().border_set(border::THICK);
+        let spanlist: Vec<Span> = vec![
+            sentence.bold(),
+            format!(" Press Enter to {}", self.then).bold(),
+        ];
+        Paragraph::new(Line::from(spanlist))
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        if let Event::Key(keyevent) = event
+            && keyevent.code == KeyCode::Enter
+        {
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::Stay)
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
+
+async fn send_start_game(infos: &Infos, game_id: &str) -> Result<()> {
+    let token = infos.authent.borrow().token.clone();
+    infos
+        .context
+        .api()
+        .with_token(&token)
+        .start_game(game_id)
+        .await?;
+    Ok(())
+}
+
+async fn send_remove_from_queue_request(infos: &Infos) -> Result<()> {
+    let token = infos.authent.borrow().token.clone();
+    infos
+        .context
+        .api()
+        .with_token(&token)
+        .remove_from_queue()
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use super::auth::{Credentials, LoginScreen, SignupScreen, logged_in};
+use super::chat::ChatScreen;
+use super::dm::Conversations;
+use super::duel::Duels;
+use super::friends::FriendsList;
+use super::game::{Matchmaking, start_direct_match};
+use super::practice::PracticeLevels;
+use super::replay::Replays;
+use super::tournament::Tournaments;
+use super::{Screen, Task, Transition, print_block, print_demo};
+use crate::infos::Infos;
+use crate::login::create_guest_session;
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    prelude::{Constraint, Direction, Layout},
+    style::{Color, Stylize},
+    text::{Line, Span},
+};
+
+/// Logo and instructions above the demo game shown behind every menu
+fn render_menu(infos: &Infos, instructions: Line, area: Rect, buf: &mut Buffer) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Max(10), Constraint::Fill(1)])
+        .split(area);
+    print_demo(&infos.demo, layout[1], buf);
+    print_block(instructions, layout[0], buf);
+}
+
+/// Count of the direct messages not read yet, nothing when there are none
+fn unread_badge(infos: &Infos) -> Span<'static> {
+    match infos.authent.borrow().unread() {
+        0 => Span::raw(""),
+        unread => format!("({}) ", unread).fg(Color::Yellow).bold(),
+    }
+}
+
+/// Code of a key press, releases and repeats are ignored
+fn pressed(event: &Event) -> Option<KeyCode> {
+    match event {
+        Event::Key(key_event) if key_event.kind == KeyEventKind::Press => Some(key_event.code),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct FirstScreen {
+    guest: Task<Result<Credentials>>,
+}
+
+#[async_trait(?Send)]
+impl Screen for FirstScreen {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        if self.guest.is_running() {
+            let pending = Line::from(" Creating a guest session… ".bold().yellow());
+            return render_menu(infos, pending, area, buf);
+        }
+        let instructions = Line::from(vec![
+            " Menu:".bold(),
+            "  ↑. Sign up".bold(),
+            "  ↓. Login".bold(),
+            "  →. Sign in as guest".bold(),
+            "  P. Practice offline".bold(),
+            "  ESC. Quit ".bold(),
+        ]);
+        render_menu(infos, instructions, area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Quit);
+        }
+        if self.guest.is_running() {
+            return Ok(Transition::Stay);
+        }
+        Ok(match pressed(&event) {
+            Some(KeyCode::Up) => Transition::Push(Box::<SignupScreen>::default()),
+            Some(KeyCode::Down) => Transition::Push(Box::<LoginScreen>::default()),
+            Some(KeyCode::Char('p' | 'P')) => Transition::Push(Box::<PracticeLevels>::default()),
+            Some(KeyCode::Right) => {
+                self.guest
+                    .start(create_guest_session(infos.context.clone()));
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        })
+    }
+    async fn wait(&mut self) {
+        self.guest.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.demo.update();
+        logged_in(infos, &mut self.guest)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Welcome {
+    logout: Task<()>,
+}
+
+#[async_trait(?Send)]
+impl Screen for Welcome {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        if self.logout.is_running() {
+            return render_menu(
+                infos,
+                Line::from(" Logging out… ".bold().yellow()),
+                area,
+                buf,
+            );
+        }
+        let instructions = Line::from(vec![
+            " Menu:  ↑ Game ".bold(),
+            " → Social Life ".bold(),
+            unread_badge(infos),
+            " ↓ Logout ".bold(),
+            " ESC. Quit ".bold(),
+        ]);
+        render_menu(infos, instructions, area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Quit);
+        }
+        if self.logout.is_running() {
+            return Ok(Transition::Stay);
+        }
+        Ok(match pressed(&event) {
+            Some(KeyCode::Up) => Transition::Push(Box::<GameChoice>::default()),
+            Some(KeyCode::Right) => Transition::Push(Box::new(SocialLife)),
+            Some(KeyCode::Down) => {
+                let mut infos = infos.handle();
+                self.logout.start(async move { infos.logout(true).await });
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        })
+    }
+    async fn wait(&mut self) {
+        self.logout.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.demo.update();
+        Ok(match self.logout.take() {
+            Some(()) => Transition::Reset(Box::<FirstScreen>::default()),
+            None => Transition::Stay,
+        })
+    }
+}
+
+#[derive(Default)]
+struct GameChoice {
+    /// Game against the bot or local, connected before its screen is pushed
+    starting: Task<Result<Box<dyn Screen>>>,
+}
+
+impl GameChoice {
+    fn start(&mut self, infos: &Infos, mode: &'static str) {
+        let infos = infos.handle();
+        self.starting
+            .start(async move { start_direct_match(&infos, mode).await });
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for GameChoice {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        if self.starting.is_running() {
+            return render_menu(
+                infos,
+                Line::from(" Starting the game… ".bold().yellow()),
+                area,
+                buf,
+            );
+        }
+        let instructions = Line::from(vec![
+            " Menu: → Online ".bold(),
+            " B. vs Bot ".bold(),
+            " L. Local ".bold(),
+            " P. Practice ".bold(),
+            " R. Replays ".bold(),
+            " ↑ Tournaments ".bold(),
+            " ↓ Duels ".bold(),
+            " ← Back  ".bold(),
+            "ESC. Quit ".bold(),
+        ]);
+        render_menu(infos, instructions, area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Quit);
+        }
+        if self.starting.is_running() {
+            return Ok(Transition::Stay);
+        }
+        Ok(match pressed(&event) {
+            Some(KeyCode::Right) => Transition::Push(Box::new(Matchmaking::new("online"))),
+            Some(KeyCode::Char('b' | 'B')) => {
+                self.start(infos, "bot");
+                Transition::Stay
+            }
+            Some(KeyCode::Char('l' | 'L')) => {
+                self.start(infos, "local");
+                Transition::Stay
+            }
+            Some(KeyCode::Char('p' | 'P')) => Transition::Push(Box::<PracticeLevels>::default()),
+            Some(KeyCode::Char('r' | 'R')) => Transition::Push(Box::<Replays>::default()),
+            Some(KeyCode::Up) => Transition::Push(Box::new(Tournaments::default())),
+            Some(KeyCode::Down) => Transition::Push(Box::new(Duels::default())),
+            Some(KeyCode::Left) => Transition::Pop,
+            _ => Transition::Stay,
+        })
+    }
+    async fn wait(&mut self) {
+        self.starting.wait().await
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.demo.update();
+        Ok(match self.starting.take() {
+            Some(game) => Transition::Push(game?),
+            None => Transition::Stay,
+        })
+    }
+}
+
+struct SocialLife;
+
+#[async_trait(?Send)]
+impl Screen for SocialLife {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " Menu: ↑ Chat  ".bold(),
+            "↓ Messages ".bold(),
+            unread_badge(infos),
+            " → Your Friends  ".bold(),
+            "← Back  ".bold(),
+            "ESC. Quit ".bold(),
+        ]);
+        render_menu(infos, instructions, area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Quit);
+        }
+        Ok(match pressed(&event) {
+            Some(KeyCode::Up) => Transition::Push(Box::new(ChatScreen::default())),
+            Some(KeyCode::Down) => Transition::Push(Box::new(Conversations::default())),
+            Some(KeyCode::Right) => Transition::Push(Box::new(FriendsList::default())),
+            Some(KeyCode::Left) => Transition::Pop,
+            _ => Transition::Stay,
+        })
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        infos.demo.update();
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,374 @@
+mod auth;
+mod bracket;
+mod chain;
+mod chat;
+mod dm;
+mod duel;
+mod error;
+mod friends;
+mod game;
+mod menus;
+mod practice;
+mod replay;
+mod spectate;
+mod tournament;
+
+pub(crate) use menus::{FirstScreen, Welcome};
+
+use crate::LOGO;
+use crate::api::MatchStart;
+use crate::game_demo::Demo;
+use crate::infos::{GameParams, Infos};
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, EventStream};
+use error::ErrorScreen;
+use futures::StreamExt;
+use futures::future::LocalBoxFuture;
+use ratatui::{
+    DefaultTerminal, Frame,
+    buffer::Buffer,
+    layout::Rect,
+    prelude::{Color, Stylize},
+    symbols::{Marker, border},
+    text::Line,
+    widgets::{
+        Block, Paragraph, Widget,
+        canvas::{Canvas, Circle, Rectangle},
+    },
+};
+use std::time::Duration;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{debug, warn};
+
+/// Delay between two frames, also drives animations
+const TICK: Duration = Duration::from_millis(16);
+
+/// What the router does once a screen handled an event
+pub(crate) enum Transition {
+    Stay,
+    Push(Box<dyn Screen>),
+    /// Back to the previous screen, quits when there is none
+    Pop,
+    Replace(Box<dyn Screen>),
+    /// Drops the whole stack, after logging in or out
+    Reset(Box<dyn Screen>),
+    Quit,
+}
+
+#[async_trait(?Send)]
+pub(crate) trait Screen {
+    fn render(&self, infos: &Infos, area: Rect, buf: &mut Buffer);
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition>;
+    /// Resolves once network data for the screen arrived, `update` is called right after.
+    /// Must be cancel safe, a key press or a tick can interrupt it at any await point
+    async fn wait(&mut self) {
+        std::future::pending().await
+    }
+    /// Called on every tick and after `wait`, for animations and network state
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        Ok(Transition::Stay)
+    }
+    /// The screen becomes the active one, either pushed or uncovered
+    async fn on_enter(&mut self, _infos: &mut Infos) -> Result<()> {
+        Ok(())
+    }
+    /// The screen stops being the active one, either covered or removed
+    async fn on_exit(&mut self, _infos: &mut Infos) {}
+    /// Dropped instead of returned to after an error, like a game in progress
+    fn is_transient(&self) -> bool {
+        false
+    }
+    /// Shown in the logs
+    fn name(&self) -> &'static str {
+        let path = std::any::type_name::<Self>();
+        path.rsplit("::").next().unwrap_or(path)
+    }
+}
+
+/// Network work started by a key press, polled from `wait` so the screen keeps drawing
+///
+/// Dropping the task, like leaving its screen, cancels the work
+pub(super) struct Task<T> {
+    running: Option<LocalBoxFuture<'static, T>>,
+    done: Option<T>,
+}
+
+impl<T> Default for Task<T> {
+    fn default() -> Self {
+        Task {
+            running: None,
+            done: None,
+        }
+    }
+}
+
+impl<T> Task<T> {
+    /// Replaces the work still running, if any
+    pub(super) fn start(&mut self, work: impl Future<Output = T> + 'static) {
+        self.running = Some(Box::pin(work));
+        self.done = None;
+    }
+    pub(super) fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+    /// Resolves once the work is done, never when there is none.
+    /// Cancel safe, the work is kept until it completes
+    pub(super) async fn wait(&mut self) {
+        let Some(work) = self.running.as_mut() else {
+            return std::future::pending().await;
+        };
+        let output = work.await;
+        self.running = None;
+        self.done = Some(output);
+    }
+    /// Result of the finished work, once
+    pub(super) fn take(&mut self) -> Option<T> {
+        self.done.take()
+    }
+}
+
+/// Navigation stack, only the top screen is drawn and receives events
+pub(crate) struct Router {
+    stack: Vec<Box<dyn Screen>>,
+    exit: bool,
+}
+
+/// What woke the router up
+enum Wake {
+    Event(Event),
+    Tick,
+    Network,
+}
+
+impl Router {
+    pub(crate) fn new(first: Box<dyn Screen>) -> Self {
+        Router {
+            stack: vec![first],
+            exit: false,
+        }
+    }
+    pub(crate) async fn run(
+        &mut self,
+        infos: &mut Infos,
+        terminal: &mut DefaultTerminal,
+    ) -> Result<()> {
+        if let Some(screen) = self.stack.last_mut()
+            && let Err(e) = screen.on_enter(infos).await
+        {
+            self.fail(infos, e).await;
+        }
+        let mut events = EventStream::new();
+        let mut tick = interval(TICK);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        while !self.exit {
+            let Some(screen) = self.stack.last_mut() else {
+                break;
+            };
+            let wake = tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => Wake::Event(event?),
+                    None => break,
+                },
+                _ = tick.tick() => Wake::Tick,
+                _ = screen.wait() => Wake::Network,
+            };
+            let redraw = matches!(wake, Wake::Tick);
+            let transition = match wake {
+                Wake::Event(event) => screen.handle_event(infos, event).await,
+                Wake::Tick | Wake::Network => screen.update(infos).await,
+            };
+            self.settle(infos, transition).await;
+            if redraw && let Err(e) = terminal.draw(|frame| self.draw(infos, frame)) {
+                self.fail(infos, e.into()).await;
+            }
+        }
+        Ok(())
+    }
+    /// Gives every screen left on the stack a chance to clean up, top first
+    pub(crate) async fn leave(&mut self, infos: &mut Infos) {
+        while let Some(mut screen) = self.stack.pop() {
+            screen.on_exit(infos).await;
+        }
+    }
+    fn draw(&self, infos: &Infos, frame: &mut Frame) {
+        if let Some(screen) = self.stack.last() {
+            screen.render(infos, frame.area(), frame.buffer_mut());
+        }
+        print_chat_status(infos, frame.area(), frame.buffer_mut());
+    }
+    async fn settle(&mut self, infos: &mut Infos, transition: Result<Transition>) {
+        let applied = match transition {
+            Ok(transition) => self.apply(infos, transition).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = applied {
+            self.fail(infos, e).await;
+        }
+    }
+    async fn apply(&mut self, infos: &mut Infos, transition: Transition) -> Result<()> {
+        match transition {
+            Transition::Stay => return Ok(()),
+            Transition::Quit => {
+                self.exit = true;
+                return Ok(());
+            }
+            Transition::Push(screen) => {
+                if let Some(top) = self.stack.last_mut() {
+                    top.on_exit(infos).await;
+                }
+                self.stack.push(screen);
+            }
+            Transition::Pop => {
+                if let Some(mut top) = self.stack.pop() {
+                    top.on_exit(infos).await;
+                }
+            }
+            Transition::Replace(screen) => {
+                if let Some(mut top) = self.stack.pop() {
+                    top.on_exit(infos).await;
+                }
+                self.stack.push(screen);
+            }
+            Transition::Reset(screen) => {
+                self.leave(infos).await;
+                self.stack.push(screen);
+            }
+        }
+        let depth = self.stack.len();
+        match self.stack.last_mut() {
+            Some(top) => {
+                debug!(screen = top.name(), depth, "screen entered");
+                top.on_enter(infos).await
+            }
+            None => {
+                self.exit = true;
+                Ok(())
+            }
+        }
+    }
+    /// Drops transient screens and shows the error above the screen they were opened from
+    async fn fail(&mut self, infos: &mut Infos, error: anyhow::Error) {
+        let screen = self.stack.last().map(|top| top.name()).unwrap_or_default();
+        warn!(screen, "{:#}", error);
+        while let Some(top) = self.stack.last_mut() {
+            top.on_exit(infos).await;
+            if !top.is_transient() {
+                break;
+            }
+            self.stack.pop();
+        }
+        self.stack
+            .push(Box::new(ErrorScreen::new(error.to_string())));
+    }
+}
+
+/// Takes over the terminal for a single match, until its end screen is left
+pub(crate) async fn play_match(
+    infos: &mut Infos,
+    start: MatchStart,
+    params: GameParams,
+) -> Result<()> {
+    let screen = game::start_match(infos, start, params).await?;
+    let mut terminal = ratatui::init();
+    let mut router = Router::new(screen);
+    let result = router.run(infos, &mut terminal).await;
+    router.leave(infos).await;
+    ratatui::restore();
+    result
+}
+
+/// Lines received by the global chat since login
+fn received(infos: &Infos) -> u64 {
+    let authent = infos.authent.borrow();
+    authent
+        .chat_log
+        .as_ref()
+        .map_or(0, |log| log.borrow().received)
+}
+
+/// Written over the top border while the chat websocket is down
+fn print_chat_status(infos: &Infos, area: Rect, buf: &mut Buffer) {
+    let authent = infos.authent.borrow();
+    let Some(status) = authent.chat_status.as_ref() else {
+        return;
+    };
+    let Some(text) = status.borrow().describe() else {
+        return;
+    };
+    let line = Rect {
+        x: area.x + 2,
+        y: area.y,
+        width: area.width.saturating_sub(4),
+        height: area.height.min(1),
+    };
+    Paragraph::new(Line::from(format!(" {} ", text).yellow().bold()))
+        .right_aligned()
+        .render(line, buf);
+}
+
+fn print_block(instructions: Line, area: Rect, buf: &mut Buffer) {
+    let block = Block::bordered()
+        .title_bottom(instructions.centered())
+        .border_set(border::THICK);
+    Paragraph::new(LOGO)
+        .centered()
+        .block(block)
+        .render(area, buf);
+}
+
+fn print_demo(demo: &Demo, area: Rect, buf: &mut Buffer) {
+    Canvas::default()
+        .block(Block::bordered())
+        .marker(Marker::Braille)
+        .x_bounds([0.0, 100.0])
+        .y_bounds([0.0, 100.0])
+        .paint(|ctx| {
+            ctx.draw(&Circle {
+                x: demo.ball_x,
+                y: demo.ball_y,
+                radius: 0.5,
+                color: Color::Yellow,
+            });
+            ctx.draw(&Rectangle {
+                x: 1.5,
+                y: demo.paddle_left_y,
+                width: 2.0,
+                height: 10.0,
+                color: Color::Green,
+            });
+            ctx.draw(&Rectangle {
+                x: 95.0,
+                y: demo.paddle_right_y,
+                width: 2.0,
+                height: 10.0,
+                color: Color::Green,
+            });
+        })
+        .render(area, buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn tasks_survive_an_interrupted_wait() {
+        let mut task = Task::default();
+        let (answer, answered) = oneshot::channel();
+        task.start(async move { answered.await.unwrap_or_default() });
+        assert!(task.is_running());
+        // a key press or a tick wins the router's select
+        let interrupted = timeout(Duration::from_millis(10), task.wait()).await;
+        assert!(interrupted.is_err() && task.is_running());
+        answer.send(42).unwrap();
+        task.wait().await;
+        assert!(!task.is_running());
+        assert_eq!(task.take(), Some(42));
+        assert_eq!(task.take(), None);
+        let idle = timeout(Duration::from_millis(10), task.wait()).await;
+        assert!(idle.is_err());
+    }
+}
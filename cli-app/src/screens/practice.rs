@@ -0,0 +1,159 @@
+use super::game::{EndGame, display_played_game};
+use super::{Screen, Transition};
+use crate::game::HOLD;
+use crate::infos::{GameParams, Infos};
+use crate::practice::{Level, Practice};
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+};
+use std::time::Instant;
+
+/// Difficulty of an offline match, the match comes back here once over
+pub(super) struct PracticeLevels {
+    selected: usize,
+}
+
+impl Default for PracticeLevels {
+    fn default() -> Self {
+        PracticeLevels { selected: 1 }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for PracticeLevels {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " ↑ ↓ Select ".bold(),
+            " Enter. Play ".bold(),
+            " ESC. Back ".bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from("Practice, no server needed").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        let lines: Vec<Line> = Level::ALL
+            .iter()
+            .enumerate()
+            .map(|(index, level)| {
+                let line = Line::from(level.name().bold());
+                match index == self.selected {
+                    true => line.reversed(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(Transition::Stay);
+        }
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < Level::ALL.len() => self.selected += 1,
+            KeyCode::Enter => {
+                let level = Level::ALL[self.selected];
+                return Ok(Transition::Push(Box::new(PracticeMatch::new(level))));
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Offline match against the computer, simulated on every tick
+struct PracticeMatch {
+    practice: Practice,
+    opponent: String,
+    /// Last press of ↑ and ↓
+    up: Option<Instant>,
+    down: Option<Instant>,
+    simulated: Instant,
+}
+
+impl PracticeMatch {
+    fn new(level: Level) -> Self {
+        PracticeMatch {
+            practice: Practice::new(GameParams::default(), level),
+            opponent: format!("Computer ({})", level.name()),
+            up: None,
+            down: None,
+            simulated: Instant::now(),
+        }
+    }
+}
+
+/// A key counts as held for a while after its last press, as in server games
+fn held(at: Option<Instant>) -> bool {
+    at.is_some_and(|at| at.elapsed() <= HOLD)
+}
+
+#[async_trait(?Send)]
+impl Screen for PracticeMatch {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        display_played_game(
+            &self.practice.stats(),
+            self.practice.params(),
+            ["You", &self.opponent],
+            Some(" ↑ ↓ Move   ESC. Give up "),
+            area,
+            buf,
+        );
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        let at = match key.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => Some(Instant::now()),
+            KeyEventKind::Release => None,
+        };
+        match key.code {
+            KeyCode::Up => self.up = at,
+            KeyCode::Down => self.down = at,
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        let direction = match (held(self.up), held(self.down)) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        };
+        let now = Instant::now();
+        self.practice.advance(now - self.simulated, direction);
+        self.simulated = now;
+        match self.practice.winner() {
+            Some(winner) => Ok(Transition::Replace(Box::new(EndGame::new(
+                winner == 0,
+                "Continue",
+            )))),
+            None => Ok(Transition::Stay),
+        }
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,232 @@
+use super::game::display_played_game;
+use super::{Screen, Transition};
+use crate::infos::Infos;
+use crate::replay::{self, Replay, ReplayInfo};
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Playback speeds, ↑ and ↓ move between them
+const SPEEDS: [f64; 3] = [0.5, 1.0, 2.0];
+/// Jump of ← and →
+const SEEK: Duration = Duration::from_secs(5);
+
+/// Matches recorded with --record, newest first
+#[derive(Default)]
+pub(super) struct Replays {
+    replays: Vec<(PathBuf, ReplayInfo)>,
+    selected: usize,
+}
+
+/// How long ago a replay was recorded
+fn ago(recorded_at: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    match now.saturating_sub(recorded_at) {
+        seconds if seconds < 3600 => format!("{} min ago", seconds / 60),
+        seconds if seconds < 86400 => format!("{} h ago", seconds / 3600),
+        seconds => format!("{} days ago", seconds / 86400),
+    }
+}
+
+fn result(info: &ReplayInfo) -> &'static str {
+    match (info.won, info.mode.as_str()) {
+        (None, _) => "left",
+        (Some(true), "local") => "player 1 won",
+        (Some(false), "local") => "player 2 won",
+        (Some(true), _) => "won",
+        (Some(false), _) => "lost",
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Replays {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " ↑ ↓ Select ".bold(),
+            " Enter. Watch ".bold(),
+            " ESC. Back ".bold(),
+        ]);
+        let block = Block::bordered()
+            .title(Line::from("Replays").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        if self.replays.is_empty() {
+            Paragraph::new("No replay yet, start the app with --record to save your matches")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        let height = area.height.saturating_sub(2) as usize;
+        let skip = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = self
+            .replays
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, (_, info))| {
+                let line = Line::from(format!(
+                    "{:<14} {} vs {} ({}) {}-{}, {}",
+                    ago(info.recorded_at),
+                    info.player,
+                    info.opponent,
+                    info.mode,
+                    info.score.0,
+                    info.score.1,
+                    result(info)
+                ));
+                match index == self.selected {
+                    true => line.reversed(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(Transition::Stay);
+        }
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.replays.len() => self.selected += 1,
+            KeyCode::Enter => {
+                if let Some((path, _)) = self.replays.get(self.selected) {
+                    let replay = Replay::load(path)?;
+                    return Ok(Transition::Push(Box::new(ReplayPlayer::new(replay))));
+                }
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        let Some(dir) = &infos.context.replay_dir else {
+            return Ok(());
+        };
+        self.replays.clear();
+        for path in replay::list(dir)? {
+            match replay::info(&path) {
+                Ok(info) => self.replays.push((path, info)),
+                Err(e) => warn!("skipped replay: {:#}", e),
+            }
+        }
+        self.selected = self.selected.min(self.replays.len().saturating_sub(1));
+        Ok(())
+    }
+}
+
+/// Recorded match drawn as it was played, with pause, seek and speed
+struct ReplayPlayer {
+    replay: Replay,
+    position: Duration,
+    /// Index in SPEEDS
+    speed: usize,
+    paused: bool,
+    played: Instant,
+}
+
+impl ReplayPlayer {
+    fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            position: Duration::ZERO,
+            speed: 1,
+            paused: false,
+            played: Instant::now(),
+        }
+    }
+}
+
+fn clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[async_trait(?Send)]
+impl Screen for ReplayPlayer {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let info = &self.replay.info;
+        let names = match info.mode.as_str() {
+            "local" => ["Player 1", "Player 2"],
+            _ => [info.player.as_str(), info.opponent.as_str()],
+        };
+        let controls = format!(
+            " {} / {}  x{}   Space. {}  ← → Seek  ↑ ↓ Speed  ESC. Back ",
+            clock(self.position),
+            clock(self.replay.duration()),
+            SPEEDS[self.speed],
+            if self.paused { "Play" } else { "Pause" },
+        );
+        display_played_game(
+            &self.replay.at(self.position),
+            &info.params,
+            names,
+            Some(&controls),
+            area,
+            buf,
+        );
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind == KeyEventKind::Release {
+            return Ok(Transition::Stay);
+        }
+        let duration = self.replay.duration();
+        match key.code {
+            KeyCode::Char(' ') => {
+                // played to the end, start over
+                if self.paused && self.position >= duration {
+                    self.position = Duration::ZERO;
+                }
+                self.paused = !self.paused;
+            }
+            KeyCode::Left => self.position = self.position.saturating_sub(SEEK),
+            KeyCode::Right => self.position = (self.position + SEEK).min(duration),
+            KeyCode::Up if self.speed + 1 < SPEEDS.len() => self.speed += 1,
+            KeyCode::Down => self.speed = self.speed.saturating_sub(1),
+            KeyCode::Home => self.position = Duration::ZERO,
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn update(&mut self, _infos: &mut Infos) -> Result<Transition> {
+        let now = Instant::now();
+        if !self.paused {
+            let elapsed = (now - self.played).mul_f64(SPEEDS[self.speed]);
+            self.position += elapsed;
+            if self.position >= self.replay.duration() {
+                self.position = self.replay.duration();
+                self.paused = true;
+            }
+        }
+        self.played = now;
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,163 @@
+use super::game::{display_played_game, start_tournament_match};
+use super::{Screen, Task, Transition};
+use crate::api::MatchStart;
+use crate::game::{GameStats, GameUpdate};
+use crate::infos::{GameParams, Infos};
+use crate::protocol::{self, GameFrame};
+use crate::smoothing::Smoother;
+use crate::spectate::Spectator;
+use crate::utils::should_exit;
+use anyhow::Result;
+use async_trait::async_trait;
+use crossterm::event::Event;
+use ratatui::{buffer::Buffer, layout::Rect};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// Match of other players, drawn as it is played; no key reaches the server
+pub(super) struct Spectating {
+    spectator: Spectator,
+    stats: GameStats,
+    smoother: Smoother,
+    params: GameParams,
+    /// Why the match stopped, shown over the last state
+    ended: Option<String>,
+    update: Option<GameUpdate>,
+    /// Watching from a tournament: our own match is joined as soon as it is announced
+    tournament: bool,
+    /// Borrowed from `Auth` while the screen is active, in a tournament only
+    receiver: Option<mpsc::Receiver<MatchStart>>,
+    start: Option<MatchStart>,
+    joining: Task<Result<Box<dyn Screen>>>,
+}
+
+impl Spectating {
+    pub(super) fn new(spectator: Spectator) -> Self {
+        Spectating {
+            spectator,
+            stats: GameStats::default(),
+            smoother: Smoother::default(),
+            params: GameParams::default(),
+            ended: None,
+            update: None,
+            tournament: false,
+            receiver: None,
+            start: None,
+            joining: Task::default(),
+        }
+    }
+    pub(super) fn in_tournament(spectator: Spectator) -> Self {
+        Spectating {
+            tournament: true,
+            ..Spectating::new(spectator)
+        }
+    }
+    fn winner_name(&self, id: u64) -> String {
+        let game = &self.spectator.game;
+        let names = &self.spectator.names;
+        match id {
+            id if id == game.player1_id => names[0].clone(),
+            id if id == game.player2_id => names[1].clone(),
+            id => format!("Player {}", id),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Spectating {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let [left, right] = &self.spectator.names;
+        let controls = match (&self.ended, self.joining.is_running()) {
+            (_, true) => " Your match is starting… ".to_string(),
+            (Some(end), false) => format!(" {}  ESC. Back ", end),
+            (None, false) => " Watching  ESC. Stop watching ".to_string(),
+        };
+        let stats = match self.ended {
+            Some(_) => self.stats,
+            None => self.smoother.sample(Instant::now()).unwrap_or(self.stats),
+        };
+        display_played_game(
+            &stats,
+            &self.params,
+            [left, right],
+            Some(&controls),
+            area,
+            buf,
+        );
+    }
+    async fn handle_event(&mut self, _infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        let (updates, ended) = (&mut self.spectator.updates, self.ended.is_some());
+        let update = async {
+            match ended {
+                true => std::future::pending().await,
+                false => updates.recv().await.unwrap_or(GameUpdate::Closed),
+            }
+        };
+        let receiver = &mut self.receiver;
+        let start = async {
+            match receiver.as_mut() {
+                Some(receiver) => receiver.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            update = update => self.update = Some(update),
+            start = start => match start {
+                Some(start) => self.start = Some(start),
+                None => self.receiver = None,
+            },
+            _ = self.joining.wait() => {}
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        if let Some(screen) = self.joining.take() {
+            return Ok(Transition::Replace(screen?));
+        }
+        if let Some(start) = self.start.take() {
+            let infos = infos.handle();
+            self.joining
+                .start(async move { start_tournament_match(&infos, start).await });
+        }
+        let ended = match self.update.take() {
+            None => return Ok(Transition::Stay),
+            Some(GameUpdate::State(received, bytes)) => match protocol::decode_state(&bytes)? {
+                GameFrame::State(stats) => {
+                    self.stats = stats;
+                    self.smoother.push(received, stats);
+                    return Ok(Transition::Stay);
+                }
+                _ => return Ok(Transition::Stay),
+            },
+            Some(GameUpdate::End(text)) => match protocol::decode_message(text.as_str())? {
+                GameFrame::Winner(id) => format!("{} won", self.winner_name(id)),
+                GameFrame::Error(message) => format!("Stopped by the server: {}", message),
+                GameFrame::State(_) => return Ok(Transition::Stay),
+            },
+            Some(GameUpdate::Closed) => "The server closed the match".to_string(),
+            Some(GameUpdate::Failed(e)) => format!("Connection lost: {}", e),
+        };
+        self.ended = Some(ended);
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        if self.tournament {
+            self.receiver = infos.authent.borrow_mut().receiver.take();
+        }
+        Ok(())
+    }
+    async fn on_exit(&mut self, infos: &mut Infos) {
+        let mut authent = infos.authent.borrow_mut();
+        if authent.receiver.is_none() {
+            authent.receiver = self.receiver.take();
+        }
+    }
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
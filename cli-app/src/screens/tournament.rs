@@ -0,0 +1,589 @@
+use super::bracket::BracketView;
+use super::chain::ChainResults;
+use super::game::start_tournament_match;
+use super::spectate::Spectating;
+use super::{Screen, Task, Transition, received};
+use crate::api::{ApiResult, MatchRecord, MatchStart, TournamentSummary};
+use crate::chat::{ChatStatus, LineKind};
+use crate::infos::Infos;
+use crate::spectate::{self, Spectator};
+use crate::tournament::{self, Bracket, Lobby, Phase, TournamentRoom, is_starting, winner};
+use crate::utils::should_exit;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::warn;
+
+/// Open tournaments come and go, the list is fetched again this often
+const REFRESH: Duration = Duration::from_secs(3);
+/// The histories the bracket is rebuilt from are fetched again this often until a champion is known
+const BRACKET_REFRESH: Duration = Duration::from_secs(5);
+
+/// Tournaments waiting for players, to join one or host a new one
+#[derive(Default)]
+pub(super) struct Tournaments {
+    open: Vec<TournamentSummary>,
+    selected: usize,
+    refreshed: Option<Instant>,
+    /// List being fetched in the background, `wait` picks it up
+    fetching: Option<oneshot::Receiver<ApiResult<Vec<TournamentSummary>>>>,
+    fetched: Option<ApiResult<Vec<TournamentSummary>>>,
+    /// Lobby being created or joined with the chat lines received before, `update` enters it
+    opening: Task<Result<(TournamentRoom, u64)>>,
+    /// Shown while `opening` runs
+    opening_text: String,
+}
+
+impl Tournaments {
+    /// Fetches the list on its own task, so a slow server does not freeze the screen
+    fn refresh(&mut self, infos: &Infos) {
+        self.refreshed = Some(Instant::now());
+        let api = infos.api();
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(api.tournaments().await);
+        });
+        self.fetching = Some(receiver);
+    }
+    /// Creating and joining wait for the lobby socket's first frame, up to `LOBBY_TIMEOUT`
+    fn open_room(
+        &mut self,
+        infos: &Infos,
+        text: String,
+        room: impl Future<Output = Result<TournamentRoom>> + 'static,
+    ) {
+        // the notices of the tournament may arrive before the room is open
+        let seen = received(infos);
+        self.opening_text = text;
+        self.opening.start(async move { Ok((room.await?, seen)) });
+    }
+    fn show(&mut self, open: Vec<TournamentSummary>) {
+        self.open = open;
+        self.selected = self.selected.min(self.open.len().saturating_sub(1));
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for Tournaments {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let instructions = match self.opening.is_running() {
+            true => Line::from(format!(" {} ", self.opening_text).bold().yellow()),
+            false => Line::from(vec![
+                " ↑ ↓ Select ".bold(),
+                " Enter. Join ".bold(),
+                " → Host ".bold(),
+                " ← Results ".bold(),
+                " ESC. Back ".bold(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(Line::from("Tournaments").bold().centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        if self.open.is_empty() {
+            Paragraph::new("No open tournament, → to host one")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        let height = area.height.saturating_sub(2) as usize;
+        let skip = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = self
+            .open
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, open)| {
+                let line = Line::from(
+                    format!(
+                        "{}'s tournament, {} joined",
+                        open.owner_name, open.player_count
+                    )
+                    .bold(),
+                );
+                match index == self.selected {
+                    true => line.reversed(),
+                    false => line,
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            return Ok(Transition::Pop);
+        }
+        let Event::Key(key) = event else {
+            return Ok(Transition::Stay);
+        };
+        if key.kind != KeyEventKind::Press || self.opening.is_running() {
+            return Ok(Transition::Stay);
+        }
+        let (me, token) = {
+            let authent = infos.authent.borrow();
+            (authent.id, authent.token.clone())
+        };
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.open.len() => self.selected += 1,
+            KeyCode::Left => return Ok(Transition::Push(Box::<ChainResults>::default())),
+            KeyCode::Right => {
+                let context = infos.context.clone();
+                let room = async move { tournament::create(&context, &token).await };
+                self.open_room(infos, "Creating the tournament…".to_string(), room);
+            }
+            KeyCode::Enter => {
+                if let Some(open) = self.open.get(self.selected) {
+                    let (context, id) = (infos.context.clone(), open.id.clone());
+                    let text = format!("Joining {}'s tournament…", open.owner_name);
+                    let room = async move { tournament::join(&context, &token, me, &id).await };
+                    self.open_room(infos, text, room);
+                }
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        let opening = &mut self.opening;
+        let Some(fetching) = self.fetching.as_mut() else {
+            return opening.wait().await;
+        };
+        tokio::select! {
+            fetched = fetching => {
+                self.fetching = None;
+                // a dropped task never answers, the next refresh tries again
+                self.fetched = fetched.ok();
+            }
+            _ = opening.wait() => {}
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        if let Some(opened) = self.opening.take() {
+            let (room, seen) = opened?;
+            let me = infos.authent.borrow().id;
+            return Ok(Transition::Push(Box::new(TournamentLobby::new(
+                room, me, seen,
+            ))));
+        }
+        match self.fetched.take() {
+            Some(Ok(open)) => self.show(open),
+            Some(Err(e)) => warn!("tournament list not refreshed: {}", e),
+            None => {}
+        }
+        if self.fetching.is_none() && self.refreshed.is_none_or(|at| at.elapsed() >= REFRESH) {
+            self.refresh(infos);
+        }
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        // the first list is awaited, a server that cannot list tournaments is an error
+        let open = infos.api().tournaments().await?;
+        self.show(open);
+        self.refreshed = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Players of a tournament until it starts, then its bracket
+///
+/// Each match is pushed over the lobby, which shows up again between the rounds
+pub(super) struct TournamentLobby {
+    room: TournamentRoom,
+    me: u64,
+    /// The first round was announced, the lobby socket stays quiet from then on
+    started: bool,
+    /// Named by the notice closing the tournament
+    winner: Option<String>,
+    /// Borrowed from `Auth` while the screen is active
+    receiver: Option<mpsc::Receiver<MatchStart>>,
+    start: Option<MatchStart>,
+    /// Chat lines already checked for the tournament notices
+    seen: u64,
+    bracket: Bracket,
+    /// Histories of the players, fetched since the start
+    games: Vec<MatchRecord>,
+    /// Highest game id when the tournament started, the games before it are not part of it
+    baseline: Option<u64>,
+    /// Opponent of our match in progress and its round
+    ongoing: Option<(u64, usize)>,
+    /// None when the bracket should be fetched again
+    refreshed: Option<Instant>,
+    /// Histories being fetched in the background, `wait` picks them up
+    fetching: Option<oneshot::Receiver<Result<Vec<MatchRecord>>>>,
+    fetched: Option<Result<Vec<MatchRecord>>>,
+    /// Start asked by the host, `tournament::start` waits for the server to close the lobby
+    starting: Task<Result<()>>,
+    /// Our next match, connected before its screen is pushed
+    connecting: Task<Result<Box<dyn Screen>>>,
+    /// Match of the bracket being joined as a spectator
+    watching: Task<Result<Spectator>>,
+    /// Matches watched so far, W goes through the ones in progress in turn
+    watched: usize,
+}
+
+/// Resolves on the next lobby update, never once the socket task is gone
+async fn changed(lobby: &mut watch::Receiver<Lobby>) {
+    if lobby.changed().await.is_err() {
+        std::future::pending().await
+    }
+}
+
+impl TournamentLobby {
+    fn new(room: TournamentRoom, me: u64, seen: u64) -> Self {
+        TournamentLobby {
+            room,
+            me,
+            started: false,
+            winner: None,
+            receiver: None,
+            start: None,
+            seen,
+            bracket: Bracket::default(),
+            games: vec![],
+            baseline: None,
+            ongoing: None,
+            refreshed: None,
+            fetching: None,
+            fetched: None,
+            starting: Task::default(),
+            connecting: Task::default(),
+            watching: Task::default(),
+            watched: 0,
+        }
+    }
+    fn my_name(&self) -> String {
+        let lobby = self.room.lobby.borrow();
+        let me = lobby.players.iter().find(|player| player.id == self.me);
+        me.map(|player| player.name.clone()).unwrap_or_default()
+    }
+    fn rebuild(&mut self) {
+        let players = self.room.lobby.borrow().players.clone();
+        let baseline = self.baseline.unwrap_or(u64::MAX);
+        self.bracket = Bracket::build(&players, &self.games, baseline, self.me, self.ongoing);
+    }
+    /// Fetches the players' histories on their own task, one request per player would
+    /// otherwise freeze the screen
+    fn refresh(&mut self, infos: &Infos) {
+        self.refreshed = Some(Instant::now());
+        let players = self.room.lobby.borrow().players.clone();
+        let api = infos.api();
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(tournament::games(&api, &players).await);
+        });
+        self.fetching = Some(receiver);
+    }
+    /// Rebuilds the bracket from fetched histories, the first ones after the start set the baseline
+    fn show(&mut self, games: Vec<MatchRecord>) {
+        self.games = games;
+        if self.baseline.is_none() {
+            self.baseline = Some(self.games.iter().map(|game| game.id).max().unwrap_or(0));
+        }
+        self.rebuild();
+    }
+    /// Looks for the start and the end of the tournament in the server notices
+    fn check_notices(&mut self, infos: &Infos) {
+        let authent = infos.authent.borrow();
+        let Some(log) = authent.chat_log.as_ref() else {
+            return;
+        };
+        let log = log.borrow();
+        for line in log.since(self.seen) {
+            // rounds ending are announced too, the bracket may have changed
+            if line.kind == LineKind::Server {
+                self.refreshed = None;
+            }
+            if is_starting(&line.text) {
+                self.started = true;
+            }
+            if let Some(name) = winner(&line.text) {
+                self.winner = Some(name.to_string());
+            }
+        }
+        self.seen = log.received;
+    }
+    fn over(&self, lobby: &Lobby) -> bool {
+        self.winner.is_some() || (self.started && lobby.closed)
+    }
+    /// Joins the next match of the tournament in progress without us
+    fn watch(&mut self, infos: &Infos) {
+        let players: Vec<u64> = self
+            .room
+            .lobby
+            .borrow()
+            .players
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        let (infos, me, turn) = (infos.handle(), self.me, self.watched);
+        self.watched += 1;
+        self.watching.start(async move {
+            let games = spectate::games_among(&infos.api(), &players, me).await?;
+            if games.is_empty() {
+                return Err(anyhow!("No match of the tournament is being played"));
+            }
+            let game = games[turn % games.len()].clone();
+            let token = infos.authent.borrow().token.clone();
+            spectate::watch(&infos.context, &token, game).await
+        });
+    }
+}
+
+#[async_trait(?Send)]
+impl Screen for TournamentLobby {
+    fn render(&self, _infos: &Infos, area: Rect, buf: &mut Buffer) {
+        let lobby = self.room.lobby.borrow();
+        let host = lobby.owner_id == self.me;
+        let status = match (&self.winner, self.over(&lobby), self.started) {
+            (Some(name), _, _) => format!("{} won the tournament!", name),
+            (None, true, _) => "The tournament is over".to_string(),
+            (None, false, true) if self.connecting.is_running() => {
+                "Joining your match…".to_string()
+            }
+            (None, false, true) if self.watching.is_running() => {
+                "Joining a match as a spectator…".to_string()
+            }
+            (None, false, true) => {
+                "Tournament started, your matches launch on their own".to_string()
+            }
+            (None, false, false) if self.starting.is_running() => {
+                "Starting the tournament…".to_string()
+            }
+            (None, false, false) if host => "Waiting for players, Enter to start".to_string(),
+            (None, false, false) => format!("Waiting for {} to start", lobby.owner_name),
+        };
+        let mut instructions = vec![];
+        if host && !self.started && !self.starting.is_running() {
+            instructions.push(" Enter. Start ".bold());
+        }
+        if self.started && !self.over(&lobby) && !self.watching.is_running() {
+            instructions.push(" W. Watch ".bold());
+        }
+        instructions.push(match self.started {
+            true => " ESC. Back ".bold(),
+            false => " ESC. Leave ".bold(),
+        });
+        let block = Block::bordered()
+            .title(
+                Line::from(format!(" {}'s tournament ", lobby.owner_name))
+                    .bold()
+                    .centered(),
+            )
+            .title_bottom(Line::from(instructions).centered())
+            .border_set(border::THICK);
+        if self.started {
+            let inner = block.inner(area);
+            block.render(area, buf);
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(2), Constraint::Fill(1)])
+                .split(inner);
+            Paragraph::new(Line::from(status.bold()))
+                .centered()
+                .render(layout[0], buf);
+            let me = self.my_name();
+            BracketView {
+                bracket: &self.bracket,
+                me: &me,
+                champion: self.winner.as_deref().or(self.bracket.champion()),
+            }
+            .render(layout[1], buf);
+            return;
+        }
+        let mut lines = vec![Line::from(status.bold()), Line::from("")];
+        for player in &lobby.players {
+            let mut text = format!("{} ({:.0})", player.name, player.elo);
+            if player.id == lobby.owner_id {
+                text.push_str(" host");
+            }
+            lines.push(match player.id == self.me {
+                true => Line::from(text.fg(Color::Yellow).bold()),
+                false => Line::from(text),
+            });
+        }
+        Paragraph::new(lines)
+            .centered()
+            .block(block)
+            .render(area, buf);
+    }
+    async fn handle_event(&mut self, infos: &mut Infos, event: Event) -> Result<Transition> {
+        if should_exit(&event)? {
+            if !self.started {
+                // the room is dropped with the screen, which closes the lobby socket
+                let leaving = self.room.leave(infos.api());
+                tokio::spawn(async move {
+                    if let Err(e) = leaving.await {
+                        warn!("leaving the tournament failed: {}", e);
+                    }
+                });
+            }
+            return Ok(Transition::Pop);
+        }
+        if let Event::Key(key) = event
+            && key.kind == KeyEventKind::Press
+            && key.code == KeyCode::Char('w')
+            && self.started
+            && !self.over(&self.room.lobby.borrow())
+            && !self.watching.is_running()
+            && !self.connecting.is_running()
+        {
+            self.watch(infos);
+        }
+        if let Event::Key(key) = event
+            && key.kind == KeyEventKind::Press
+            && key.code == KeyCode::Enter
+            && !self.started
+            && !self.starting.is_running()
+            && self.room.lobby.borrow().owner_id == self.me
+        {
+            let (api, room) = (infos.api(), self.room.id.clone());
+            self.starting
+                .start(async move { tournament::start(&api, &room).await });
+        }
+        Ok(Transition::Stay)
+    }
+    async fn wait(&mut self) {
+        let lobby = &mut self.room.lobby;
+        let fetching = &mut self.fetching;
+        let fetch = async {
+            match fetching.as_mut() {
+                Some(fetching) => fetching.await,
+                None => std::future::pending().await,
+            }
+        };
+        let (starting, connecting) = (&mut self.starting, &mut self.connecting);
+        let watching = &mut self.watching;
+        let tasks = async {
+            tokio::select! {
+                _ = starting.wait() => {}
+                _ = connecting.wait() => {}
+                _ = watching.wait() => {}
+            }
+        };
+        let mut fetched = None;
+        let start = match self.receiver.as_mut() {
+            Some(receiver) => tokio::select! {
+                start = receiver.recv() => Some(start),
+                result = fetch => {
+                    fetched = Some(result);
+                    None
+                }
+                _ = changed(lobby) => None,
+                _ = tasks => None,
+            },
+            None => tokio::select! {
+                result = fetch => {
+                    fetched = Some(result);
+                    None
+                }
+                _ = changed(lobby) => None,
+                _ = tasks => None,
+            },
+        };
+        if let Some(result) = fetched {
+            self.fetching = None;
+            // a dropped task never answers, the next refresh tries again
+            self.fetched = result.ok();
+        }
+        match start {
+            Some(Some(start)) => self.start = Some(start),
+            Some(None) => self.receiver = None,
+            None => {}
+        }
+    }
+    async fn update(&mut self, infos: &mut Infos) -> Result<Transition> {
+        let rejected = infos
+            .authent
+            .borrow()
+            .chat_status
+            .as_ref()
+            .is_some_and(|status| *status.borrow() == ChatStatus::Rejected);
+        if rejected {
+            return Err(anyhow!("Session expired, log in again"));
+        }
+        if let Some(started) = self.starting.take() {
+            started?;
+            self.started = true;
+        }
+        if let Some(screen) = self.connecting.take() {
+            return Ok(Transition::Push(screen?));
+        }
+        if let Some(spectator) = self.watching.take() {
+            return Ok(Transition::Push(Box::new(Spectating::in_tournament(
+                spectator?,
+            ))));
+        }
+        self.check_notices(infos);
+        let (phase, closed) = {
+            let lobby = self.room.lobby.borrow();
+            (lobby.phase, lobby.closed)
+        };
+        if phase != Phase::Waiting {
+            self.started = true;
+        }
+        if closed && !self.started {
+            return Err(anyhow!("The server closed the tournament lobby"));
+        }
+        if self.start.is_some() {
+            self.started = true;
+        }
+        match self.fetched.take() {
+            Some(Ok(games)) => self.show(games),
+            Some(Err(e)) => warn!("bracket not refreshed: {}", e),
+            None => {}
+        }
+        let due = match self.refreshed {
+            None => true,
+            Some(at) => self.winner.is_none() && at.elapsed() >= BRACKET_REFRESH,
+        };
+        if self.started && self.bracket.champion().is_none() && due && self.fetching.is_none() {
+            self.refresh(infos);
+        }
+        let Some(start) = self.start.take() else {
+            if self.receiver.is_none() {
+                return Err(anyhow!("Chat connection closed"));
+            }
+            return Ok(Transition::Stay);
+        };
+        self.ongoing = Some((start.opponent_id, self.bracket.reached(&self.my_name())));
+        self.rebuild();
+        let infos = infos.handle();
+        self.connecting
+            .start(async move { start_tournament_match(&infos, start).await });
+        Ok(Transition::Stay)
+    }
+    async fn on_enter(&mut self, infos: &mut Infos) -> Result<()> {
+        self.receiver = Some(
+            infos
+                .authent
+                .borrow_mut()
+                .receiver
+                .take()
+                .ok_or_else(|| anyhow!("receiver not initialized"))?,
+        );
+        // back from a match, its result is in the histories
+        self.refreshed = None;
+        Ok(())
+    }
+    async fn on_exit(&mut self, infos: &mut Infos) {
+        let mut authent = infos.authent.borrow_mut();
+        if authent.receiver.is_none() {
+            authent.receiver = self.receiver.take();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context as _, Result};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// JWT kept between launches, in a file only readable by the user
+pub(crate) struct Session {
+    path: Option<PathBuf>,
+}
+
+impl Session {
+    /// A session without a file (default context) is never persisted
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Session { path }
+    }
+    pub(crate) fn load(&self) -> Option<String> {
+        let token = std::fs::read_to_string(self.path.as_ref()?).ok()?;
+        let token = token.trim();
+        (!token.is_empty()).then(|| token.to_string())
+    }
+    ///Written to a temporary file first so a crash never leaves half a token
+    pub(crate) fn save(&self, token: &str) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            create_private_dir(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut file = private_file(&tmp)?;
+        file.write_all(token.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path).with_context(|| format!("writing {}", path.display()))
+    }
+    pub(crate) fn clear(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("removing {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One session per server so profiles do not overwrite each other
+pub(crate) fn session_file(data_dir: &Path, server: &str) -> PathBuf {
+    let name: String = server
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    data_dir.join("sessions").join(name)
+}
+
+fn create_private_dir(dir: &Path) -> Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("creating {}", dir.display()))
+}
+
+fn private_file(path: &Path) -> Result<std::fs::File> {
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("writing {}", path.display()))?;
+    // mode() only applies on creation, a leftover file keeps its permissions
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
@@ -0,0 +1,719 @@
+use crate::api::MatchStart;
+use crate::chat::ChatStatus;
+use crate::commands::{history, leaderboard, show_friends, show_user};
+use crate::context::Context;
+use crate::dm::recipient;
+use crate::duel::{accept, challenge, pending_duels};
+use crate::friends::{
+    get_friends, send_accept_friend_request, send_delete_friend_request, send_friend_request,
+};
+use crate::infos::{GameParams, Infos, send_post_game_request};
+use crate::login::{Auth, create_guest_session, login, resume_session, signup};
+use crate::screens::play_match;
+use anyhow::{Result, anyhow};
+use rustyline::completion::Completer;
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{ColorMode, Editor, ExternalPrinter, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{Duration, timeout};
+
+const PROMPT: &str = "pong> ";
+
+/// Usage and description of every command, in the order `help` lists them
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("help", "", "show this list"),
+    ("login", "<email> [2fa code]", "log in, the password is asked for"),
+    ("signup", "<username> <email>", "create an account and log in"),
+    ("guest", "", "log in with a new guest account"),
+    ("logout", "", "end the session"),
+    ("whoami", "", "show your profile"),
+    ("friends", "", "list your friends and pending requests"),
+    ("friend", "add|accept|remove <name>", "manage a friend"),
+    ("dm", "<name> <message>", "send a direct message"),
+    ("history", "[name]", "show the matches played by a user"),
+    ("leaderboard", "[count]", "show the best players by elo"),
+    ("queue", "", "wait for an online opponent, then play"),
+    ("duel", "list|invite|accept|decline [name]", "challenge a player"),
+    ("play", "", "play the match just found, an empty line does too"),
+    ("quit", "", "leave the shell"),
+];
+const FRIEND_ACTIONS: &[&str] = &["add", "accept", "remove"];
+const DUEL_ACTIONS: &[&str] = &["list", "invite", "accept", "decline"];
+
+/// What the prompt thread is asked to read
+enum Prompt {
+    Command,
+    Secret(&'static str),
+}
+
+enum Input {
+    Line(String),
+    /// Ctrl-C, the line is dropped
+    Interrupted,
+    /// Ctrl-D or stdin closed
+    Eof,
+}
+
+/// Completion of commands and, once `friends` was run, of friend names
+struct ShellHelper {
+    masking: bool,
+    names: Arc<Mutex<Vec<String>>>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.rfind(' ').map_or(0, |space| space + 1);
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+        let names = || self.names.lock().map(|n| n.clone()).unwrap_or_default();
+        let candidates: Vec<String> = match previous.as_slice() {
+            [] => COMMANDS.iter().map(|c| c.0.to_string()).collect(),
+            ["friend"] => FRIEND_ACTIONS.iter().map(|a| a.to_string()).collect(),
+            ["duel"] => DUEL_ACTIONS.iter().map(|a| a.to_string()).collect(),
+            ["friend", _] | ["duel", "invite" | "accept" | "decline"] | ["dm"] | ["history"] => {
+                names()
+            }
+            _ => vec![],
+        };
+        let matching = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect();
+        Ok((start, matching))
+    }
+}
+
+/// Hides what is typed while a password is read
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if self.masking {
+            Cow::Owned("*".repeat(line.chars().count()))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.masking && kind != CmdKind::MoveCursor
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Line editor running on its own thread, rustyline blocks while reading
+struct LineReader {
+    prompts: std::sync::mpsc::Sender<Prompt>,
+    lines: mpsc::UnboundedReceiver<Input>,
+    /// Prints above the line being edited, None when the terminal does not support it
+    printer: Option<Box<dyn ExternalPrinter + Send>>,
+}
+
+impl LineReader {
+    fn spawn(history: Option<PathBuf>, names: Arc<Mutex<Vec<String>>>) -> Result<Self> {
+        let mut editor: Editor<ShellHelper, FileHistory> = Editor::new()?;
+        editor.set_helper(Some(ShellHelper {
+            masking: false,
+            names,
+        }));
+        editor.set_auto_add_history(false);
+        if let Some(path) = &history {
+            let _ = editor.load_history(path);
+        }
+        let printer = editor
+            .create_external_printer()
+            .ok()
+            .map(|printer| Box::new(printer) as Box<dyn ExternalPrinter + Send>);
+        let (prompts, requests) = std::sync::mpsc::channel();
+        let (sender, lines) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while let Ok(prompt) = requests.recv() {
+                if sender.send(read(&mut editor, prompt)).is_err() {
+                    break;
+                }
+            }
+            if let Some(path) = &history {
+                if let Some(dir) = path.parent() {
+                    let _ = std::fs::create_dir_all(dir);
+                }
+                let _ = editor.save_history(path);
+            }
+        });
+        Ok(LineReader {
+            prompts,
+            lines,
+            printer,
+        })
+    }
+    /// Asks the thread for a line, the answer comes on `lines`
+    fn request(&self, prompt: Prompt) -> Result<()> {
+        self.prompts
+            .send(prompt)
+            .map_err(|_| anyhow!("the prompt is closed"))
+    }
+    /// Reads a line without showing it
+    async fn secret(&mut self, prompt: &'static str) -> Result<String> {
+        self.request(Prompt::Secret(prompt))?;
+        match self.lines.recv().await {
+            Some(Input::Line(line)) => Ok(line),
+            _ => Err(anyhow!("cancelled")),
+        }
+    }
+    /// Shown right away, even while a line is being edited
+    fn notify(&mut self, message: String) {
+        match &mut self.printer {
+            Some(printer) => {
+                let _ = printer.print(message);
+            }
+            None => println!("{}", message),
+        }
+    }
+}
+
+fn read(editor: &mut Editor<ShellHelper, FileHistory>, prompt: Prompt) -> Input {
+    let result = match prompt {
+        Prompt::Command => {
+            let result = editor.readline(PROMPT);
+            if let Ok(line) = &result
+                && !line.trim().is_empty()
+            {
+                let _ = editor.add_history_entry(line.as_str());
+            }
+            result
+        }
+        Prompt::Secret(prompt) => {
+            if let Some(helper) = editor.helper_mut() {
+                helper.masking = true;
+            }
+            editor.set_color_mode(ColorMode::Forced);
+            let result = editor.readline(prompt);
+            editor.set_color_mode(ColorMode::Enabled);
+            if let Some(helper) = editor.helper_mut() {
+                helper.masking = false;
+            }
+            result
+        }
+    };
+    match result {
+        Ok(line) => Input::Line(line),
+        Err(ReadlineError::Interrupted) => Input::Interrupted,
+        Err(_) => Input::Eof,
+    }
+}
+
+/// Waits for a match notification on the chat socket, forever when there is none
+async fn next_match(receiver: &mut Option<mpsc::Receiver<MatchStart>>) -> Option<MatchStart> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for the chat socket to change state, forever when there is none
+///
+/// #Returns
+/// None once the socket is closed for good
+async fn next_status(status: &mut Option<watch::Receiver<ChatStatus>>) -> Option<ChatStatus> {
+    let Some(status) = status else {
+        return std::future::pending().await;
+    };
+    status.changed().await.ok()?;
+    Some(status.borrow_and_update().clone())
+}
+
+struct Shell {
+    infos: Infos,
+    reader: LineReader,
+    /// Taken from `Auth` so matches can be announced while a line is edited
+    receiver: Option<mpsc::Receiver<MatchStart>>,
+    status: Option<watch::Receiver<ChatStatus>>,
+    /// Announced while the prompt was waiting, started by an empty line or `play`
+    pending: Option<MatchStart>,
+    names: Arc<Mutex<Vec<String>>>,
+    exit: bool,
+}
+
+/// Line oriented alternative to the full screen interface
+pub(crate) async fn run(context: Rc<Context>, history: Option<PathBuf>) -> Result<()> {
+    let names = Arc::new(Mutex::new(vec![]));
+    let reader = LineReader::spawn(history, names.clone())?;
+    let auth = Rc::new(RefCell::new(Auth::default()));
+    let resumed = timeout(Duration::from_secs(5), resume_session(context.clone())).await;
+    let logged_in = if let Ok(Some(credentials)) = resumed {
+        auth.borrow_mut().set_credentials(credentials);
+        true
+    } else {
+        false
+    };
+    let mut shell = Shell {
+        infos: Infos::new(context, auth),
+        reader,
+        receiver: None,
+        status: None,
+        pending: None,
+        names,
+        exit: false,
+    };
+    if logged_in {
+        shell.joined()?;
+        shell.whoami().await?;
+    } else {
+        println!("Not logged in, use `login`, `signup` or `guest`. `help` lists the commands.");
+    }
+    while !shell.exit {
+        let Some(line) = shell.next_line().await? else {
+            break;
+        };
+        if let Err(e) = shell.execute(&line).await {
+            println!("error: {}", e);
+        }
+    }
+    // the stored token is kept so the next launch resumes the session
+    shell.infos.logout(false).await;
+    Ok(())
+}
+
+impl Shell {
+    /// Next command line, announcing matches that arrive meanwhile
+    async fn next_line(&mut self) -> Result<Option<String>> {
+        self.reader.request(Prompt::Command)?;
+        loop {
+            tokio::select! {
+                input = self.reader.lines.recv() => {
+                    return Ok(match input {
+                        Some(Input::Line(line)) => Some(line),
+                        Some(Input::Interrupted) => Some(String::new()),
+                        Some(Input::Eof) | None => None,
+                    });
+                }
+                start = next_match(&mut self.receiver) => match start {
+                    Some(start) => {
+                        self.reader
+                            .notify(format!("{} match found, press Enter or type `play`", start.mode));
+                        self.pending = Some(start);
+                    }
+                    None => {
+                        self.receiver = None;
+                        self.reader.notify("Chat connection closed".to_string());
+                    }
+                },
+                status = next_status(&mut self.status) => match status {
+                    Some(ChatStatus::Connected) => self.reader.notify("Chat reconnected".to_string()),
+                    Some(status) => self.reader.notify(status.describe().unwrap_or_default()),
+                    None => self.status = None,
+                },
+            }
+        }
+    }
+    async fn execute(&mut self, line: &str) -> Result<()> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return match self.pending.is_some() {
+                true => self.play().await,
+                false => Ok(()),
+            };
+        };
+        let args: Vec<&str> = words.collect();
+        match (command, args.as_slice()) {
+            ("help", []) => help(),
+            ("play", []) => self.play().await?,
+            ("quit" | "exit", []) => self.exit = true,
+            ("login", [email]) => self.login(email, "").await?,
+            ("login", [email, totp]) => self.login(email, totp).await?,
+            ("signup", [username, email]) => self.signup(username, email).await?,
+            ("guest", []) => {
+                self.logged_out()?;
+                let credentials = create_guest_session(self.infos.context.clone()).await?;
+                self.infos.authent.borrow_mut().set_credentials(credentials);
+                self.joined()?;
+                self.whoami().await?;
+            }
+            ("logout", []) => {
+                self.logged_in()?;
+                self.infos.logout(true).await;
+                self.receiver = None;
+                self.status = None;
+                self.pending = None;
+                println!("Logged out");
+            }
+            ("whoami", []) => {
+                self.logged_in()?;
+                self.whoami().await?;
+            }
+            ("friends", []) => {
+                self.logged_in()?;
+                let me = self.infos.authent.borrow().id;
+                let friends = get_friends(&self.infos.api(), me).await?;
+                if let Ok(mut names) = self.names.lock() {
+                    *names = friends.iter().map(|friend| friend.name.clone()).collect();
+                }
+                show_friends(&friends)?.print(false);
+            }
+            ("friend", [action, name]) => {
+                self.logged_in()?;
+                let api = self.infos.api();
+                let answer = match *action {
+                    "add" => send_friend_request(&api, name).await?,
+                    "accept" => send_accept_friend_request(&api, name).await?,
+                    "remove" => send_delete_friend_request(&api, name).await?,
+                    _ => return Err(usage("friend")),
+                };
+                println!("{}", answer);
+            }
+            ("dm", [name, _, ..]) => {
+                self.logged_in()?;
+                let message = dm_message(line);
+                let me = self.infos.authent.borrow().id;
+                let api = self.infos.api();
+                let to = recipient(&api, me, name).await?;
+                let answer = api.send_dm(&to.name, message).await?;
+                println!("{}", answer.message);
+            }
+            ("history", []) => {
+                self.logged_in()?;
+                let api = self.infos.api();
+                let name = api.profile_from_token().await?.name;
+                history(&api, &name).await?.print(false);
+            }
+            ("history", [name]) => history(&self.infos.context.api(), name).await?.print(false),
+            ("leaderboard", []) => leaderboard(&self.infos.context.api(), 10)
+                .await?
+                .print(false),
+            ("leaderboard", [count]) => {
+                let count = count.parse().map_err(|_| usage("leaderboard"))?;
+                leaderboard(&self.infos.context.api(), count)
+                    .await?
+                    .print(false)
+            }
+            ("queue", []) => self.queue().await?,
+            ("duel", ["list"]) => self.duels().await?,
+            ("duel", [action, name]) => self.duel(action, name).await?,
+            _ => {
+                return Err(match COMMANDS.iter().any(|c| c.0 == command) {
+                    true => usage(command),
+                    false => anyhow!("unknown command `{}`, try `help`", command),
+                });
+            }
+        }
+        Ok(())
+    }
+    fn logged_in(&self) -> Result<()> {
+        match self.infos.authent.borrow().token.is_empty() {
+            true => Err(anyhow!("not logged in")),
+            false => Ok(()),
+        }
+    }
+    fn logged_out(&self) -> Result<()> {
+        match self.infos.authent.borrow().token.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("already logged in, `logout` first")),
+        }
+    }
+    /// Keeps the chat socket's match notifications once a session started
+    fn joined(&mut self) -> Result<()> {
+        self.status = self.infos.authent.borrow().chat_status.clone();
+        self.receiver = self.infos.authent.borrow_mut().receiver.take();
+        match self.receiver {
+            Some(_) => Ok(()),
+            None => Err(anyhow!("receiver not initialized")),
+        }
+    }
+    async fn whoami(&self) -> Result<()> {
+        let profile = self.infos.api().profile_from_token().await?;
+        show_user(profile)?.print(false);
+        Ok(())
+    }
+    async fn login(&mut self, email: &str, totp: &str) -> Result<()> {
+        self.logged_out()?;
+        let password = self.reader.secret("password: ").await?;
+        let form = (email.to_string(), password, totp.to_string());
+        let credentials = login(self.infos.context.clone(), form).await?;
+        self.infos.authent.borrow_mut().set_credentials(credentials);
+        self.joined()?;
+        self.whoami().await
+    }
+    async fn signup(&mut self, username: &str, email: &str) -> Result<()> {
+        self.logged_out()?;
+        let password = self.reader.secret("password: ").await?;
+        let form = (username.to_string(), password, email.to_string());
+        let credentials = signup(self.infos.context.clone(), form).await?;
+        self.infos.authent.borrow_mut().set_credentials(credentials);
+        self.joined()?;
+        self.whoami().await
+    }
+    /// Waits for the chat socket to announce the match, Ctrl-C gives up
+    ///
+    /// #Parameters
+    /// - requeue: mode to queue for again after a reconnection, the server drops
+    ///   players from the queue with their socket. None for a duel, which is cancelled
+    async fn wait_match(&mut self, requeue: Option<&str>) -> Result<Option<MatchStart>> {
+        println!("Waiting for the match, Ctrl-C to cancel");
+        loop {
+            tokio::select! {
+                start = next_match(&mut self.receiver) => return match start {
+                    Some(start) => Ok(Some(start)),
+                    None => {
+                        self.receiver = None;
+                        Err(anyhow!("Chat connection closed"))
+                    }
+                },
+                status = next_status(&mut self.status) => match (status, requeue) {
+                    (Some(ChatStatus::Connected), Some(mode)) => {
+                        send_post_game_request(&self.infos, mode).await?;
+                        println!("Chat reconnected, back in the queue");
+                    }
+                    (Some(ChatStatus::Reconnecting { .. }), None) => {
+                        return Err(anyhow!("Chat connection lost, the duel was cancelled"));
+                    }
+                    (Some(status), _) if status != ChatStatus::Connected => {
+                        println!("{}", status.describe().unwrap_or_default());
+                        if status == ChatStatus::Rejected {
+                            return Err(anyhow!("Chat connection closed"));
+                        }
+                    }
+                    (None, _) => self.status = None,
+                    _ => {}
+                },
+                _ = tokio::signal::ctrl_c() => return Ok(None),
+            }
+        }
+    }
+    /// Starts the match announced while the prompt was waiting
+    async fn play(&mut self) -> Result<()> {
+        let start = self
+            .pending
+            .take()
+            .ok_or_else(|| anyhow!("no match found yet, `queue` or `duel` first"))?;
+        play_match(&mut self.infos, start, GameParams::default()).await
+    }
+    async fn queue(&mut self) -> Result<()> {
+        self.logged_in()?;
+        let params = send_post_game_request(&self.infos, "online").await?;
+        match self.wait_match(Some("online")).await {
+            Ok(Some(start)) => play_match(&mut self.infos, start, params).await,
+            result => {
+                self.infos.api().remove_from_queue().await?;
+                println!("Left the queue");
+                result.map(|_| ())
+            }
+        }
+    }
+    async fn duels(&self) -> Result<()> {
+        self.logged_in()?;
+        let me = self.infos.authent.borrow().id;
+        let duels = pending_duels(&self.infos.api(), me).await?;
+        if duels.is_empty() {
+            println!("No pending duel");
+        }
+        for duel in duels {
+            match duel.incoming {
+                true => println!("from\t{}", duel.name),
+                false => println!("to\t{}", duel.name),
+            }
+        }
+        Ok(())
+    }
+    async fn duel(&mut self, action: &str, name: &str) -> Result<()> {
+        self.logged_in()?;
+        let api = self.infos.api();
+        let me = self.infos.authent.borrow().id;
+        match action {
+            "invite" => {
+                let invited = challenge(&api, me, name).await?;
+                println!("Invite sent");
+                println!("The match is announced here once {} accepts", invited.name);
+            }
+            "decline" => {
+                let id = api.profile_by_name(name).await?.id;
+                println!("{}", api.decline_duel(id).await?.message);
+            }
+            "accept" => {
+                accept(&api, api.profile_by_name(name).await?.id).await?;
+                if let Some(start) = self.wait_match(None).await? {
+                    play_match(&mut self.infos, start, GameParams::default()).await?;
+                }
+            }
+            _ => return Err(usage("duel")),
+        }
+        Ok(())
+    }
+}
+
+fn help() {
+    for (name, arguments, description) in COMMANDS {
+        let usage = format!("{} {}", name, arguments);
+        println!("  {:<40} {}", usage.trim_end(), description);
+    }
+}
+
+/// Text of a `dm <name> <message>` line, its inner spacing kept
+fn dm_message(line: &str) -> &str {
+    line.trim_start()
+        .split_once(char::is_whitespace)
+        .and_then(|(_, rest)| rest.trim_start().split_once(char::is_whitespace))
+        .map_or("", |(_, message)| message.trim())
+}
+
+fn usage(command: &str) -> anyhow::Error {
+    match COMMANDS.iter().find(|c| c.0 == command) {
+        Some((name, arguments, _)) => anyhow!("usage: {} {}", name, arguments),
+        None => anyhow!("unknown command `{}`", command),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::join;
+    use crate::mock::MockServer;
+    use rustyline::history::DefaultHistory;
+
+    fn complete(line: &str, names: &[&str]) -> (usize, Vec<String>) {
+        let helper = ShellHelper {
+            masking: false,
+            names: Arc::new(Mutex::new(names.iter().map(|n| n.to_string()).collect())),
+        };
+        let history = DefaultHistory::new();
+        helper
+            .complete(line, line.len(), &rustyline::Context::new(&history))
+            .unwrap()
+    }
+
+    fn shell(context: Rc<Context>) -> Shell {
+        let names = Arc::new(Mutex::new(vec![]));
+        Shell {
+            infos: Infos::new(context, Rc::new(RefCell::new(Auth::default()))),
+            reader: LineReader::spawn(None, names.clone()).unwrap(),
+            receiver: None,
+            status: None,
+            pending: None,
+            names,
+            exit: false,
+        }
+    }
+
+    #[test]
+    fn completes_commands_actions_and_names() {
+        let names = ["bob", "bea", "carol"];
+        assert_eq!(complete("he", &names), (0, vec!["help".to_string()]));
+        assert_eq!(complete("  fri", &names).1, vec!["friends", "friend"]);
+        assert_eq!(
+            complete("friend a", &names),
+            (7, vec!["add".to_string(), "accept".to_string()])
+        );
+        assert_eq!(complete("friend add b", &names).1, vec!["bob", "bea"]);
+        assert_eq!(complete("duel invite c", &names).1, vec!["carol"]);
+        assert_eq!(complete("dm ", &names).1, names);
+        // the message of a dm and unknown commands are not completed
+        assert!(complete("dm bob b", &names).1.is_empty());
+        assert!(complete("leaderboard 1", &names).1.is_empty());
+    }
+
+    #[test]
+    fn dm_keeps_the_spacing_of_the_message() {
+        assert_eq!(dm_message("dm bob hi"), "hi");
+        assert_eq!(
+            dm_message("  dm   bob  see   you\tlater  "),
+            "see   you\tlater"
+        );
+        assert_eq!(dm_message("dm bob"), "");
+    }
+
+    #[tokio::test]
+    async fn wrong_arguments_print_the_usage() {
+        let server = MockServer::start().await;
+        let mut shell = shell(server.context());
+        let error = |result: Result<()>| result.err().unwrap().to_string();
+        assert_eq!(
+            error(shell.execute("leaderboard x").await),
+            "usage: leaderboard [count]"
+        );
+        assert_eq!(
+            error(shell.execute("friend add").await),
+            "usage: friend add|accept|remove <name>"
+        );
+        assert_eq!(
+            error(shell.execute("login").await),
+            "usage: login <email> [2fa code]"
+        );
+        assert_eq!(
+            error(shell.execute("dm bob").await),
+            "usage: dm <name> <message>"
+        );
+        assert_eq!(
+            error(shell.execute("frobnicate").await),
+            "unknown command `frobnicate`, try `help`"
+        );
+        assert_eq!(error(shell.execute("whoami").await), "not logged in");
+        assert!(error(shell.execute("play").await).starts_with("no match found yet"));
+        shell.execute("   ").await.unwrap();
+        shell.execute("quit").await.unwrap();
+        assert!(shell.exit);
+    }
+
+    #[tokio::test]
+    async fn lines_typed_after_a_match_was_found_still_run() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let mut bob_room = join(&server.context(), &server.token(bob)).await.unwrap();
+        assert!(server.wait_chat(bob, true).await);
+        let mut shell = shell(server.context());
+        let form = (
+            "alice@mail.com".to_string(),
+            "secret".to_string(),
+            String::new(),
+        );
+        let credentials = login(shell.infos.context.clone(), form).await.unwrap();
+        shell
+            .infos
+            .authent
+            .borrow_mut()
+            .set_credentials(credentials);
+        shell.joined().unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        shell.pending = Some(MatchStart {
+            game_id: "found".to_string(),
+            opponent_id: bob,
+            player_side: 1,
+            mode: "online".to_string(),
+        });
+        shell.execute("dm bob  rematch   later ").await.unwrap();
+        timeout(
+            Duration::from_secs(5),
+            bob_room
+                .log
+                .wait_for(|log| log.conversation("alice").is_some()),
+        )
+        .await
+        .expect("direct message timed out")
+        .unwrap();
+        let text = bob_room.log.borrow().conversation("alice").unwrap().lines[0]
+            .text
+            .clone();
+        assert_eq!(text, "rematch   later");
+        assert_eq!(shell.pending.as_ref().unwrap().game_id, "found");
+    }
+}
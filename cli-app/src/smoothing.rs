@@ -0,0 +1,158 @@
+use crate::game::GameStats;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Steps per second of the server's GameInstance, the speeds are per step
+pub(crate) const SERVER_RATE: f64 = 200.0;
+/// Interval between two states sent by GameServer.ts
+const SEND_INTERVAL: Duration = Duration::from_micros(16_667);
+/// The field is drawn this far in the past, so a late state usually still has a successor
+const DELAY: Duration = Duration::from_millis(50);
+/// Longest the ball keeps moving past the newest state before it waits for the server
+const MAX_EXTRAPOLATION: Duration = Duration::from_millis(100);
+/// States kept, well over `DELAY`
+const CAPACITY: usize = 16;
+
+/// Jitter buffer of the states received, drawn at a steady pace whatever their arrival
+///
+/// States are placed on a timeline one send interval apart: a burst is spread back out, and a
+/// state arriving too late for the timeline moves it forward
+#[derive(Default)]
+pub(crate) struct Smoother {
+    states: VecDeque<(Instant, GameStats)>,
+}
+
+impl Smoother {
+    pub(crate) fn push(&mut self, arrival: Instant, stats: GameStats) {
+        let at = match self.states.back() {
+            Some((last, _)) => (*last + SEND_INTERVAL).clamp(
+                arrival.checked_sub(DELAY).unwrap_or(arrival),
+                arrival + DELAY,
+            ),
+            None => arrival,
+        };
+        if self.states.len() == CAPACITY {
+            self.states.pop_front();
+        }
+        self.states.push_back((at, stats));
+    }
+    /// Positions to draw at `now`, the scores are the newest ones
+    pub(crate) fn sample(&self, now: Instant) -> Option<GameStats> {
+        let (newest_at, newest) = *self.states.back()?;
+        let at = now.checked_sub(DELAY).unwrap_or(now);
+        let mut drawn = match self.states.iter().position(|(time, _)| *time > at) {
+            // ahead of the newest state, the ball goes on at its last speed
+            None => extrapolate(&newest, (at - newest_at).min(MAX_EXTRAPOLATION)),
+            Some(0) => self.states[0].1,
+            Some(next) => {
+                let (from_at, from) = self.states[next - 1];
+                let (to_at, to) = self.states[next];
+                let share = (at - from_at).as_secs_f32() / (to_at - from_at).as_secs_f32();
+                interpolate(&from, &to, share)
+            }
+        };
+        drawn.player1_score = newest.player1_score;
+        drawn.player2_score = newest.player2_score;
+        Some(drawn)
+    }
+}
+
+fn lerp(from: f32, to: f32, share: f32) -> f32 {
+    from + (to - from) * share
+}
+
+fn interpolate(from: &GameStats, to: &GameStats, share: f32) -> GameStats {
+    // a point was scored in between, the ball was put back in the middle
+    let served = (from.player1_score, from.player2_score) != (to.player1_score, to.player2_score);
+    let ball = match served {
+        true => (to.ball_x, to.ball_y),
+        false => (
+            lerp(from.ball_x, to.ball_x, share),
+            lerp(from.ball_y, to.ball_y, share),
+        ),
+    };
+    GameStats {
+        left_y: lerp(from.left_y, to.left_y, share),
+        right_y: lerp(from.right_y, to.right_y, share),
+        ball_x: ball.0,
+        ball_y: ball.1,
+        ..*to
+    }
+}
+
+/// Moves the ball by its speed, bouncing off the top and bottom of the field
+fn extrapolate(stats: &GameStats, elapsed: Duration) -> GameStats {
+    let steps = (elapsed.as_secs_f64() * SERVER_RATE) as f32;
+    let y = (stats.ball_y + stats.speed_y * steps).rem_euclid(200.0);
+    GameStats {
+        ball_x: (stats.ball_x + stats.speed_x * steps).clamp(0.0, 100.0),
+        ball_y: if y > 100.0 { 200.0 - y } else { y },
+        ..*stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball(x: f32, y: f32, speed: (f32, f32)) -> GameStats {
+        GameStats {
+            ball_x: x,
+            ball_y: y,
+            speed_x: speed.0,
+            speed_y: speed.1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn states_are_interpolated_behind_their_arrival() {
+        let start = Instant::now();
+        let mut smoother = Smoother::default();
+        smoother.push(start, ball(10.0, 50.0, (0.5, 0.0)));
+        smoother.push(start + SEND_INTERVAL, ball(20.0, 50.0, (0.5, 0.0)));
+        let halfway = start + DELAY + SEND_INTERVAL / 2;
+        let drawn = smoother.sample(halfway).unwrap();
+        assert!((drawn.ball_x - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn bursts_are_spread_back_out() {
+        let start = Instant::now();
+        let mut smoother = Smoother::default();
+        for x in [10.0, 20.0, 30.0] {
+            smoother.push(start, ball(x, 50.0, (0.5, 0.0)));
+        }
+        let drawn = smoother.sample(start + DELAY + SEND_INTERVAL).unwrap();
+        assert!((drawn.ball_x - 20.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn ball_goes_on_between_late_states() {
+        let start = Instant::now();
+        let mut smoother = Smoother::default();
+        smoother.push(start, ball(50.0, 98.0, (0.5, 0.5)));
+        // 10ms past the state, 2 server steps
+        let drawn = smoother
+            .sample(start + DELAY + Duration::from_millis(10))
+            .unwrap();
+        assert!((drawn.ball_x - 51.0).abs() < 0.01);
+        // bounced off the bottom
+        assert!((drawn.ball_y - 99.0).abs() < 0.01);
+        // and stops after a while without news
+        let drawn = smoother.sample(start + Duration::from_secs(5)).unwrap();
+        assert!((drawn.ball_x - 60.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn served_ball_is_not_dragged_across_the_field() {
+        let start = Instant::now();
+        let mut smoother = Smoother::default();
+        smoother.push(start, ball(99.0, 50.0, (0.5, 0.0)));
+        let mut served = ball(50.0, 50.0, (-0.5, 0.0));
+        served.player1_score = 1;
+        smoother.push(start + SEND_INTERVAL, served);
+        let drawn = smoother.sample(start + DELAY + SEND_INTERVAL / 2).unwrap();
+        assert_eq!((drawn.ball_x, drawn.player1_score), (50.0, 1));
+    }
+}
@@ -0,0 +1,220 @@
+//! Watching a match played by others, on the server's read-only spectator socket
+//!
+//! The server sends spectators the side 1 state: player 1 on the left, player 2 on the right.
+//! Nothing is ever written to the socket and closing it does not end the match.
+
+use crate::Context;
+use crate::api::{ApiClient, OngoingGame};
+use crate::game::{GameUpdate, WsStream};
+use crate::protocol::{self, GameFrame};
+use anyhow::{Result, anyhow, bail};
+use futures_util::StreamExt;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::protocol::Message};
+use tracing::{debug, info};
+
+/// The server answers right away: with a state, the winner or a refusal
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages queued between the reading task and the screen, about a second of states
+const FRAME_QUEUE: usize = 64;
+
+/// Match being watched, its messages arrive from the socket's task
+pub(crate) struct Spectator {
+    pub(crate) game: OngoingGame,
+    /// Players 1 and 2, drawn on the left and on the right
+    pub(crate) names: [String; 2],
+    pub(crate) updates: mpsc::Receiver<GameUpdate>,
+    /// Dropping the spectator closes the socket
+    _closer: oneshot::Sender<()>,
+}
+
+/// Game `player` is playing, None when they are not in one
+pub(crate) async fn game_of(api: &ApiClient, player: u64) -> Result<Option<OngoingGame>> {
+    let games = api.ongoing_games().await?;
+    Ok(games.into_iter().find(|game| game.has(player)))
+}
+
+/// Games played between `players` that `me` is not part of, in the server's order
+pub(crate) async fn games_among(
+    api: &ApiClient,
+    players: &[u64],
+    me: u64,
+) -> Result<Vec<OngoingGame>> {
+    let mut games = api.ongoing_games().await?;
+    games.retain(|game| {
+        !game.has(me) && players.contains(&game.player1_id) && players.contains(&game.player2_id)
+    });
+    Ok(games)
+}
+
+/// Joins `game` as a spectator
+///
+/// #Errors
+/// Returns an error if the server refuses the spectator or the match is already over
+pub(crate) async fn watch(context: &Context, token: &str, game: OngoingGame) -> Result<Spectator> {
+    let api = context.api().with_token(token);
+    let names = [
+        api.profile_by_id(game.player1_id).await?.name,
+        api.profile_by_id(game.player2_id).await?.name,
+    ];
+    let url = format!("wss://{}/api/spectate/{}", context.location, game.game_id);
+    let mut request = url.into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert("Cookie", format!("jwt_session={}", token).parse()?);
+    let (mut ws_stream, _) =
+        connect_async_tls_with_config(request, None, false, Some(context.connector())).await?;
+    let first = timeout(FIRST_FRAME_TIMEOUT, first_state(&mut ws_stream))
+        .await
+        .map_err(|_| anyhow!("The server did not send the match"))??;
+    info!(game_id = game.game_id, "watching");
+    let (sender, updates) = mpsc::channel(FRAME_QUEUE);
+    let _ = sender.try_send(first);
+    let (closer, close_signal) = oneshot::channel();
+    tokio::spawn(read_socket(ws_stream, sender, close_signal));
+    Ok(Spectator {
+        game,
+        names,
+        updates,
+        _closer: closer,
+    })
+}
+
+/// #Returns
+/// The first state of the match, an error for a refusal or a match already over
+async fn first_state(ws_stream: &mut WsStream) -> Result<GameUpdate> {
+    loop {
+        match ws_stream.next().await {
+            Some(Ok(Message::Binary(frame))) => {
+                return Ok(GameUpdate::State(Instant::now(), frame));
+            }
+            Some(Ok(Message::Text(text))) => match protocol::decode_message(text.as_str())? {
+                GameFrame::Error(message) => bail!("The match cannot be watched: {}", message),
+                GameFrame::Winner(_) => bail!("The match is already over"),
+                GameFrame::State(_) => bail!("state frame sent as text"),
+            },
+            Some(Ok(Message::Close(_))) | None => bail!("The server closed the match"),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    }
+}
+
+/// Forwards the messages of the server until it closes the socket or the spectator leaves
+async fn read_socket(
+    mut ws_stream: WsStream,
+    sender: mpsc::Sender<GameUpdate>,
+    mut close_signal: oneshot::Receiver<()>,
+) {
+    loop {
+        let update = tokio::select! {
+            _ = &mut close_signal => break,
+            message = ws_stream.next() => match message {
+                Some(Ok(Message::Binary(frame))) => GameUpdate::State(Instant::now(), frame),
+                Some(Ok(Message::Text(text))) => GameUpdate::End(text),
+                Some(Ok(Message::Close(frame))) => {
+                    debug!(?frame, "spectator socket closed by the server");
+                    break;
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => GameUpdate::Failed(e.into()),
+                None => break,
+            },
+        };
+        let failed = matches!(update, GameUpdate::Failed(_));
+        if sender.send(update).await.is_err() || failed {
+            break;
+        }
+    }
+    let _ = sender.send(GameUpdate::Closed).await;
+    let _ = ws_stream.close(None).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, state_frame, winner_frame};
+
+    #[tokio::test]
+    async fn match_is_watched_until_the_winner() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        server.play("watched", (bob, carol));
+        server.script_game(vec![
+            state_frame(40.0, 60.0, 50.0, 25.0, 1.0, -1.0, 2, 1),
+            winner_frame(carol),
+        ]);
+        let (context, token) = (server.context(), server.token(alice));
+        let api = context.api().with_token(&token);
+        assert!(game_of(&api, alice).await.unwrap().is_none());
+        let game = game_of(&api, bob).await.unwrap().unwrap();
+        assert_eq!(game.game_id, "watched");
+        let mut spectator = watch(&context, &token, game).await.unwrap();
+        assert_eq!(spectator.names, ["bob", "carol"]);
+        let Some(GameUpdate::State(_, bytes)) = spectator.updates.recv().await else {
+            panic!("expected a state frame");
+        };
+        let GameFrame::State(stats) = protocol::decode_state(&bytes).unwrap() else {
+            panic!("expected a state");
+        };
+        assert_eq!((stats.player1_score, stats.player2_score), (2, 1));
+        let Some(GameUpdate::End(text)) = spectator.updates.recv().await else {
+            panic!("expected the winner");
+        };
+        assert_eq!(
+            protocol::decode_message(text.as_str()).unwrap(),
+            GameFrame::Winner(carol)
+        );
+        assert!(matches!(
+            spectator.updates.recv().await,
+            Some(GameUpdate::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn refusals_and_finished_matches_are_errors() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let carol = server.add_user("carol", "carol@mail.com", "secret");
+        let (context, token) = (server.context(), server.token(alice));
+        let unknown = OngoingGame {
+            game_id: "gone".to_string(),
+            player1_id: bob,
+            player2_id: carol,
+        };
+        let error = watch(&context, &token, unknown).await.err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The match cannot be watched: Game not found"
+        );
+        server.play("over", (bob, carol));
+        server.script_game(vec![winner_frame(bob)]);
+        let game = game_of(&context.api().with_token(&token), carol)
+            .await
+            .unwrap()
+            .unwrap();
+        let error = watch(&context, &token, game).await.err().unwrap();
+        assert_eq!(error.to_string(), "The match is already over");
+    }
+
+    #[tokio::test]
+    async fn tournament_matches_leave_ours_and_strangers_out() {
+        let server = MockServer::start().await;
+        let ids: Vec<u64> = ["alice", "bob", "carol", "dave", "erin", "frank"]
+            .iter()
+            .map(|name| server.add_user(name, &format!("{}@mail.com", name), "secret"))
+            .collect();
+        server.play("ours", (ids[0], ids[1]));
+        server.play("outside", (ids[2], ids[4]));
+        server.play("theirs", (ids[2], ids[3]));
+        let api = server.context().api().with_token(&server.token(ids[0]));
+        let games = games_among(&api, &ids[..4], ids[0]).await.unwrap();
+        let listed: Vec<&str> = games.iter().map(|game| game.game_id.as_str()).collect();
+        assert_eq!(listed, ["theirs"]);
+    }
+}
@@ -0,0 +1,411 @@
+use anyhow::{Context as _, Result, anyhow};
+use ring::digest::{SHA256, digest};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// How the server certificate is trusted, shared by the HTTP client and the websockets
+#[derive(Clone, Debug, Default)]
+pub(crate) enum TrustPolicy {
+    /// Public web PKI roots only
+    #[default]
+    System,
+    /// PEM bundle of trusted certificates, such as the one generated by the stack
+    CaBundle(PathBuf),
+    /// Only accept the certificate with this SHA-256 fingerprint
+    Pinned(Fingerprint),
+    /// Public roots, or remember the first certificate seen in a known_hosts file
+    Tofu(PathBuf),
+    /// Accept anything, only reachable through `--insecure`
+    Insecure,
+}
+
+/// SHA-256 fingerprint of a DER certificate, written as `openssl x509 -fingerprint -sha256` does
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    pub(crate) fn of(cert: &CertificateDer<'_>) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(digest(&SHA256, cert.as_ref()).as_ref());
+        Fingerprint(bytes)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = anyhow::Error;
+
+    /// Accepts hex with or without colons, optionally prefixed by `sha256:`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix("sha256:").unwrap_or(s);
+        let hex: String = s.chars().filter(|c| *c != ':').collect();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(anyhow!("'{}' is not a SHA-256 fingerprint", s));
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| anyhow!("'{}' is not a SHA-256 fingerprint", s))?;
+        }
+        Ok(Fingerprint(bytes))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex: Vec<String> = self.0.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(f, "{}", hex.join(":"))
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Builds the rustls configuration enforcing `policy` for `server` (host:port)
+pub(crate) fn client_config(policy: &TrustPolicy, server: &str) -> Result<Arc<ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let check = match policy {
+        TrustPolicy::System => Check::Roots {
+            webpki: webpki_verifier(public_roots(), &provider)?,
+            anchors: Vec::new(),
+        },
+        TrustPolicy::CaBundle(path) => {
+            let anchors = load_bundle(path)?;
+            let mut roots = RootCertStore::empty();
+            let (added, _) = roots.add_parsable_certificates(anchors.iter().cloned());
+            if added == 0 {
+                return Err(anyhow!("no usable certificate in {}", path.display()));
+            }
+            Check::Roots {
+                webpki: webpki_verifier(roots, &provider)?,
+                anchors,
+            }
+        }
+        TrustPolicy::Pinned(fingerprint) => Check::Pinned(*fingerprint),
+        TrustPolicy::Tofu(path) => Check::Tofu {
+            webpki: webpki_verifier(public_roots(), &provider)?,
+            known_hosts: KnownHosts {
+                path: path.clone(),
+                host: server.to_string(),
+            },
+        },
+        TrustPolicy::Insecure => Check::Insecure,
+    };
+    let verifier = TrustVerifier {
+        check,
+        algorithms: provider.signature_verification_algorithms,
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+fn public_roots() -> RootCertStore {
+    RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    }
+}
+
+fn webpki_verifier(
+    roots: RootCertStore,
+    provider: &Arc<CryptoProvider>,
+) -> Result<Arc<WebPkiServerVerifier>> {
+    Ok(WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?)
+}
+
+fn load_bundle(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("reading certificates from {}", path.display()))
+}
+
+#[derive(Debug)]
+enum Check {
+    /// webpki chain validation, a certificate listed verbatim in the bundle is trusted as is
+    /// since the stack's self-signed one has no subjectAltName and is flagged as a CA
+    Roots {
+        webpki: Arc<WebPkiServerVerifier>,
+        anchors: Vec<CertificateDer<'static>>,
+    },
+    Pinned(Fingerprint),
+    Tofu {
+        webpki: Arc<WebPkiServerVerifier>,
+        known_hosts: KnownHosts,
+    },
+    Insecure,
+}
+
+#[derive(Debug)]
+struct TrustVerifier {
+    check: Check,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for TrustVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.check {
+            Check::Roots { webpki, anchors } => {
+                if anchors.iter().any(|anchor| anchor == end_entity) {
+                    return Ok(ServerCertVerified::assertion());
+                }
+                webpki.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    ocsp_response,
+                    now,
+                )
+            }
+            Check::Pinned(expected) => {
+                let found = Fingerprint::of(end_entity);
+                if found == *expected {
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(rustls::Error::General(format!(
+                        "server certificate {} does not match the pinned fingerprint {}",
+                        found, expected
+                    )))
+                }
+            }
+            Check::Tofu {
+                webpki,
+                known_hosts,
+            } => {
+                if let Ok(verified) = webpki.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    ocsp_response,
+                    now,
+                ) {
+                    return Ok(verified);
+                }
+                known_hosts
+                    .check(Fingerprint::of(end_entity))
+                    .map(|_| ServerCertVerified::assertion())
+                    .map_err(|e| rustls::Error::General(e.to_string()))
+            }
+            Check::Insecure => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// SSH-style `host fingerprint` lines, `#` starts a comment
+#[derive(Debug)]
+struct KnownHosts {
+    path: PathBuf,
+    host: String,
+}
+
+impl KnownHosts {
+    /// Accepts a known certificate, records an unknown host and rejects a changed certificate
+    fn check(&self, found: Fingerprint) -> Result<()> {
+        match self.lookup()? {
+            Some(known) if known == found => Ok(()),
+            Some(known) => Err(anyhow!(
+                "certificate of {} changed: expected {}, got {}. \
+                 Remove its line from {} if this is expected",
+                self.host,
+                known,
+                found,
+                self.path.display()
+            )),
+            None => self.record(found),
+        }
+    }
+
+    fn lookup(&self) -> Result<Option<Fingerprint>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", self.path.display())),
+        };
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            if let (Some(host), Some(fingerprint)) = (fields.next(), fields.next())
+                && host == self.host
+            {
+                return fingerprint.parse().map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn record(&self, fingerprint: Fingerprint) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("writing {}", self.path.display()))?;
+        writeln!(file, "{} {}", self.host, fingerprint)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::context::Context;
+    use crate::mock::{MockServer, temp_path};
+
+    fn context(server: &MockServer, trust: TrustPolicy) -> Context {
+        let settings = Settings {
+            server: server.address(),
+            trust,
+            ..Settings::default()
+        };
+        Context::new(&settings).unwrap()
+    }
+
+    #[test]
+    fn fingerprint_round_trips() {
+        let hex = "AB".repeat(32);
+        let fingerprint: Fingerprint = format!("sha256:{}", hex.to_lowercase()).parse().unwrap();
+        assert_eq!(fingerprint.to_string().replace(':', ""), hex);
+        assert_eq!(
+            fingerprint.to_string().parse::<Fingerprint>().unwrap(),
+            fingerprint
+        );
+        assert!("AB:CD".parse::<Fingerprint>().is_err());
+    }
+
+    #[tokio::test]
+    async fn wrong_pin_is_rejected() {
+        let server = MockServer::start().await;
+        let settings = Settings {
+            server: server.address(),
+            trust: TrustPolicy::Pinned("00".repeat(32).parse().unwrap()),
+            ..Settings::default()
+        };
+        let context = Context::new(&settings).unwrap();
+        let error = context.api().create_guest().await.err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("does not match the pinned fingerprint"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn first_certificate_is_trusted_and_remembered() {
+        let server = MockServer::start().await;
+        let dir = temp_path("tofu");
+        let known_hosts = dir.join("known_hosts");
+        let trust = TrustPolicy::Tofu(known_hosts.clone());
+        let line = format!("{} {}\n", server.address(), server.fingerprint());
+        context(&server, trust.clone())
+            .api()
+            .create_guest()
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&known_hosts).unwrap(), line);
+        // a new client finds the recorded certificate
+        context(&server, trust).api().create_guest().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&known_hosts).unwrap(), line);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn changed_certificate_is_rejected() {
+        let server = MockServer::start().await;
+        let dir = temp_path("tofu-changed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let known_hosts = dir.join("known_hosts");
+        let recorded = format!(
+            "# pong servers\nother:8443 {}\n{} {}\n",
+            server.fingerprint(),
+            server.address(),
+            "00".repeat(32)
+        );
+        std::fs::write(&known_hosts, &recorded).unwrap();
+        let error = context(&server, TrustPolicy::Tofu(known_hosts.clone()))
+            .api()
+            .create_guest()
+            .await
+            .err()
+            .unwrap();
+        let expected = format!("certificate of {} changed", server.address());
+        assert!(error.to_string().contains(&expected), "{}", error);
+        assert_eq!(std::fs::read_to_string(&known_hosts).unwrap(), recorded);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn ca_bundle_trusts_only_its_certificates() {
+        let server = MockServer::start().await;
+        let dir = temp_path("ca-bundle");
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("transcendence.crt");
+        std::fs::write(&bundle, server.certificate()).unwrap();
+        context(&server, TrustPolicy::CaBundle(bundle.clone()))
+            .api()
+            .create_guest()
+            .await
+            .unwrap();
+        let other = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(&bundle, other.cert.pem()).unwrap();
+        let error = context(&server, TrustPolicy::CaBundle(bundle))
+            .api()
+            .create_guest()
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            error.to_string().contains("invalid peer certificate"),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
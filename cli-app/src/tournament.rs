@@ -0,0 +1,559 @@
+use crate::Context;
+use crate::api::{ApiClient, LobbyFrame, LobbyPlayer, MatchRecord, urlencode};
+use crate::game::WsStream;
+use anyhow::{Result, anyhow, bail};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::protocol::Message};
+use tracing::{debug, info, warn};
+
+/// The server registers a new tournament on the blockchain before answering
+const LOBBY_TIMEOUT: Duration = Duration::from_secs(30);
+/// The start route never answers, the list of open tournaments tells whether it worked
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+const STARTING_NOTICE: &str = "The tournament is starting!";
+const WINNER_NOTICE: (&str, &str) = (
+    "The tournament is over! Congratulations to ",
+    " for winning!",
+);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Phase {
+    #[default]
+    Waiting,
+    Started,
+    Finished,
+}
+
+impl From<u8> for Phase {
+    fn from(state: u8) -> Self {
+        match state {
+            0 => Phase::Waiting,
+            1 => Phase::Started,
+            _ => Phase::Finished,
+        }
+    }
+}
+
+/// Last known state of a tournament lobby
+///
+/// The server only sends it while waiting for players, the start and the matches are
+/// announced on the chat
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Lobby {
+    pub(crate) owner_id: u64,
+    pub(crate) owner_name: String,
+    pub(crate) players: Vec<LobbyPlayer>,
+    pub(crate) phase: Phase,
+    /// The server closed the socket, at the end of the tournament or when we were removed
+    pub(crate) closed: bool,
+}
+
+impl From<LobbyFrame> for Lobby {
+    fn from(frame: LobbyFrame) -> Self {
+        Lobby {
+            owner_id: frame.owner_id.unwrap_or_default(),
+            owner_name: frame.owner_name,
+            players: frame.players,
+            phase: frame.state.into(),
+            closed: false,
+        }
+    }
+}
+
+/// Handles of a lobby websocket, dropping them closes it and leaves a waiting lobby
+pub(crate) struct TournamentRoom {
+    pub(crate) id: String,
+    pub(crate) lobby: watch::Receiver<Lobby>,
+    _closer: oneshot::Sender<()>,
+}
+
+impl TournamentRoom {
+    /// The server ignores it once the tournament started, the player is then eliminated
+    /// by forfeit
+    ///
+    /// The request does not borrow the room, it can run on its own task once the room is dropped
+    pub(crate) fn leave(
+        &self,
+        api: ApiClient,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let id = self.id.clone();
+        async move {
+            api.leave_tournament(&id).await?;
+            Ok(())
+        }
+    }
+}
+
+/// One match of a round, its players are unknown until the server announces or records it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Pairing {
+    pub(crate) players: [Option<String>; 2],
+    /// Points of each side once played
+    pub(crate) score: Option<(u64, u64)>,
+    /// Side that advanced to the next round
+    pub(crate) winner: Option<usize>,
+}
+
+impl Pairing {
+    fn side(&self, name: &str) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|player| player.as_deref() == Some(name))
+    }
+    pub(crate) fn winner_name(&self) -> Option<&str> {
+        self.players[self.winner?].as_deref()
+    }
+}
+
+/// Rounds of a tournament, from the first one to the final
+///
+/// The server keeps the draw to itself, the bracket is rebuilt from the games the players
+/// recorded since the start
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Bracket {
+    pub(crate) rounds: Vec<Vec<Pairing>>,
+}
+
+/// Bots all share one id, so a repeated id is a bot
+fn bots(players: &[LobbyPlayer]) -> Vec<u64> {
+    let mut seen = vec![];
+    let mut bots = vec![];
+    for player in players {
+        match seen.contains(&player.id) {
+            true if !bots.contains(&player.id) => bots.push(player.id),
+            true => {}
+            false => seen.push(player.id),
+        }
+    }
+    bots
+}
+
+impl Bracket {
+    /// #Parameters
+    /// `games`: history of the players, only the ones after `baseline` between two of them count
+    /// `ongoing`: opponent of the match `me` is playing, and the round it belongs to
+    pub(crate) fn build(
+        players: &[LobbyPlayer],
+        games: &[MatchRecord],
+        baseline: u64,
+        me: u64,
+        ongoing: Option<(u64, usize)>,
+    ) -> Bracket {
+        let size = players.len().max(2).next_power_of_two();
+        let depth = size.trailing_zeros() as usize;
+        let bots = bots(players);
+        let name = |id: u64| {
+            players
+                .iter()
+                .find(|player| player.id == id)
+                .map(|player| player.name.clone())
+        };
+        let playing = |id: u64| players.iter().any(|player| player.id == id);
+        let mut games: Vec<&MatchRecord> = games
+            .iter()
+            .filter(|game| {
+                game.id > baseline && playing(game.player1_id) && playing(game.player2_id)
+            })
+            .collect();
+        games.sort_by_key(|game| game.id);
+        games.dedup_by_key(|game| game.id);
+        let mut rounds = vec![vec![]; depth];
+        // rounds played by each human, the nth game of a player belongs to the nth round
+        let mut reached: HashMap<u64, usize> = HashMap::new();
+        for game in games {
+            let humans: Vec<u64> = [game.player1_id, game.player2_id]
+                .into_iter()
+                .filter(|id| !bots.contains(id))
+                .collect();
+            let Some(round) = humans
+                .iter()
+                .map(|id| reached.get(id).copied().unwrap_or(0))
+                .max()
+            else {
+                continue;
+            };
+            if round >= depth {
+                continue;
+            }
+            rounds[round].push(Pairing {
+                players: [name(game.player1_id), name(game.player2_id)],
+                score: Some((game.score1, game.score2)),
+                winner: Some((game.winner_id == game.player2_id) as usize),
+            });
+            for id in humans {
+                reached.insert(id, round + 1);
+            }
+        }
+        if let Some((opponent, round)) = ongoing
+            && round < depth
+            && reached.get(&me).copied().unwrap_or(0) == round
+        {
+            rounds[round].push(Pairing {
+                players: [name(me), name(opponent)],
+                ..Pairing::default()
+            });
+        }
+        for (index, round) in rounds.iter_mut().enumerate() {
+            round.resize(size >> (index + 1), Pairing::default());
+        }
+        arrange(&mut rounds);
+        Bracket { rounds }
+    }
+    /// Rounds `name` already played
+    pub(crate) fn reached(&self, name: &str) -> usize {
+        self.rounds
+            .iter()
+            .flatten()
+            .filter(|pairing| pairing.score.is_some() && pairing.side(name).is_some())
+            .count()
+    }
+    pub(crate) fn champion(&self) -> Option<&str> {
+        self.rounds.last()?.first()?.winner_name()
+    }
+}
+
+/// Orders each round after the next one, the two matches feeding a pairing sit side by side
+fn arrange(rounds: &mut [Vec<Pairing>]) {
+    for index in (0..rounds.len().saturating_sub(1)).rev() {
+        let (before, after) = rounds.split_at_mut(index + 1);
+        let (round, next) = (&mut before[index], &after[0]);
+        let mut slots: Vec<Option<Pairing>> = vec![None; round.len()];
+        let mut rest = vec![];
+        for pairing in round.drain(..) {
+            let slot = pairing.winner_name().and_then(|winner| {
+                next.iter()
+                    .enumerate()
+                    .find_map(|(i, later)| later.side(winner).map(|side| 2 * i + side))
+            });
+            match slot {
+                Some(slot) if slots[slot].is_none() => slots[slot] = Some(pairing),
+                _ => rest.push(pairing),
+            }
+        }
+        let mut rest = rest.into_iter();
+        *round = slots
+            .into_iter()
+            .map(|slot| slot.or_else(|| rest.next()).unwrap_or_default())
+            .collect();
+    }
+}
+
+/// Games of the tournament players, oldest first
+///
+/// Bots are left out, their games against humans are in the humans' history
+pub(crate) async fn games(api: &ApiClient, players: &[LobbyPlayer]) -> Result<Vec<MatchRecord>> {
+    let bots = bots(players);
+    let mut games = vec![];
+    for player in players.iter().filter(|player| !bots.contains(&player.id)) {
+        games.extend(api.history(&player.name).await?);
+    }
+    games.sort_by_key(|game| game.id);
+    games.dedup_by_key(|game| game.id);
+    Ok(games)
+}
+
+/// Whether this chat notice is the server announcing the first round
+pub(crate) fn is_starting(notice: &str) -> bool {
+    notice.starts_with(STARTING_NOTICE)
+}
+
+/// #Returns
+/// The name of the winner, when this chat notice closes a tournament
+pub(crate) fn winner(notice: &str) -> Option<&str> {
+    notice
+        .strip_prefix(WINNER_NOTICE.0)?
+        .strip_suffix(WINNER_NOTICE.1)
+}
+
+async fn connect(context: &Context, token: &str, route: &str) -> Result<WsStream> {
+    let url = format!("wss://{}/api/tournament/{}", context.location, route);
+    let mut request = url.into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert("Cookie", format!("jwt_session={}", token).parse()?);
+    let (ws_stream, _) =
+        connect_async_tls_with_config(request, None, false, Some(context.connector())).await?;
+    Ok(ws_stream)
+}
+
+/// #Returns
+/// The next lobby frame, an error when the server refused the socket or closed it
+async fn next_frame(ws_stream: &mut WsStream) -> Result<LobbyFrame> {
+    loop {
+        match ws_stream.next().await {
+            Some(Ok(Message::Text(text))) => {
+                let frame: LobbyFrame = serde_json::from_str(text.as_str())?;
+                if let Some(error) = frame.error {
+                    bail!("{}", error);
+                }
+                return Ok(frame);
+            }
+            Some(Ok(Message::Close(_))) | None => bail!("The server closed the lobby"),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    }
+}
+
+/// Creates a tournament owned by us
+///
+/// The server sends nothing when it refuses, for instance when we already are in a lobby
+pub(crate) async fn create(context: &Context, token: &str) -> Result<TournamentRoom> {
+    let mut ws_stream = connect(context, token, "create").await?;
+    let frame = timeout(LOBBY_TIMEOUT, next_frame(&mut ws_stream))
+        .await
+        .map_err(|_| anyhow!("The tournament was not created, you may already be in one"))??;
+    let id = frame
+        .lobby_id
+        .clone()
+        .ok_or_else(|| anyhow!("The server did not name the tournament"))?;
+    info!(lobby_id = id, "tournament created");
+    Ok(follow(ws_stream, id, frame.into()))
+}
+
+/// Joins the tournament `lobby_id` as the player `me`
+///
+/// The server sends nothing when it refuses, the first update listing us confirms it
+pub(crate) async fn join(
+    context: &Context,
+    token: &str,
+    me: u64,
+    lobby_id: &str,
+) -> Result<TournamentRoom> {
+    let route = format!("join?lobbyId={}", urlencode(lobby_id));
+    let mut ws_stream = connect(context, token, &route).await?;
+    let joined = async {
+        loop {
+            let frame = next_frame(&mut ws_stream).await?;
+            if frame.players.iter().any(|player| player.id == me) {
+                return Ok::<_, anyhow::Error>(frame);
+            }
+        }
+    };
+    let frame = timeout(LOBBY_TIMEOUT, joined).await.map_err(|_| {
+        anyhow!("Could not join the tournament, it may have started or you may already be in one")
+    })??;
+    info!(lobby_id, "tournament joined");
+    Ok(follow(ws_stream, lobby_id.to_string(), frame.into()))
+}
+
+/// Starts the tournament, bots fill the bracket up to a power of two
+///
+/// The server never answers and ignores the requests of the other players, the tournament
+/// started once it left the list of open ones
+pub(crate) async fn start(api: &ApiClient, lobby_id: &str) -> Result<()> {
+    if let Ok(sent) = timeout(START_TIMEOUT, api.start_tournament(lobby_id)).await {
+        sent?;
+    }
+    if api.tournaments().await?.iter().any(|t| t.id == lobby_id) {
+        bail!("The tournament did not start, only its host can start it");
+    }
+    info!(lobby_id, "tournament started");
+    Ok(())
+}
+
+/// Keeps the lobby state up to date until the server closes the socket or the room is dropped
+fn follow(mut ws_stream: WsStream, id: String, lobby: Lobby) -> TournamentRoom {
+    let (sender, receiver) = watch::channel(lobby);
+    let (closer, mut close_signal) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        loop {
+            let msg = tokio::select! {
+                msg = ws_stream.next() => msg,
+                _ = &mut close_signal => {
+                    let _ = ws_stream.close(None).await;
+                    debug!("lobby socket closed");
+                    return;
+                }
+            };
+            match msg {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<LobbyFrame>(&text) {
+                    Ok(frame) if frame.message == "UPDATE" => {
+                        sender.send_replace(frame.into());
+                    }
+                    Ok(frame) => debug!(message = frame.message, "lobby frame ignored"),
+                    Err(e) => warn!("unexpected lobby frame: {}", e),
+                },
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    warn!("lobby connection lost: {}", e);
+                    break;
+                }
+            }
+        }
+        info!("lobby closed by the server");
+        sender.send_modify(|lobby| lobby.closed = true);
+    });
+    TournamentRoom {
+        id,
+        lobby: receiver,
+        _closer: closer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn notices_are_recognized() {
+        assert!(is_starting("The tournament is starting! Good luck!"));
+        assert_eq!(
+            winner("The tournament is over! Congratulations to bob for winning!"),
+            Some("bob")
+        );
+        assert_eq!(winner("Round finished! The next round is starting"), None);
+    }
+
+    fn player(id: u64, name: &str) -> LobbyPlayer {
+        LobbyPlayer {
+            id,
+            name: name.to_string(),
+            elo: 1000.0,
+        }
+    }
+
+    fn game(id: u64, players: (u64, u64), score: (u64, u64)) -> MatchRecord {
+        MatchRecord {
+            id,
+            player1_id: players.0,
+            player2_id: players.1,
+            winner_id: match score.0 > score.1 {
+                true => players.0,
+                false => players.1,
+            },
+            score1: score.0,
+            score2: score.1,
+            played_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn bracket_follows_the_recorded_games() {
+        let players = [
+            player(1, "alice"),
+            player(2, "bob"),
+            player(3, "carol"),
+            player(4, "dave"),
+        ];
+        let games = [
+            game(5, (1, 2), (5, 0)),
+            game(11, (2, 4), (1, 5)),
+            game(12, (1, 3), (5, 3)),
+        ];
+        let bracket = Bracket::build(&players, &games, 10, 1, Some((4, 1)));
+        let semis = &bracket.rounds[0];
+        // dave plays the final on the right, his semi goes below alice's
+        assert_eq!(semis[0].winner_name(), Some("alice"));
+        assert_eq!(semis[1].score, Some((1, 5)));
+        let final_match = &bracket.rounds[1][0];
+        assert_eq!(final_match.players[1].as_deref(), Some("dave"));
+        assert_eq!(final_match.score, None);
+        assert_eq!(bracket.reached("alice"), 1);
+        assert_eq!(bracket.champion(), None);
+        let games = [games[1].clone(), games[2].clone(), game(13, (1, 4), (2, 5))];
+        let bracket = Bracket::build(&players, &games, 10, 1, Some((4, 1)));
+        assert_eq!(bracket.champion(), Some("dave"));
+    }
+
+    #[test]
+    fn bots_share_an_id_and_unplayed_pairings_stay_unknown() {
+        let players = [
+            player(1, "alice"),
+            player(2, "bob"),
+            player(3, "carol"),
+            player(9, "bot"),
+            player(9, "bot"),
+            player(9, "bot"),
+        ];
+        let games = [game(1, (1, 9), (5, 2)), game(2, (2, 9), (5, 4))];
+        let bracket = Bracket::build(&players, &games, 0, 3, Some((9, 0)));
+        assert_eq!(bracket.rounds.len(), 3);
+        assert_eq!(bracket.rounds[0].len(), 4);
+        let known = bracket.rounds[0]
+            .iter()
+            .filter(|pairing| pairing.players[0].is_some())
+            .count();
+        assert_eq!(known, 3);
+        assert_eq!(bracket.rounds[1], vec![Pairing::default(); 2]);
+    }
+
+    #[tokio::test]
+    async fn players_join_and_leave_a_lobby() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let (alice_token, bob_token) = (server.token(alice), server.token(bob));
+        let context = server.context();
+        let mut hosted = create(&context, &alice_token).await.unwrap();
+        assert_eq!(hosted.lobby.borrow().owner_id, alice);
+        let api = context.api().with_token(&bob_token);
+        let open = api.tournaments().await.unwrap();
+        assert_eq!(
+            (open[0].id.as_str(), open[0].player_count),
+            (hosted.id.as_str(), 1)
+        );
+        let joined = join(&context, &bob_token, bob, &hosted.id).await.unwrap();
+        assert_eq!(joined.lobby.borrow().owner_name, "alice");
+        hosted
+            .lobby
+            .wait_for(|lobby| lobby.players.len() == 2)
+            .await
+            .unwrap();
+        joined.leave(api.clone()).await.unwrap();
+        hosted
+            .lobby
+            .wait_for(|lobby| lobby.players.len() == 1)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_the_host_starts_and_matches_come_through_the_chat() {
+        let server = MockServer::start().await;
+        let alice = server.add_user("alice", "alice@mail.com", "secret");
+        let bob = server.add_user("bob", "bob@mail.com", "secret");
+        let (alice_token, bob_token) = (server.token(alice), server.token(bob));
+        let context = server.context();
+        let mut alice_chat = chat::join(&context, &alice_token).await.unwrap();
+        assert!(server.wait_chat(alice, true).await);
+        let hosted = create(&context, &alice_token).await.unwrap();
+        let _joined = join(&context, &bob_token, bob, &hosted.id).await.unwrap();
+        let bob_api = context.api().with_token(&bob_token);
+        assert!(start(&bob_api, &hosted.id).await.is_err());
+        let alice_api = context.api().with_token(&alice_token);
+        start(&alice_api, &hosted.id).await.unwrap();
+        let announced = alice_chat.receiver.recv().await.unwrap();
+        assert_eq!(
+            (announced.opponent_id, announced.mode.as_str()),
+            (bob, "online")
+        );
+        assert!(alice_api.tournaments().await.unwrap().is_empty());
+        {
+            let log = alice_chat.log.borrow();
+            assert!(log.lines.iter().any(|line| is_starting(&line.text)));
+        }
+        let players = hosted.lobby.borrow().players.clone();
+        // a match of theirs from before the tournament stays out of the bracket
+        server.record_game((alice, bob), (5, 0));
+        let baseline = games(&alice_api, &players)
+            .await
+            .unwrap()
+            .iter()
+            .map(|g| g.id)
+            .max()
+            .unwrap_or(0);
+        server.record_game((alice, bob), (3, 5));
+        let played = games(&alice_api, &players).await.unwrap();
+        assert_eq!(played.len(), 2);
+        let bracket = Bracket::build(&players, &played, baseline, alice, Some((bob, 0)));
+        assert_eq!(bracket.champion(), Some("bob"));
+    }
+}
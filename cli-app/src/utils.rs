@@ -1,7 +1,6 @@
-use crate::Context;
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use crossterm::event::{Event, KeyCode, KeyModifiers};
-use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 pub(crate) const LOGO: &str = r#"
   ██████╗  ██████╗ ███╗   ██╗ ██████╗ 
@@ -12,35 +11,31 @@ pub(crate) const LOGO: &str = r#"
   ╚═╝      ╚═════╝ ╚═╝  ╚═══╝ ╚═════╝ 
   "#;
 
-#[derive(Clone, Copy, PartialEq, Default)]
-pub(crate) enum CurrentScreen {
-    #[default]
-    FirstScreen,
-    Welcome,
-    Login,
-    SignUp,
-    GameChoice,
-    SocialLife,
-    CreateGame,
-    StartGame,
-    PlayGame,
-    EndGame,
-    FriendsDisplay,
-    AddFriend,
-    DeleteFriend,
-    ErrorScreen,
+/// Text cursor toggling every half second
+pub(crate) struct Blink {
+    on: bool,
+    since: Instant,
 }
 
-pub(crate) fn get_location() -> Result<String> {
-    let mut args = std::env::args();
-    args.next();
-    let first = match args.next() {
-        Some(addr) => addr,
-        _ => {
-            return Err(anyhow!("no argument provided"));
+impl Blink {
+    pub(crate) fn tick(&mut self) {
+        if self.since.elapsed() >= Duration::from_millis(500) {
+            self.on = !self.on;
+            self.since = Instant::now();
         }
-    };
-    Ok(first)
+    }
+    pub(crate) fn on(&self) -> bool {
+        self.on
+    }
+}
+
+impl Default for Blink {
+    fn default() -> Self {
+        Blink {
+            on: false,
+            since: Instant::now(),
+        }
+    }
 }
 
 ///Checks for ESC of Ctrl+C event
@@ -54,30 +49,3 @@ pub(crate) fn should_exit(event: &Event) -> Result<bool> {
     }
     Ok(false)
 }
-
-pub(crate) async fn get_name_from_id(context: Rc<Context>, id: u64) -> Result<String> {
-    let apiloc = format!(
-        "https://{}/api/user/get_profile_id?user_id={}",
-        context.location, id
-    );
-    let response = context.client.get(apiloc).send().await?;
-    let response: serde_json::Value = response.json().await?;
-    if let Some(result) = response["name"].as_str() {
-        return Ok(result.to_string());
-    }
-    Err(anyhow!("Opponent has no name"))
-}
-
-pub(crate) async fn get_id_from_name(context: Rc<Context>, name: &String) -> Result<i64> {
-    let apiloc = format!(
-        "https://{}/api/user/get_profile_name?profile_name={}",
-        context.location, name
-    );
-    let response = context.client.get(apiloc).send().await?;
-    let response: serde_json::Value = response.json().await?;
-    let result: i64 = match response["id"].as_i64() {
-        Some(id) => id,
-        _ => return Err(anyhow!("Friend not found")),
-    };
-    Ok(result)
-}